@@ -0,0 +1,87 @@
+// Fires many `POST /orders` for the same item at once against a running server and checks that no
+// more units were sold than were in stock. Point the server at a local Postgres first, then run
+//
+//     API_URL=http://localhost:8000 ITEM_ID=<item id> CONCURRENCY=50 \
+//         cargo run --example concurrent_orders
+//
+// Orders are placed as guest cash-on-delivery pick ups so they never expire and don't need a
+// payment gateway. Use an item with only a few units left to make the race likely.
+
+use futures::future::join_all;
+use reqwest::{Client, StatusCode};
+use serde_json::{json, Value};
+
+async fn available_stock(client: &Client, api_url: &str, item_id: &str) -> i64 {
+    let item = client
+        .get(format!("{api_url}/items/{item_id}"))
+        .send()
+        .await
+        .expect("failed to fetch the item")
+        .json::<Value>()
+        .await
+        .expect("the item response is not json");
+
+    let lifetime_stock = item["data"]["lifetime_stock"].as_i64().unwrap_or(0);
+    let amount_sold = item["data"]["amount_sold"].as_i64().unwrap_or(0);
+
+    lifetime_stock - amount_sold
+}
+
+#[actix_rt::main]
+async fn main() {
+    let api_url = std::env::var("API_URL").unwrap_or_else(|_| "http://localhost:8000".to_string());
+    let item_id = std::env::var("ITEM_ID").expect("ITEM_ID must be set");
+    let concurrency = std::env::var("CONCURRENCY")
+        .unwrap_or_else(|_| "20".to_string())
+        .parse::<usize>()
+        .expect("CONCURRENCY must be a number");
+
+    let client = Client::new();
+
+    let stock_before = available_stock(&client, &api_url, &item_id).await;
+
+    println!("{stock_before} units available, placing {concurrency} orders at once");
+
+    let order = json!({
+        "data": [{
+            "items": [{ "item_id": item_id, "amount": 1 }],
+            "delivery_type": "school_pickup",
+            "receiver_name": "Concurrency Test",
+            "payment_method": "cod",
+            "contact_email": "concurrency.test@example.com",
+        }]
+    });
+
+    let requests = (0..concurrency).map(|_| {
+        client
+            .post(format!("{api_url}/orders"))
+            .json(&order)
+            .send()
+    });
+
+    let mut accepted = 0;
+    let mut rejected = 0;
+    let mut failed = 0;
+
+    for res in join_all(requests).await {
+        match res.map(|res| res.status()) {
+            Ok(StatusCode::OK) => accepted += 1,
+            Ok(StatusCode::BAD_REQUEST) => rejected += 1,
+            // the order may still have been placed, e.g. when the invoice email can't be sent
+            _ => failed += 1,
+        }
+    }
+
+    let stock_after = available_stock(&client, &api_url, &item_id).await;
+    let sold = stock_before - stock_after;
+
+    println!("{accepted} accepted, {rejected} rejected, {failed} failed");
+    println!("{sold} units sold, {stock_after} left");
+
+    if stock_after < 0 || sold > stock_before.max(0) || accepted > stock_before.max(0) {
+        eprintln!("🔥 the item was oversold");
+        std::process::exit(1);
+    }
+
+    println!("✅ no overselling");
+}
@@ -0,0 +1,8 @@
+-- Distinguish shop owners (who can invite and remove managers) from regular managers.
+ALTER TABLE shop_managers ADD COLUMN IF NOT EXISTS is_owner BOOLEAN NOT NULL DEFAULT FALSE;
+
+-- Every manager added by hand before this migration keeps full control of their shop.
+UPDATE shop_managers SET is_owner = TRUE;
+
+CREATE UNIQUE INDEX IF NOT EXISTS shop_managers_shop_id_user_id_key
+    ON shop_managers (shop_id, user_id);
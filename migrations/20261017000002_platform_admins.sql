@@ -0,0 +1,2 @@
+-- Platform admins (the student council) hold every shop role in every shop.
+ALTER TABLE users ADD COLUMN IF NOT EXISTS is_admin BOOLEAN NOT NULL DEFAULT FALSE;
@@ -0,0 +1,8 @@
+-- New order states; they have to be committed before any row can use them.
+ALTER TYPE order_status ADD VALUE IF NOT EXISTS 'awaiting_payment';
+ALTER TYPE order_status ADD VALUE IF NOT EXISTS 'paid';
+ALTER TYPE order_status ADD VALUE IF NOT EXISTS 'verified';
+ALTER TYPE order_status ADD VALUE IF NOT EXISTS 'packed';
+ALTER TYPE order_status ADD VALUE IF NOT EXISTS 'shipped';
+ALTER TYPE order_status ADD VALUE IF NOT EXISTS 'ready_for_pickup';
+ALTER TYPE order_status ADD VALUE IF NOT EXISTS 'refunded';
@@ -0,0 +1,26 @@
+-- Move existing orders off the legacy states, using the payment flags to tell how far they got.
+UPDATE orders
+SET shipment_status = CASE
+    WHEN shipment_status = 'not_shipped_out' AND is_verified THEN 'verified'::order_status
+    WHEN shipment_status = 'not_shipped_out' AND is_paid THEN 'paid'::order_status
+    WHEN shipment_status = 'not_shipped_out' THEN 'awaiting_payment'::order_status
+    WHEN shipment_status = 'pending' AND delivery_type = 'delivery' THEN 'shipped'::order_status
+    WHEN shipment_status = 'pending' THEN 'ready_for_pickup'::order_status
+    ELSE shipment_status
+END;
+
+ALTER TABLE orders ALTER COLUMN shipment_status SET DEFAULT 'awaiting_payment';
+
+-- Every status change of an order, a NULL actor means the system made the change.
+CREATE TABLE IF NOT EXISTS order_status_history (
+    id UUID PRIMARY KEY DEFAULT gen_random_uuid(),
+    created_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    order_id UUID NOT NULL REFERENCES orders (id) ON DELETE CASCADE,
+    from_status order_status,
+    to_status order_status NOT NULL,
+    actor_id UUID REFERENCES users (id) ON DELETE SET NULL,
+    note TEXT
+);
+
+CREATE INDEX IF NOT EXISTS order_status_history_order_id_idx
+    ON order_status_history (order_id, created_at);
@@ -0,0 +1,11 @@
+-- Unpaid orders hold their items until this time, NULL holds them until the order is canceled.
+ALTER TABLE orders ADD COLUMN IF NOT EXISTS reserved_until TIMESTAMPTZ;
+
+-- Existing unpaid PromptPay orders get the default 15 minute hold, so the sweeper releases them.
+UPDATE orders
+SET reserved_until = created_at + INTERVAL '15 minute'
+WHERE shipment_status = 'awaiting_payment' AND payment_method = 'promptpay';
+
+CREATE INDEX IF NOT EXISTS orders_expired_reservations_idx
+    ON orders (reserved_until)
+    WHERE shipment_status = 'awaiting_payment';
@@ -0,0 +1,15 @@
+-- Carts of buyers who are not signed in, they are merged into the user's cart on sign in.
+CREATE TABLE IF NOT EXISTS guest_carts (
+    id UUID PRIMARY KEY DEFAULT gen_random_uuid(),
+    created_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    expires_at TIMESTAMPTZ NOT NULL
+);
+
+CREATE TABLE IF NOT EXISTS guest_cart_items (
+    id UUID PRIMARY KEY DEFAULT gen_random_uuid(),
+    created_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    guest_cart_id UUID NOT NULL REFERENCES guest_carts (id) ON DELETE CASCADE,
+    item_id UUID NOT NULL REFERENCES items (id) ON DELETE CASCADE,
+    amount INT8 NOT NULL CHECK (amount > 0),
+    UNIQUE (guest_cart_id, item_id)
+);
@@ -0,0 +1,28 @@
+CREATE TYPE discount_type AS ENUM ('percent', 'fixed');
+
+-- Shop-scoped codes a buyer can enter when placing an order.
+CREATE TABLE IF NOT EXISTS discount_codes (
+    id UUID PRIMARY KEY DEFAULT gen_random_uuid(),
+    created_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    shop_id UUID NOT NULL REFERENCES shops (id) ON DELETE CASCADE,
+    code TEXT NOT NULL,
+    discount_type discount_type NOT NULL,
+    value INT8 NOT NULL CHECK (value > 0),
+    min_spend INT8,
+    max_uses INT8,
+    max_uses_per_user INT8,
+    starts_at TIMESTAMPTZ,
+    ends_at TIMESTAMPTZ,
+    is_active BOOLEAN NOT NULL DEFAULT TRUE,
+    -- When all three are empty the code applies to every item in the shop.
+    item_ids UUID[] NOT NULL DEFAULT '{}',
+    listing_ids UUID[] NOT NULL DEFAULT '{}',
+    collection_ids UUID[] NOT NULL DEFAULT '{}'
+);
+
+CREATE UNIQUE INDEX IF NOT EXISTS discount_codes_shop_id_code_key
+    ON discount_codes (shop_id, LOWER(code));
+
+ALTER TABLE orders
+    ADD COLUMN IF NOT EXISTS discount_code_id UUID REFERENCES discount_codes (id),
+    ADD COLUMN IF NOT EXISTS discount_amount INT8 NOT NULL DEFAULT 0;
@@ -0,0 +1,5 @@
+-- Per-shop shipping rules, NULL keeps the old flat 70 baht fee.
+ALTER TABLE shops ADD COLUMN IF NOT EXISTS shipping_rules JSONB;
+
+-- Only needed for shops that charge by weight.
+ALTER TABLE items ADD COLUMN IF NOT EXISTS weight_grams INT8;
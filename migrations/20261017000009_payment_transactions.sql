@@ -0,0 +1,25 @@
+CREATE TYPE payment_transaction_status AS ENUM ('accepted', 'duplicate', 'failed', 'rejected');
+
+-- Every payment callback received from a provider, whether or not it settled the order.
+CREATE TABLE IF NOT EXISTS payment_transactions (
+    id UUID PRIMARY KEY DEFAULT gen_random_uuid(),
+    created_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    order_id UUID REFERENCES orders (id) ON DELETE SET NULL,
+    provider TEXT NOT NULL,
+    reference_no TEXT NOT NULL,
+    provider_reference_no TEXT,
+    amount_satang INT8 NOT NULL,
+    result_code TEXT,
+    is_retry BOOLEAN NOT NULL DEFAULT FALSE,
+    status payment_transaction_status NOT NULL,
+    detail TEXT,
+    payload JSONB
+);
+
+CREATE INDEX IF NOT EXISTS payment_transactions_order_id_idx
+    ON payment_transactions (order_id, created_at);
+
+-- A provider payment can settle an order only once, however many times it is retried.
+CREATE UNIQUE INDEX IF NOT EXISTS payment_transactions_accepted_idx
+    ON payment_transactions (provider, provider_reference_no)
+    WHERE status = 'accepted';
@@ -0,0 +1,3 @@
+-- Shops that turn this off are paid straight to their promptpay_number with a QR code made
+-- by the API, and confirm the payment from the slip.
+ALTER TABLE shops ADD COLUMN IF NOT EXISTS use_payment_gateway BOOLEAN NOT NULL DEFAULT TRUE;
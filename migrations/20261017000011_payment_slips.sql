@@ -0,0 +1,18 @@
+CREATE TYPE payment_slip_status AS ENUM ('pending', 'approved', 'rejected');
+
+-- Every slip a buyer uploads, reviewed by a shop manager before the order is verified.
+CREATE TABLE IF NOT EXISTS payment_slips (
+    id UUID PRIMARY KEY DEFAULT gen_random_uuid(),
+    created_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    order_id UUID NOT NULL REFERENCES orders (id) ON DELETE CASCADE,
+    uploader_id UUID REFERENCES users (id) ON DELETE SET NULL,
+    slip_url TEXT NOT NULL,
+    amount_claimed INT8,
+    status payment_slip_status NOT NULL DEFAULT 'pending',
+    reviewer_id UUID REFERENCES users (id) ON DELETE SET NULL,
+    reviewed_at TIMESTAMPTZ,
+    rejection_reason TEXT
+);
+
+CREATE INDEX IF NOT EXISTS payment_slips_order_id_idx ON payment_slips (order_id);
+CREATE INDEX IF NOT EXISTS payment_slips_status_idx ON payment_slips (status, created_at);
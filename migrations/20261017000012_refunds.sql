@@ -0,0 +1,37 @@
+CREATE TYPE refund_method AS ENUM ('payment_provider', 'bank_transfer', 'cash');
+
+ALTER TABLE order_items ADD COLUMN IF NOT EXISTS refunded_amount INT8 NOT NULL DEFAULT 0;
+
+-- Money given back on an order, either for some of its lines or for the whole order.
+CREATE TABLE IF NOT EXISTS refunds (
+    id UUID PRIMARY KEY DEFAULT gen_random_uuid(),
+    created_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    order_id UUID NOT NULL REFERENCES orders (id) ON DELETE CASCADE,
+    amount INT8 NOT NULL CHECK (amount >= 0),
+    method refund_method NOT NULL,
+    reference TEXT,
+    reason TEXT,
+    actor_id UUID REFERENCES users (id) ON DELETE SET NULL
+);
+
+CREATE TABLE IF NOT EXISTS refund_items (
+    id UUID PRIMARY KEY DEFAULT gen_random_uuid(),
+    refund_id UUID NOT NULL REFERENCES refunds (id) ON DELETE CASCADE,
+    order_item_id UUID NOT NULL REFERENCES order_items (id) ON DELETE CASCADE,
+    amount INT8 NOT NULL CHECK (amount > 0),
+    is_restocked BOOLEAN NOT NULL DEFAULT TRUE
+);
+
+CREATE INDEX IF NOT EXISTS refunds_order_id_idx ON refunds (order_id);
+CREATE INDEX IF NOT EXISTS refund_items_refund_id_idx ON refund_items (refund_id);
+
+-- Refunded orders now count as sold and only give their items back through the stock ledger,
+-- so the items of orders refunded before this keep being available.
+INSERT INTO item_stock_updates (item_id, stock_added)
+SELECT order_items.item_id, order_items.amount
+FROM order_items
+INNER JOIN orders ON order_items.order_id = orders.id
+WHERE orders.shipment_status = 'refunded';
+
+UPDATE order_items SET refunded_amount = amount
+WHERE order_id IN (SELECT id FROM orders WHERE shipment_status = 'refunded');
@@ -0,0 +1,11 @@
+-- Cash taken by a shop manager when a cash on delivery order is handed over.
+CREATE TABLE IF NOT EXISTS cash_collections (
+    id UUID PRIMARY KEY DEFAULT gen_random_uuid(),
+    created_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    order_id UUID NOT NULL REFERENCES orders (id) ON DELETE CASCADE,
+    amount INT8 NOT NULL,
+    collector_id UUID REFERENCES users (id) ON DELETE SET NULL
+);
+
+CREATE INDEX IF NOT EXISTS cash_collections_order_id_idx ON cash_collections (order_id);
+CREATE INDEX IF NOT EXISTS cash_collections_created_at_idx ON cash_collections (created_at);
@@ -0,0 +1,4 @@
+-- Scanned at the booth to look items up for point of sale orders.
+ALTER TABLE items ADD COLUMN IF NOT EXISTS barcode TEXT;
+
+CREATE UNIQUE INDEX IF NOT EXISTS items_barcode_idx ON items (barcode) WHERE barcode IS NOT NULL;
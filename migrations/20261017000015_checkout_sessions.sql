@@ -0,0 +1,21 @@
+-- Orders from several shops checked out together and paid with one payment under ref_id.
+CREATE TABLE IF NOT EXISTS checkout_sessions (
+    id UUID PRIMARY KEY DEFAULT gen_random_uuid(),
+    created_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    ref_id TEXT NOT NULL UNIQUE,
+    buyer_id UUID REFERENCES users (id) ON DELETE SET NULL,
+    payment_method payment_method NOT NULL,
+    total_price INT8 NOT NULL,
+    qr_code_file TEXT
+);
+
+ALTER TABLE orders
+    ADD COLUMN IF NOT EXISTS checkout_session_id UUID REFERENCES checkout_sessions (id) ON DELETE SET NULL;
+
+CREATE INDEX IF NOT EXISTS orders_checkout_session_id_idx
+    ON orders (checkout_session_id)
+    WHERE checkout_session_id IS NOT NULL;
+
+-- Payments for a checkout session settle all of its orders, so they aren't tied to one order.
+ALTER TABLE payment_transactions
+    ADD COLUMN IF NOT EXISTS checkout_session_id UUID REFERENCES checkout_sessions (id) ON DELETE SET NULL;
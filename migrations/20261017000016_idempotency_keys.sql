@@ -0,0 +1,17 @@
+-- Responses to creation requests sent with an Idempotency-Key header, replayed when the same
+-- request is retried. status_code stays NULL while the first request is still being handled.
+CREATE TABLE IF NOT EXISTS idempotency_keys (
+    id UUID PRIMARY KEY DEFAULT gen_random_uuid(),
+    created_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    expires_at TIMESTAMPTZ NOT NULL,
+    key TEXT NOT NULL,
+    method TEXT NOT NULL,
+    path TEXT NOT NULL,
+    request_hash TEXT NOT NULL,
+    status_code INT4,
+    content_type TEXT,
+    response_body BYTEA,
+    UNIQUE (key, method, path)
+);
+
+CREATE INDEX IF NOT EXISTS idempotency_keys_expires_at_idx ON idempotency_keys (expires_at);
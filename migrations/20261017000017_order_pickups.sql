@@ -0,0 +1,19 @@
+-- School pickup orders are collected by showing this code at the booth.
+ALTER TABLE orders ADD COLUMN IF NOT EXISTS pickup_code TEXT;
+
+UPDATE orders
+SET pickup_code = UPPER(SUBSTRING(MD5(RANDOM()::TEXT || id::TEXT) FROM 1 FOR 8))
+WHERE delivery_type = 'pick_up' AND pickup_code IS NULL;
+
+CREATE UNIQUE INDEX IF NOT EXISTS orders_shop_id_pickup_code_idx
+    ON orders (shop_id, pickup_code)
+    WHERE pickup_code IS NOT NULL;
+
+-- Who handed each pickup order over.
+CREATE TABLE IF NOT EXISTS order_pickups (
+    id UUID PRIMARY KEY DEFAULT gen_random_uuid(),
+    created_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    order_id UUID NOT NULL UNIQUE REFERENCES orders (id) ON DELETE CASCADE,
+    handed_over_by UUID REFERENCES users (id) ON DELETE SET NULL,
+    note TEXT
+);
@@ -0,0 +1,6 @@
+CREATE TYPE shipping_carrier AS ENUM ('thailand_post', 'kerry', 'flash', 'j_and_t', 'other');
+
+-- Set when a delivery order is moved to shipped.
+ALTER TABLE orders
+    ADD COLUMN IF NOT EXISTS shipping_carrier shipping_carrier,
+    ADD COLUMN IF NOT EXISTS tracking_number TEXT;
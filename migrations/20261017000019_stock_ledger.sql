@@ -0,0 +1,11 @@
+CREATE TYPE stock_update_reason AS ENUM ('initial', 'restock', 'write_off', 'correction', 'refund');
+
+-- Stock is changed by adding signed lines to the ledger, which now records why and by whom.
+ALTER TABLE item_stock_updates
+    ADD COLUMN IF NOT EXISTS created_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    ADD COLUMN IF NOT EXISTS reason stock_update_reason NOT NULL DEFAULT 'initial',
+    ADD COLUMN IF NOT EXISTS actor_id UUID REFERENCES users (id) ON DELETE SET NULL,
+    ADD COLUMN IF NOT EXISTS note TEXT;
+
+CREATE INDEX IF NOT EXISTS item_stock_updates_item_id_created_at_idx
+    ON item_stock_updates (item_id, created_at);
@@ -0,0 +1,12 @@
+-- Managers are emailed once an item's available stock drops to this.
+ALTER TABLE items ADD COLUMN IF NOT EXISTS low_stock_threshold INT8 CHECK (low_stock_threshold >= 0);
+
+CREATE TYPE stock_alert_level AS ENUM ('low', 'sold_out');
+
+-- The level each item was last reported at, so managers aren't emailed about it on every check.
+-- Rows are removed once the item is restocked above its threshold.
+CREATE TABLE IF NOT EXISTS item_stock_alerts (
+    item_id UUID PRIMARY KEY REFERENCES items (id) ON DELETE CASCADE,
+    level stock_alert_level NOT NULL,
+    reported_at TIMESTAMPTZ NOT NULL DEFAULT NOW()
+);
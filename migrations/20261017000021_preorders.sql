@@ -0,0 +1,21 @@
+-- Units that can be ordered beyond the stock while the preorder window is open, no limit if NULL.
+ALTER TABLE items ADD COLUMN IF NOT EXISTS preorder_cap INT8 CHECK (preorder_cap >= 0);
+
+-- Lines ordered while the item's preorder window was open, fulfilled once a batch arrives.
+ALTER TABLE order_items
+    ADD COLUMN IF NOT EXISTS is_preorder BOOLEAN NOT NULL DEFAULT FALSE,
+    ADD COLUMN IF NOT EXISTS preorder_arrived_at TIMESTAMPTZ;
+
+ALTER TABLE orders ADD COLUMN IF NOT EXISTS is_preorder BOOLEAN NOT NULL DEFAULT FALSE;
+
+-- Orders placed during a preorder window before this count as preorders.
+UPDATE order_items SET is_preorder = TRUE
+FROM orders, items
+WHERE order_items.order_id = orders.id
+    AND order_items.item_id = items.id
+    AND (items.preorder_start IS NOT NULL OR items.preorder_end IS NOT NULL)
+    AND (items.preorder_start IS NULL OR orders.created_at >= items.preorder_start)
+    AND (items.preorder_end IS NULL OR orders.created_at <= items.preorder_end);
+
+UPDATE orders SET is_preorder = TRUE
+WHERE id IN (SELECT order_id FROM order_items WHERE is_preorder);
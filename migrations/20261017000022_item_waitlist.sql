@@ -0,0 +1,15 @@
+-- Users waiting for an item to come back in stock. A subscription is armed while the item is sold
+-- out and emailed when a stock update brings it back, until the user unsubscribes.
+CREATE TABLE IF NOT EXISTS item_waitlist (
+    id UUID PRIMARY KEY DEFAULT gen_random_uuid(),
+    created_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    item_id UUID NOT NULL REFERENCES items (id) ON DELETE CASCADE,
+    user_id UUID NOT NULL REFERENCES users (id) ON DELETE CASCADE,
+    is_armed BOOLEAN NOT NULL DEFAULT FALSE,
+    armed_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    last_notified_at TIMESTAMPTZ,
+    unsubscribe_token UUID NOT NULL UNIQUE DEFAULT gen_random_uuid(),
+    UNIQUE (item_id, user_id)
+);
+
+CREATE INDEX IF NOT EXISTS item_waitlist_armed_idx ON item_waitlist (item_id) WHERE is_armed;
@@ -0,0 +1,9 @@
+-- Most units of an item, or of all the items of a listing together, one order or one buyer
+-- across their orders that aren't canceled can have, no limit if NULL.
+ALTER TABLE items
+    ADD COLUMN IF NOT EXISTS max_per_order INT8 CHECK (max_per_order > 0),
+    ADD COLUMN IF NOT EXISTS max_per_user INT8 CHECK (max_per_user > 0);
+
+ALTER TABLE listings
+    ADD COLUMN IF NOT EXISTS max_per_order INT8 CHECK (max_per_order > 0),
+    ADD COLUMN IF NOT EXISTS max_per_user INT8 CHECK (max_per_user > 0);
@@ -0,0 +1,27 @@
+-- What the buyer paid for each line: the unit price when the order was placed and the part of
+-- the order's discount that went to the line. Refunds are worked out from these.
+ALTER TABLE order_items
+    ADD COLUMN IF NOT EXISTS unit_price INT8,
+    ADD COLUMN IF NOT EXISTS discount_amount INT8 NOT NULL DEFAULT 0;
+
+-- Orders placed before this only have the price the item has now, and their discount is split
+-- over all of their lines by price.
+UPDATE order_items SET unit_price = LEAST(items.price, items.discounted_price)
+FROM items
+WHERE order_items.item_id = items.id AND order_items.unit_price IS NULL;
+
+UPDATE order_items
+SET discount_amount = orders.discount_amount * order_items.unit_price * order_items.amount / subtotals.subtotal
+FROM orders, (
+    SELECT order_id, SUM(unit_price * amount) AS subtotal FROM order_items GROUP BY order_id
+) AS subtotals
+WHERE order_items.order_id = orders.id
+    AND subtotals.order_id = orders.id
+    AND orders.discount_amount > 0
+    AND subtotals.subtotal > 0
+    AND NOT EXISTS (
+        SELECT 1 FROM order_items AS split
+        WHERE split.order_id = orders.id AND split.discount_amount > 0
+    );
+
+ALTER TABLE order_items ALTER COLUMN unit_price SET NOT NULL;
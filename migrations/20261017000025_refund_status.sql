@@ -0,0 +1,8 @@
+CREATE TYPE refund_status AS ENUM ('pending', 'completed', 'failed');
+
+-- Provider refunds are saved as pending before the provider is asked, so a refund that went
+-- through is never lost when saving the rest of it fails. Refunds made before this all went
+-- through.
+ALTER TABLE refunds ADD COLUMN IF NOT EXISTS status refund_status NOT NULL DEFAULT 'completed';
+
+CREATE INDEX IF NOT EXISTS refunds_pending_idx ON refunds (order_id) WHERE status = 'pending';
@@ -0,0 +1,3 @@
+-- When the request holding an unfinished key started. A key whose request was dropped without an
+-- answer, e.g. by a worker restart, can be claimed again once this is old enough.
+ALTER TABLE idempotency_keys ADD COLUMN IF NOT EXISTS locked_at TIMESTAMPTZ NOT NULL DEFAULT NOW();
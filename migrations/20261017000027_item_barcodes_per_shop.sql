@@ -0,0 +1,15 @@
+-- The shop an item is sold by, copied from its listing so barcodes can be unique per shop. Two
+-- shops selling the same retail product scan the same barcode.
+ALTER TABLE items ADD COLUMN IF NOT EXISTS shop_id UUID REFERENCES shops (id);
+
+UPDATE items SET shop_id = listings.shop_id
+FROM listings
+WHERE items.listing_id = listings.id;
+
+ALTER TABLE items ALTER COLUMN shop_id SET NOT NULL;
+
+DROP INDEX IF EXISTS items_barcode_idx;
+
+CREATE UNIQUE INDEX IF NOT EXISTS items_shop_id_barcode_idx
+    ON items (shop_id, barcode)
+    WHERE barcode IS NOT NULL;
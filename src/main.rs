@@ -15,6 +15,7 @@ pub struct AppState {
     db: Pool<Postgres>,
     smtp_credential: Credentials,
     env: utils::common::config::Config,
+    payment_provider: models::order::provider::PaymentGateway,
 }
 
 #[actix_web::main]
@@ -49,6 +50,27 @@ async fn main() -> std::io::Result<()> {
         env.google_email_password.clone(),
     );
 
+    let payment_provider = models::order::provider::PaymentGateway::from_config(&env);
+
+    models::order::reservation::spawn_reservation_sweeper(
+        pool.clone(),
+        std::time::Duration::from_secs(env.reservation_sweep_interval_seconds),
+    );
+
+    models::item::low_stock::spawn_stock_alert_checker(
+        pool.clone(),
+        smtp_credential.clone(),
+        std::time::Duration::from_secs(env.stock_alert_interval_seconds),
+    );
+
+    // runs as often as the low stock check, both follow the stock
+    models::item::waitlist::spawn_waitlist_notifier(
+        pool.clone(),
+        smtp_credential.clone(),
+        env.api_base_url.clone(),
+        std::time::Duration::from_secs(env.stock_alert_interval_seconds),
+    );
+
     // let mut builder = SslAcceptor::mozilla_intermediate(SslMethod::tls()).unwrap();
 
     // builder
@@ -60,20 +82,9 @@ async fn main() -> std::io::Result<()> {
 
     HttpServer::new(move || {
         let cors = Cors::default()
-            .allowed_origin_fn(|origin, req_head| {
-                // if req_head.method == "POST"  and it is fetching /orders/webhook
-                // allow all origin
-                // dbg!(&req_head);
-                // dbg!(&origin);
-                // dbg!(req_head.uri.path() == "/orders/webhook");
-                // dbg!(req_head.method.as_str());
-
-                if (req_head.method.as_str() == "POST" || req_head.method.as_str() == "OPTIONS")
-                    && req_head.uri.path() == "/orders/webhook"
-                {
-                    // dbg!("allow all origin");
-                    return true;
-                }
+            .allowed_origin_fn(|origin, _req_head| {
+                // the GBPrimePay webhook is a server to server call that sends no origin, and its
+                // payments are checked with GBPrimePay so it needs no exception here
                 origin.as_bytes().ends_with(b".skkornor.org")
                     || origin.as_bytes().ends_with(b".gbprimepay.com")
                     || origin.as_bytes().ends_with(b".globalprimepay.com")
@@ -108,6 +119,8 @@ async fn main() -> std::io::Result<()> {
                 header::ACCEPT,
                 // Custom headers
                 header::HeaderName::from_lowercase(b"x-api-key").unwrap(),
+                header::HeaderName::from_lowercase(b"x-guest-cart").unwrap(),
+                header::HeaderName::from_lowercase(b"idempotency-key").unwrap(),
             ])
             .supports_credentials();
         App::new()
@@ -115,6 +128,7 @@ async fn main() -> std::io::Result<()> {
                 db: pool.clone(),
                 env: env.clone(),
                 smtp_credential: smtp_credential.clone(),
+                payment_provider: payment_provider.clone(),
             }))
             // .service(web::scope("/api/v1").configure(routes::config))
             .configure(routes::config)
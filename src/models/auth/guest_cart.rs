@@ -0,0 +1,285 @@
+use actix_web::{dev::Payload, Error as ActixWebError};
+use actix_web::{FromRequest, HttpRequest};
+use chrono::{Duration, Utc};
+use futures::future::{ready, Ready};
+use jsonwebtoken::{decode, encode, DecodingKey, EncodingKey, Header, Validation};
+use mysk_lib::models::common::requests::FetchLevel;
+use serde::{Deserialize, Serialize};
+use sqlx::{types::Uuid, PgPool, Row};
+
+use crate::models::item::{stock::ItemStock, CartItem, Item};
+use crate::AppState;
+
+pub const GUEST_CART_HEADER: &str = "x-guest-cart";
+
+// guest carts are kept for as long as their token is valid
+const GUEST_CART_MAX_AGE_DAYS: i64 = 30;
+
+// signed with the same secret as user tokens, `kind` keeps the two from being swapped
+#[derive(Debug, Serialize, Deserialize)]
+struct GuestCartClaims {
+    sub: String,
+    kind: String,
+    iat: usize,
+    exp: usize,
+}
+
+const GUEST_CART_KIND: &str = "guest_cart";
+
+pub struct GuestCart {
+    pub id: Uuid,
+}
+
+impl GuestCart {
+    pub async fn create(pool: &PgPool) -> Result<Self, sqlx::Error> {
+        // expired carts can't be reached through their token anymore, cleared out as new ones
+        // are made so they don't pile up
+        sqlx::query("DELETE FROM guest_carts WHERE expires_at <= NOW()")
+            .execute(pool)
+            .await?;
+
+        let id = sqlx::query(
+            r#"
+            INSERT INTO guest_carts (expires_at)
+            VALUES (NOW() + make_interval(days => $1))
+            RETURNING id
+            "#,
+        )
+        .bind(GUEST_CART_MAX_AGE_DAYS as i32)
+        .fetch_one(pool)
+        .await?
+        .get::<Uuid, _>("id");
+
+        Ok(Self { id })
+    }
+
+    // the cart behind a token outlives it when it was merged on sign in or has expired, a new
+    // cart is started then and the caller hands out its token in place of the old one
+    pub async fn find_or_create(
+        pool: &PgPool,
+        guest_cart: Option<Self>,
+    ) -> Result<Self, sqlx::Error> {
+        if let Some(guest_cart) = guest_cart {
+            let is_active = sqlx::query(
+                r#"
+                SELECT EXISTS (SELECT 1 FROM guest_carts WHERE id = $1 AND expires_at > NOW())
+                AS is_active
+                "#,
+            )
+            .bind(guest_cart.id)
+            .fetch_one(pool)
+            .await?
+            .get::<bool, _>("is_active");
+
+            if is_active {
+                return Ok(guest_cart);
+            }
+        }
+
+        Self::create(pool).await
+    }
+
+    pub fn token(&self, jwt_secret: &str) -> Result<String, jsonwebtoken::errors::Error> {
+        let now = Utc::now();
+        let claims = GuestCartClaims {
+            sub: self.id.to_string(),
+            kind: GUEST_CART_KIND.to_string(),
+            iat: now.timestamp() as usize,
+            exp: (now + Duration::days(GUEST_CART_MAX_AGE_DAYS)).timestamp() as usize,
+        };
+
+        encode(
+            &Header::default(),
+            &claims,
+            &EncodingKey::from_secret(jwt_secret.as_bytes()),
+        )
+    }
+
+    pub fn from_token(token: &str, jwt_secret: &str) -> Option<Self> {
+        let claims = decode::<GuestCartClaims>(
+            token,
+            &DecodingKey::from_secret(jwt_secret.as_bytes()),
+            &Validation::default(),
+        )
+        .ok()?
+        .claims;
+
+        if claims.kind != GUEST_CART_KIND {
+            return None;
+        }
+
+        Uuid::parse_str(&claims.sub).ok().map(|id| Self { id })
+    }
+
+    // follows the same rules as the user cart, an amount of 0 removes the item
+    pub async fn set_item_amount(
+        &self,
+        pool: &PgPool,
+        item_id: Uuid,
+        amount: i64,
+    ) -> Result<(), sqlx::Error> {
+        if amount == 0 {
+            sqlx::query("DELETE FROM guest_cart_items WHERE guest_cart_id = $1 AND item_id = $2")
+                .bind(self.id)
+                .bind(item_id)
+                .execute(pool)
+                .await?;
+
+            return Ok(());
+        }
+
+        sqlx::query(
+            r#"
+            INSERT INTO guest_cart_items (guest_cart_id, item_id, amount) VALUES ($1, $2, $3)
+            ON CONFLICT (guest_cart_id, item_id) DO UPDATE SET amount = EXCLUDED.amount
+            "#,
+        )
+        .bind(self.id)
+        .bind(item_id)
+        .bind(amount)
+        .execute(pool)
+        .await?;
+
+        Ok(())
+    }
+
+    pub async fn get_items(
+        &self,
+        pool: &PgPool,
+        level: Option<&FetchLevel>,
+        descendant_fetch_level: Option<&FetchLevel>,
+    ) -> Result<Vec<CartItem>, sqlx::Error> {
+        let res = sqlx::query(
+            r#"
+            SELECT guest_cart_items.item_id, guest_cart_items.amount
+            FROM guest_cart_items
+            INNER JOIN guest_carts ON guest_cart_items.guest_cart_id = guest_carts.id
+            WHERE guest_carts.id = $1 AND guest_carts.expires_at > NOW()
+            "#,
+        )
+        .bind(self.id)
+        .fetch_all(pool)
+        .await?;
+
+        let mut items = vec![];
+
+        for row in res {
+            let item_id = row.get::<Uuid, _>("item_id");
+            let amount = row.get::<i64, _>("amount");
+
+            let item = Item::get_by_id(pool, item_id, level, descendant_fetch_level).await?;
+
+            items.push(CartItem { item, amount });
+        }
+
+        Ok(items)
+    }
+
+    // moves the guest cart into the user's cart and deletes it. When both carts hold the same
+    // item the amounts are added up, and every merged amount is capped at the stock that is
+    // currently available; items that are sold out keep whatever the user cart already had.
+    pub async fn merge_into_user_cart(
+        &self,
+        pool: &PgPool,
+        user_id: Uuid,
+    ) -> Result<(), sqlx::Error> {
+        let mut transaction = pool.begin().await?;
+
+        let guest_items = sqlx::query(
+            r#"
+            SELECT guest_cart_items.item_id, guest_cart_items.amount
+            FROM guest_cart_items
+            INNER JOIN guest_carts ON guest_cart_items.guest_cart_id = guest_carts.id
+            WHERE guest_carts.id = $1 AND guest_carts.expires_at > NOW()
+            "#,
+        )
+        .bind(self.id)
+        .fetch_all(transaction.as_mut())
+        .await?
+        .into_iter()
+        .map(|row| (row.get::<Uuid, _>("item_id"), row.get::<i64, _>("amount")))
+        .collect::<Vec<(Uuid, i64)>>();
+
+        let item_ids = guest_items
+            .iter()
+            .map(|(item_id, _)| *item_id)
+            .collect::<Vec<Uuid>>();
+
+        let stocks = ItemStock::get_by_item_ids(transaction.as_mut(), &item_ids).await?;
+
+        for (item_id, guest_amount) in guest_items {
+            let user_amount = sqlx::query(
+                "SELECT amount FROM user_cart_items WHERE user_id = $1 AND item_id = $2",
+            )
+            .bind(user_id)
+            .bind(item_id)
+            .fetch_optional(transaction.as_mut())
+            .await?
+            .map(|row| row.get::<i64, _>("amount"))
+            .unwrap_or(0);
+
+            let available = stocks
+                .get(&item_id)
+                .map(|stock| stock.available())
+                .unwrap_or(0);
+
+            let amount = (user_amount + guest_amount).min(available).max(user_amount);
+
+            if amount == 0 || amount == user_amount {
+                continue;
+            }
+
+            if user_amount == 0 {
+                sqlx::query(
+                    "INSERT INTO user_cart_items (user_id, item_id, amount) VALUES ($1, $2, $3)",
+                )
+                .bind(user_id)
+                .bind(item_id)
+                .bind(amount)
+                .execute(transaction.as_mut())
+                .await?;
+            } else {
+                sqlx::query(
+                    "UPDATE user_cart_items SET amount = $1 WHERE user_id = $2 AND item_id = $3",
+                )
+                .bind(amount)
+                .bind(user_id)
+                .bind(item_id)
+                .execute(transaction.as_mut())
+                .await?;
+            }
+        }
+
+        sqlx::query("DELETE FROM guest_carts WHERE id = $1")
+            .bind(self.id)
+            .execute(transaction.as_mut())
+            .await?;
+
+        transaction.commit().await?;
+
+        Ok(())
+    }
+}
+
+// the guest cart named by the `X-Guest-Cart` header, if its token is valid
+pub struct OptionalGuestCart(pub Option<GuestCart>);
+
+impl FromRequest for OptionalGuestCart {
+    type Error = ActixWebError;
+    type Future = Ready<Result<Self, Self::Error>>;
+
+    fn from_request(req: &HttpRequest, _: &mut Payload) -> Self::Future {
+        let jwt_secret = match req.app_data::<actix_web::web::Data<AppState>>() {
+            Some(state) => state.env.jwt_secret.clone(),
+            None => return ready(Ok(Self(None))),
+        };
+
+        let guest_cart = req
+            .headers()
+            .get(GUEST_CART_HEADER)
+            .and_then(|token| token.to_str().ok())
+            .and_then(|token| GuestCart::from_token(token, &jwt_secret));
+
+        ready(Ok(Self(guest_cart)))
+    }
+}
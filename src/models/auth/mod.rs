@@ -1,2 +1,4 @@
+pub(crate) mod guest_cart;
 pub(crate) mod oauth;
+pub(crate) mod permission;
 pub(crate) mod user;
@@ -0,0 +1,362 @@
+use actix_web::error::{ErrorForbidden, ErrorInternalServerError, ErrorNotFound};
+use actix_web::{dev::Payload, http::StatusCode, Error as ActixWebError};
+use actix_web::{web, FromRequest, HttpRequest};
+use futures::Future as FutureTrait;
+use mysk_lib::models::common::response::{ErrorResponseType, ErrorType};
+use serde::{Deserialize, Serialize};
+use sqlx::{types::Uuid, PgPool, Row};
+
+use std::marker::PhantomData;
+use std::pin::Pin;
+
+use crate::models::shop::db::ShopManagerTable;
+use crate::AppState;
+
+use super::user::User;
+
+// roles are ordered from the least to the most privileged so that they can be compared with `>=`
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Role {
+    Buyer,
+    ShopStaff,
+    ShopOwner,
+    PlatformAdmin,
+}
+
+impl Role {
+    pub async fn is_platform_admin(pool: &PgPool, user_id: Uuid) -> Result<bool, sqlx::Error> {
+        let res = sqlx::query("SELECT is_admin FROM users WHERE id = $1")
+            .bind(user_id)
+            .fetch_optional(pool)
+            .await?;
+
+        Ok(match res {
+            Some(row) => row.get::<bool, _>("is_admin"),
+            None => false,
+        })
+    }
+
+    // the highest role the user holds in the given shop
+    pub async fn for_shop(
+        pool: &PgPool,
+        user_id: Uuid,
+        shop_id: Uuid,
+    ) -> Result<Self, sqlx::Error> {
+        if Self::is_platform_admin(pool, user_id).await? {
+            return Ok(Self::PlatformAdmin);
+        }
+
+        match ShopManagerTable::get_by_shop_id_and_user_id(pool, shop_id, user_id).await {
+            Ok(manager) if manager.is_owner => Ok(Self::ShopOwner),
+            Ok(_) => Ok(Self::ShopStaff),
+            Err(sqlx::Error::RowNotFound) => Ok(Self::Buyer),
+            Err(err) => Err(err),
+        }
+    }
+}
+
+// anything that belongs to a shop and can be used to look up which shop that is
+#[derive(Debug, Clone, Copy)]
+pub enum ShopResource {
+    Shop(Uuid),
+    Listing(Uuid),
+    Item(Uuid),
+    Collection(Uuid),
+    Order(Uuid),
+}
+
+impl ShopResource {
+    // path parameters are checked in this order, so `/shops/{shop_id}/...` always wins
+    fn from_path(req: &HttpRequest) -> Option<Self> {
+        let match_info = req.match_info();
+
+        let resources = [
+            ("shop_id", Self::Shop as fn(Uuid) -> Self),
+            ("order_id", Self::Order),
+            ("item_id", Self::Item),
+            ("listing_id", Self::Listing),
+            ("collection_id", Self::Collection),
+        ];
+
+        for (key, resource) in resources {
+            if let Some(id) = match_info.get(key) {
+                return Uuid::parse_str(id).ok().map(resource);
+            }
+        }
+
+        None
+    }
+
+    pub async fn shop_id(&self, pool: &PgPool) -> Result<Uuid, sqlx::Error> {
+        let (query, id) = match self {
+            Self::Shop(id) => ("SELECT id AS shop_id FROM shops WHERE id = $1", id),
+            Self::Listing(id) => ("SELECT shop_id FROM listings WHERE id = $1", id),
+            Self::Item(id) => (
+                "SELECT listings.shop_id FROM items INNER JOIN listings ON items.listing_id = listings.id WHERE items.id = $1",
+                id,
+            ),
+            Self::Collection(id) => ("SELECT shop_id FROM collections WHERE id = $1", id),
+            Self::Order(id) => ("SELECT shop_id FROM orders WHERE id = $1", id),
+        };
+
+        let res = sqlx::query(query).bind(id).fetch_one(pool).await?;
+
+        Ok(res.get::<Uuid, _>("shop_id"))
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct ShopPermission {
+    pub user_id: Uuid,
+    pub role: Role,
+}
+
+fn forbidden(detail: String, source: &str) -> ActixWebError {
+    ErrorForbidden(ErrorResponseType::new(
+        ErrorType {
+            id: Uuid::new_v4().to_string(),
+            code: 403,
+            error_type: "forbidden".to_string(),
+            detail,
+            source: source.to_string(),
+        },
+        None,
+    ))
+}
+
+fn not_found(detail: String, source: &str) -> ActixWebError {
+    ErrorNotFound(ErrorResponseType::new(
+        ErrorType {
+            id: Uuid::new_v4().to_string(),
+            code: 404,
+            error_type: "entity_not_found".to_string(),
+            detail,
+            source: source.to_string(),
+        },
+        None,
+    ))
+}
+
+fn internal_server_error(detail: String, source: &str) -> ActixWebError {
+    ErrorInternalServerError(ErrorResponseType::new(
+        ErrorType {
+            id: Uuid::new_v4().to_string(),
+            code: 500,
+            error_type: "internal_server_error".to_string(),
+            detail,
+            source: source.to_string(),
+        },
+        None,
+    ))
+}
+
+// used directly by routes whose shop can only be found in the request body
+pub async fn authorize(
+    pool: &PgPool,
+    user_id: Uuid,
+    resource: ShopResource,
+    required: Role,
+    source: &str,
+) -> Result<ShopPermission, ActixWebError> {
+    let shop_id = match resource.shop_id(pool).await {
+        Ok(shop_id) => shop_id,
+        Err(sqlx::Error::RowNotFound) => {
+            return Err(not_found(format!("{:?} not found", resource), source))
+        }
+        Err(err) => return Err(internal_server_error(err.to_string(), source)),
+    };
+
+    let role = match Role::for_shop(pool, user_id, shop_id).await {
+        Ok(role) => role,
+        Err(err) => return Err(internal_server_error(err.to_string(), source)),
+    };
+
+    if role < required {
+        return Err(forbidden(
+            format!(
+                "user {} does not have the {:?} role in shop {}",
+                user_id, required, shop_id
+            ),
+            source,
+        ));
+    }
+
+    Ok(ShopPermission { user_id, role })
+}
+
+// orders without a buyer were placed by guests and stay reachable by anyone who has their id
+pub async fn authorize_order_access(
+    pool: &PgPool,
+    user_id: Option<Uuid>,
+    order_id: Uuid,
+    source: &str,
+) -> Result<Role, ActixWebError> {
+    let res = sqlx::query("SELECT buyer_id FROM orders WHERE id = $1")
+        .bind(order_id)
+        .fetch_one(pool)
+        .await;
+
+    let buyer_id = match res {
+        Ok(res) => res.get::<Option<Uuid>, _>("buyer_id"),
+        Err(sqlx::Error::RowNotFound) => {
+            return Err(not_found(format!("order {} not found", order_id), source))
+        }
+        Err(err) => return Err(internal_server_error(err.to_string(), source)),
+    };
+
+    let user_id = match (buyer_id, user_id) {
+        (None, None) => return Ok(Role::Buyer),
+        (Some(buyer_id), Some(user_id)) if buyer_id == user_id => return Ok(Role::Buyer),
+        (_, Some(user_id)) => user_id,
+        (Some(_), None) => {
+            return Err(forbidden(
+                format!("order {} belongs to another user", order_id),
+                source,
+            ))
+        }
+    };
+
+    match authorize(
+        pool,
+        user_id,
+        ShopResource::Order(order_id),
+        Role::ShopStaff,
+        source,
+    )
+    .await
+    {
+        Ok(permission) => Ok(permission.role),
+        // signed-in users may still look at guest orders, but a failed lookup is still a failure
+        Err(err)
+            if buyer_id.is_none()
+                && err.as_response_error().status_code() == StatusCode::FORBIDDEN =>
+        {
+            Ok(Role::Buyer)
+        }
+        Err(err) => Err(err),
+    }
+}
+
+pub trait RequiredRole {
+    const ROLE: Role;
+}
+
+pub struct Staff;
+
+impl RequiredRole for Staff {
+    const ROLE: Role = Role::ShopStaff;
+}
+
+pub struct Owner;
+
+impl RequiredRole for Owner {
+    const ROLE: Role = Role::ShopOwner;
+}
+
+// resolves the shop from the route's path parameters and makes sure the signed-in user holds at
+// least the role `R` in it, e.g. `RequireShopRole<Staff>` on `/orders/{order_id}`
+pub struct RequireShopRole<R: RequiredRole> {
+    pub permission: ShopPermission,
+    _required: PhantomData<R>,
+}
+
+impl<R: RequiredRole + 'static> FromRequest for RequireShopRole<R> {
+    type Error = ActixWebError;
+    type Future = Pin<Box<dyn FutureTrait<Output = Result<Self, Self::Error>>>>;
+
+    fn from_request(req: &HttpRequest, payload: &mut Payload) -> Self::Future {
+        let source = req.path().to_string();
+
+        let app_state = match req.app_data::<web::Data<AppState>>() {
+            Some(state) => state,
+            None => {
+                return Box::pin(async move {
+                    Err(internal_server_error("AppState not found".to_owned(), &source))
+                })
+            }
+        };
+
+        let pool = app_state.db.clone();
+
+        let resource = match ShopResource::from_path(req) {
+            Some(resource) => resource,
+            None => {
+                return Box::pin(async move {
+                    Err(not_found(
+                        "the route does not point to a shop resource".to_owned(),
+                        &source,
+                    ))
+                })
+            }
+        };
+
+        let user = User::from_request(req, payload);
+
+        Box::pin(async move {
+            let user = user.await?;
+
+            let permission = authorize(&pool, user.id(), resource, R::ROLE, &source).await?;
+
+            Ok(Self {
+                permission,
+                _required: PhantomData,
+            })
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use actix_web::test::TestRequest;
+
+    use super::*;
+
+    #[test]
+    fn roles_are_ordered_by_privilege() {
+        assert!(Role::Buyer < Role::ShopStaff);
+        assert!(Role::ShopStaff < Role::ShopOwner);
+        assert!(Role::ShopOwner < Role::PlatformAdmin);
+        assert!(Role::PlatformAdmin >= Role::ShopStaff);
+    }
+
+    #[test]
+    fn resource_is_taken_from_the_path() {
+        let id = Uuid::new_v4();
+
+        let req = TestRequest::default()
+            .param("item_id", id.to_string())
+            .to_http_request();
+
+        assert!(matches!(
+            ShopResource::from_path(&req),
+            Some(ShopResource::Item(item_id)) if item_id == id
+        ));
+    }
+
+    #[test]
+    fn shop_id_wins_over_other_path_parameters() {
+        let shop_id = Uuid::new_v4();
+
+        let req = TestRequest::default()
+            .param("order_id", Uuid::new_v4().to_string())
+            .param("shop_id", shop_id.to_string())
+            .to_http_request();
+
+        assert!(matches!(
+            ShopResource::from_path(&req),
+            Some(ShopResource::Shop(id)) if id == shop_id
+        ));
+    }
+
+    #[test]
+    fn path_without_a_resource_or_with_a_bad_id() {
+        let req = TestRequest::default().to_http_request();
+        assert!(ShopResource::from_path(&req).is_none());
+
+        let req = TestRequest::default()
+            .param("listing_id", "not-a-uuid")
+            .to_http_request();
+        assert!(ShopResource::from_path(&req).is_none());
+    }
+}
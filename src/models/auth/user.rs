@@ -178,6 +178,15 @@ impl User {
 
         Ok(Self::from_table(pool, user, fetch_level).await?)
     }
+
+    pub fn id(&self) -> Uuid {
+        match self {
+            User::IdOnly(user) => user.id,
+            User::Compact(user) => user.id,
+            User::Default(user) => user.id,
+            User::Detailed(user) => user.id,
+        }
+    }
 }
 
 impl From<UserTable> for User {
@@ -0,0 +1,156 @@
+use std::fmt::Display;
+
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use sqlx::types::Uuid;
+use sqlx::{FromRow, PgConnection, Type};
+
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum DiscountType {
+    Percent,
+    Fixed,
+}
+
+impl Display for DiscountType {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let s = match self {
+            Self::Percent => "percent",
+            Self::Fixed => "fixed",
+        };
+        write!(f, "{}", s)
+    }
+}
+
+impl Type<sqlx::Postgres> for DiscountType {
+    fn type_info() -> sqlx::postgres::PgTypeInfo {
+        sqlx::postgres::PgTypeInfo::with_name("discount_type")
+    }
+}
+
+impl sqlx::Encode<'_, sqlx::Postgres> for DiscountType {
+    fn encode_by_ref(
+        &self,
+        buf: &mut <sqlx::Postgres as sqlx::database::HasArguments<'_>>::ArgumentBuffer,
+    ) -> sqlx::encode::IsNull {
+        let s: String = self.to_string();
+        <String as sqlx::Encode<sqlx::Postgres>>::encode(s, buf)
+    }
+}
+
+impl sqlx::Decode<'_, sqlx::Postgres> for DiscountType {
+    fn decode(
+        value: <sqlx::Postgres as sqlx::database::HasValueRef<'_>>::ValueRef,
+    ) -> Result<Self, Box<dyn std::error::Error + 'static + Send + Sync>> {
+        let s: String = <String as sqlx::Decode<sqlx::Postgres>>::decode(value)?;
+        match s.as_str() {
+            "percent" => Ok(Self::Percent),
+            "fixed" => Ok(Self::Fixed),
+            _ => Err("invalid discount type".into()),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, FromRow)]
+pub struct DiscountCodeTable {
+    pub id: Uuid,
+    pub created_at: Option<DateTime<Utc>>,
+    pub shop_id: Uuid,
+    pub code: String,
+    pub discount_type: DiscountType,
+    // a percentage from 1 to 100 or an amount in baht, depending on discount_type
+    pub value: i64,
+    pub min_spend: Option<i64>,
+    pub max_uses: Option<i64>,
+    pub max_uses_per_user: Option<i64>,
+    pub starts_at: Option<DateTime<Utc>>,
+    pub ends_at: Option<DateTime<Utc>>,
+    pub is_active: bool,
+    // when all of these are empty the code applies to every item in the shop
+    pub item_ids: Vec<Uuid>,
+    pub listing_ids: Vec<Uuid>,
+    pub collection_ids: Vec<Uuid>,
+}
+
+impl DiscountCodeTable {
+    pub async fn get_by_id(pool: &sqlx::PgPool, id: Uuid) -> Result<Self, sqlx::Error> {
+        sqlx::query_as::<_, Self>("SELECT * FROM discount_codes WHERE id = $1")
+            .bind(id)
+            .fetch_one(pool)
+            .await
+    }
+
+    pub async fn get_by_shop_id(
+        pool: &sqlx::PgPool,
+        shop_id: Uuid,
+    ) -> Result<Vec<Self>, sqlx::Error> {
+        sqlx::query_as::<_, Self>(
+            "SELECT * FROM discount_codes WHERE shop_id = $1 ORDER BY created_at DESC",
+        )
+        .bind(shop_id)
+        .fetch_all(pool)
+        .await
+    }
+
+    // codes are matched case-insensitively. The row stays locked until the transaction ends so
+    // that two orders can't both take the last use of a code.
+    pub async fn get_by_code_for_update(
+        connection: &mut PgConnection,
+        shop_id: Uuid,
+        code: &str,
+    ) -> Result<Self, sqlx::Error> {
+        sqlx::query_as::<_, Self>(
+            r#"
+            SELECT * FROM discount_codes
+            WHERE shop_id = $1 AND LOWER(code) = LOWER($2)
+            FOR UPDATE
+            "#,
+        )
+        .bind(shop_id)
+        .bind(code)
+        .fetch_one(connection)
+        .await
+    }
+
+    // orders that were canceled or refunded give their use back
+    pub async fn count_uses(
+        executor: impl sqlx::PgExecutor<'_>,
+        discount_code_id: Uuid,
+    ) -> Result<i64, sqlx::Error> {
+        let res = sqlx::query(
+            r#"
+            SELECT COUNT(*) AS uses FROM orders
+            WHERE discount_code_id = $1 AND shipment_status NOT IN ('canceled', 'refunded')
+            "#,
+        )
+        .bind(discount_code_id)
+        .fetch_one(executor)
+        .await?;
+
+        Ok(sqlx::Row::get::<i64, _>(&res, "uses"))
+    }
+
+    // guests are told apart by their contact email, which also catches signed-in users who
+    // used the code before as a guest
+    pub async fn count_uses_by_buyer(
+        executor: impl sqlx::PgExecutor<'_>,
+        discount_code_id: Uuid,
+        buyer_id: Option<Uuid>,
+        contact_email: &str,
+    ) -> Result<i64, sqlx::Error> {
+        let res = sqlx::query(
+            r#"
+            SELECT COUNT(*) AS uses FROM orders
+            WHERE discount_code_id = $1 AND shipment_status NOT IN ('canceled', 'refunded')
+            AND (buyer_id = $2 OR LOWER(contact_email) = LOWER($3))
+            "#,
+        )
+        .bind(discount_code_id)
+        .bind(buyer_id)
+        .bind(contact_email)
+        .fetch_one(executor)
+        .await?;
+
+        Ok(sqlx::Row::get::<i64, _>(&res, "uses"))
+    }
+}
@@ -0,0 +1,299 @@
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use sqlx::{PgConnection, Row};
+use uuid::Uuid;
+
+use self::db::{DiscountCodeTable, DiscountType};
+
+use super::order::request::OrderCreationError;
+
+pub(crate) mod db;
+pub(crate) mod request;
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct DiscountCode {
+    pub id: Uuid,
+    pub created_at: Option<DateTime<Utc>>,
+    pub code: String,
+    pub discount_type: DiscountType,
+    pub value: i64,
+    pub min_spend: Option<i64>,
+    pub max_uses: Option<i64>,
+    pub max_uses_per_user: Option<i64>,
+    pub starts_at: Option<DateTime<Utc>>,
+    pub ends_at: Option<DateTime<Utc>>,
+    pub is_active: bool,
+    pub item_ids: Vec<Uuid>,
+    pub listing_ids: Vec<Uuid>,
+    pub collection_ids: Vec<Uuid>,
+    pub times_used: i64,
+}
+
+impl DiscountCode {
+    pub async fn from_table(
+        pool: &sqlx::PgPool,
+        discount_code: DiscountCodeTable,
+    ) -> Result<Self, sqlx::Error> {
+        let times_used = DiscountCodeTable::count_uses(pool, discount_code.id).await?;
+
+        Ok(Self {
+            id: discount_code.id,
+            created_at: discount_code.created_at,
+            code: discount_code.code,
+            discount_type: discount_code.discount_type,
+            value: discount_code.value,
+            min_spend: discount_code.min_spend,
+            max_uses: discount_code.max_uses,
+            max_uses_per_user: discount_code.max_uses_per_user,
+            starts_at: discount_code.starts_at,
+            ends_at: discount_code.ends_at,
+            is_active: discount_code.is_active,
+            item_ids: discount_code.item_ids,
+            listing_ids: discount_code.listing_ids,
+            collection_ids: discount_code.collection_ids,
+            times_used,
+        })
+    }
+
+    pub async fn get_by_id(pool: &sqlx::PgPool, id: Uuid) -> Result<Self, sqlx::Error> {
+        let discount_code = DiscountCodeTable::get_by_id(pool, id).await?;
+
+        Self::from_table(pool, discount_code).await
+    }
+
+    pub async fn get_by_shop_id(
+        pool: &sqlx::PgPool,
+        shop_id: Uuid,
+    ) -> Result<Vec<Self>, sqlx::Error> {
+        let discount_codes = DiscountCodeTable::get_by_shop_id(pool, shop_id).await?;
+
+        let mut result = vec![];
+
+        for discount_code in discount_codes {
+            result.push(Self::from_table(pool, discount_code).await?);
+        }
+
+        Ok(result)
+    }
+}
+
+// an order line as far as discounts are concerned, unit_price already includes the item's own
+// discounted price
+pub struct DiscountableItem {
+    pub item_id: Uuid,
+    pub listing_id: Uuid,
+    pub unit_price: i64,
+    pub amount: i64,
+}
+
+// checks that the code can be used on this order and works out how much it takes off each of the
+// items, in the same order as they were given. The code stays locked until the order's
+// transaction ends
+pub async fn apply_discount_code(
+    connection: &mut PgConnection,
+    shop_id: Uuid,
+    code: &str,
+    items: &[DiscountableItem],
+    buyer_id: Option<Uuid>,
+    contact_email: &str,
+) -> Result<(DiscountCodeTable, Vec<i64>), OrderCreationError> {
+    let rejected = |reason: &str| {
+        Err(OrderCreationError::Rejected(format!(
+            "discount code {} {}",
+            code, reason
+        )))
+    };
+
+    let discount_code =
+        match DiscountCodeTable::get_by_code_for_update(connection, shop_id, code).await {
+            Ok(discount_code) => discount_code,
+            Err(sqlx::Error::RowNotFound) => return rejected("does not exist"),
+            Err(err) => return Err(err.into()),
+        };
+
+    let now = Utc::now();
+
+    if !discount_code.is_active {
+        return rejected("is no longer active");
+    }
+
+    if discount_code
+        .starts_at
+        .is_some_and(|starts_at| now < starts_at)
+    {
+        return rejected("is not valid yet");
+    }
+
+    if discount_code.ends_at.is_some_and(|ends_at| now >= ends_at) {
+        return rejected("has expired");
+    }
+
+    let subtotal = items
+        .iter()
+        .map(|item| item.unit_price * item.amount)
+        .sum::<i64>();
+
+    if let Some(min_spend) = discount_code.min_spend {
+        if subtotal < min_spend {
+            return rejected(&format!("needs a minimum spend of {}", min_spend));
+        }
+    }
+
+    if let Some(max_uses) = discount_code.max_uses {
+        if DiscountCodeTable::count_uses(&mut *connection, discount_code.id).await? >= max_uses {
+            return rejected("has been used up");
+        }
+    }
+
+    if let Some(max_uses_per_user) = discount_code.max_uses_per_user {
+        let uses = DiscountCodeTable::count_uses_by_buyer(
+            &mut *connection,
+            discount_code.id,
+            buyer_id,
+            contact_email,
+        )
+        .await?;
+
+        if uses >= max_uses_per_user {
+            return rejected("has already been used the maximum number of times");
+        }
+    }
+
+    let is_restricted = !discount_code.item_ids.is_empty()
+        || !discount_code.listing_ids.is_empty()
+        || !discount_code.collection_ids.is_empty();
+
+    let collection_listing_ids = if discount_code.collection_ids.is_empty() {
+        vec![]
+    } else {
+        sqlx::query("SELECT listing_id FROM collection_listings WHERE collection_id = ANY($1)")
+            .bind(&discount_code.collection_ids)
+            .fetch_all(&mut *connection)
+            .await?
+            .into_iter()
+            .map(|row| row.get::<Uuid, _>("listing_id"))
+            .collect::<Vec<Uuid>>()
+    };
+
+    let is_eligible = |item: &DiscountableItem| {
+        !is_restricted
+            || discount_code.item_ids.contains(&item.item_id)
+            || discount_code.listing_ids.contains(&item.listing_id)
+            || collection_listing_ids.contains(&item.listing_id)
+    };
+
+    let eligible_subtotal = items
+        .iter()
+        .filter(|item| is_eligible(item))
+        .map(|item| item.unit_price * item.amount)
+        .sum::<i64>();
+
+    if eligible_subtotal == 0 {
+        return rejected("does not apply to any item in the order");
+    }
+
+    let discounts = split_discount(
+        discount_code.discount_type,
+        discount_code.value,
+        items,
+        is_eligible,
+    );
+
+    Ok((discount_code, discounts))
+}
+
+// how much a discount takes off each of the items, split by what each eligible item costs. The
+// baht lost to rounding go to the first ones
+fn split_discount(
+    discount_type: DiscountType,
+    value: i64,
+    items: &[DiscountableItem],
+    is_eligible: impl Fn(&DiscountableItem) -> bool,
+) -> Vec<i64> {
+    let eligible_subtotal = items
+        .iter()
+        .filter(|item| is_eligible(item))
+        .map(|item| item.unit_price * item.amount)
+        .sum::<i64>();
+
+    if eligible_subtotal == 0 {
+        return vec![0; items.len()];
+    }
+
+    let discount = match discount_type {
+        DiscountType::Percent => eligible_subtotal * value / 100,
+        DiscountType::Fixed => value.min(eligible_subtotal),
+    };
+
+    let mut discounts = items
+        .iter()
+        .map(|item| match is_eligible(item) {
+            true => discount * item.unit_price * item.amount / eligible_subtotal,
+            false => 0,
+        })
+        .collect::<Vec<i64>>();
+
+    let mut remainder = discount - discounts.iter().sum::<i64>();
+
+    for (item, item_discount) in items.iter().zip(discounts.iter_mut()) {
+        if remainder == 0 {
+            break;
+        }
+
+        if is_eligible(item) && *item_discount < item.unit_price * item.amount {
+            *item_discount += 1;
+            remainder -= 1;
+        }
+    }
+
+    discounts
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn item(unit_price: i64, amount: i64) -> DiscountableItem {
+        DiscountableItem {
+            item_id: Uuid::new_v4(),
+            listing_id: Uuid::new_v4(),
+            unit_price,
+            amount,
+        }
+    }
+
+    #[test]
+    fn remainder_goes_to_the_first_eligible_items() {
+        let items = [item(50, 1), item(10, 1), item(10, 1), item(10, 1)];
+        let excluded = items[0].item_id;
+
+        let discounts = split_discount(DiscountType::Fixed, 10, &items, |item| {
+            item.item_id != excluded
+        });
+
+        assert_eq!(discounts, vec![0, 4, 3, 3]);
+    }
+
+    #[test]
+    fn discount_larger_than_the_subtotal_only_takes_the_subtotal() {
+        let items = [item(40, 2), item(20, 1)];
+
+        let discounts = split_discount(DiscountType::Fixed, 500, &items, |_| true);
+
+        assert_eq!(discounts, vec![80, 20]);
+    }
+
+    #[test]
+    fn single_line_gets_the_whole_discount() {
+        let discounts = split_discount(DiscountType::Percent, 15, &[item(35, 3)], |_| true);
+
+        assert_eq!(discounts, vec![15]);
+    }
+
+    #[test]
+    fn percent_discount_is_rounded_down() {
+        let discounts = split_discount(DiscountType::Percent, 10, &[item(33, 1)], |_| true);
+
+        assert_eq!(discounts, vec![3]);
+    }
+}
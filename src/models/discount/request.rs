@@ -0,0 +1,269 @@
+use std::fmt::Display;
+
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Deserializer, Serialize};
+use sqlx::{PgConnection, Row};
+use uuid::Uuid;
+
+use super::db::DiscountType;
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct CreatableDiscountCode {
+    pub code: String,
+    pub discount_type: DiscountType,
+    pub value: i64,
+    pub min_spend: Option<i64>,
+    pub max_uses: Option<i64>,
+    pub max_uses_per_user: Option<i64>,
+    pub starts_at: Option<DateTime<Utc>>,
+    pub ends_at: Option<DateTime<Utc>>,
+    pub item_ids: Option<Vec<Uuid>>,
+    pub listing_ids: Option<Vec<Uuid>>,
+    pub collection_ids: Option<Vec<Uuid>>,
+}
+
+#[derive(Debug)]
+pub enum DiscountCodeError {
+    // the message is meant for the manager creating the code
+    Rejected(String),
+    Database(sqlx::Error),
+}
+
+impl Display for DiscountCodeError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Rejected(reason) => write!(f, "{}", reason),
+            Self::Database(err) => write!(f, "{}", err),
+        }
+    }
+}
+
+impl From<sqlx::Error> for DiscountCodeError {
+    fn from(err: sqlx::Error) -> Self {
+        Self::Database(err)
+    }
+}
+
+// a code can only be limited to the shop's own items, listings and collections
+async fn check_shop_ids(
+    connection: &mut PgConnection,
+    shop_id: Uuid,
+    code: &CreatableDiscountCode,
+) -> Result<(), DiscountCodeError> {
+    let checks = [
+        (
+            "item",
+            &code.item_ids,
+            r#"
+            SELECT items.id FROM items
+            INNER JOIN listings ON items.listing_id = listings.id
+            WHERE items.id = ANY($1) AND listings.shop_id = $2
+            "#,
+        ),
+        (
+            "listing",
+            &code.listing_ids,
+            "SELECT id FROM listings WHERE id = ANY($1) AND shop_id = $2",
+        ),
+        (
+            "collection",
+            &code.collection_ids,
+            "SELECT id FROM collections WHERE id = ANY($1) AND shop_id = $2",
+        ),
+    ];
+
+    for (kind, ids, query) in checks {
+        let ids = match ids {
+            Some(ids) if !ids.is_empty() => ids,
+            _ => continue,
+        };
+
+        let found = sqlx::query(query)
+            .bind(ids)
+            .bind(shop_id)
+            .fetch_all(&mut *connection)
+            .await?
+            .into_iter()
+            .map(|row| row.get::<Uuid, _>("id"))
+            .collect::<Vec<Uuid>>();
+
+        if let Some(id) = ids.iter().find(|id| !found.contains(id)) {
+            return Err(DiscountCodeError::Rejected(format!(
+                "{} {} is not in this shop",
+                kind, id
+            )));
+        }
+    }
+
+    Ok(())
+}
+
+impl CreatableDiscountCode {
+    pub async fn bulk_insert(
+        codes: &[CreatableDiscountCode],
+        shop_id: Uuid,
+        pool: &sqlx::PgPool,
+    ) -> Result<Vec<Uuid>, DiscountCodeError> {
+        let mut transaction = pool.begin().await?;
+
+        let mut ids = Vec::new();
+
+        for code in codes {
+            check_shop_ids(transaction.as_mut(), shop_id, code).await?;
+
+            let res = sqlx::query(
+                r#"
+                INSERT INTO discount_codes (shop_id, code, discount_type, value, min_spend, max_uses, max_uses_per_user, starts_at, ends_at, item_ids, listing_ids, collection_ids)
+                VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10, $11, $12)
+                RETURNING id
+                "#,
+            )
+            .bind(shop_id)
+            .bind(code.code.trim())
+            .bind(code.discount_type)
+            .bind(code.value)
+            .bind(code.min_spend)
+            .bind(code.max_uses)
+            .bind(code.max_uses_per_user)
+            .bind(code.starts_at)
+            .bind(code.ends_at)
+            .bind(code.item_ids.clone().unwrap_or_default())
+            .bind(code.listing_ids.clone().unwrap_or_default())
+            .bind(code.collection_ids.clone().unwrap_or_default())
+            .fetch_one(transaction.as_mut())
+            .await?;
+
+            ids.push(res.get::<Uuid, _>("id"));
+        }
+
+        transaction.commit().await?;
+
+        Ok(ids)
+    }
+
+    pub fn validate(&self) -> Result<&Self, String> {
+        let code = self.code.trim();
+
+        if code.is_empty() {
+            return Err("code must not be empty".to_string());
+        }
+
+        if !code
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_')
+        {
+            return Err("code may only contain letters, digits, - and _".to_string());
+        }
+
+        if self.value <= 0 {
+            return Err("value must be greater than 0".to_string());
+        }
+
+        if self.discount_type == DiscountType::Percent && self.value > 100 {
+            return Err("a percent discount must not be more than 100".to_string());
+        }
+
+        if self.min_spend.unwrap_or(0) < 0 {
+            return Err("min_spend must not be negative".to_string());
+        }
+
+        if self.max_uses.is_some_and(|max_uses| max_uses < 1)
+            || self
+                .max_uses_per_user
+                .is_some_and(|max_uses_per_user| max_uses_per_user < 1)
+        {
+            return Err("max_uses and max_uses_per_user must be at least 1".to_string());
+        }
+
+        if let (Some(starts_at), Some(ends_at)) = (self.starts_at, self.ends_at) {
+            if ends_at <= starts_at {
+                return Err("ends_at must be after starts_at".to_string());
+            }
+        }
+
+        Ok(self)
+    }
+}
+
+// tells a field that was left out (None) from one that was set to null (Some(None))
+fn double_option<'de, T, D>(deserializer: D) -> Result<Option<Option<T>>, D::Error>
+where
+    T: Deserialize<'de>,
+    D: Deserializer<'de>,
+{
+    Option::<T>::deserialize(deserializer).map(Some)
+}
+
+// the code itself, its type and value can't change once it may have been used on an order
+#[derive(Debug, Serialize, Deserialize)]
+pub struct UpdatableDiscountCode {
+    pub min_spend: Option<i64>,
+    // null removes the limit
+    #[serde(default, deserialize_with = "double_option")]
+    pub max_uses: Option<Option<i64>>,
+    #[serde(default, deserialize_with = "double_option")]
+    pub max_uses_per_user: Option<Option<i64>>,
+    pub starts_at: Option<DateTime<Utc>>,
+    pub ends_at: Option<DateTime<Utc>>,
+    pub is_active: Option<bool>,
+}
+
+impl UpdatableDiscountCode {
+    pub async fn commit_changes(
+        &self,
+        pool: &sqlx::PgPool,
+        shop_id: Uuid,
+        discount_code_id: Uuid,
+    ) -> Result<(), sqlx::Error> {
+        sqlx::query(
+            r#"
+            UPDATE discount_codes
+            SET min_spend = COALESCE($1, min_spend),
+                max_uses = CASE WHEN $2 THEN $3 ELSE max_uses END,
+                max_uses_per_user = CASE WHEN $4 THEN $5 ELSE max_uses_per_user END,
+                starts_at = COALESCE($6, starts_at),
+                ends_at = COALESCE($7, ends_at),
+                is_active = COALESCE($8, is_active)
+            WHERE id = $9 AND shop_id = $10
+            RETURNING id
+            "#,
+        )
+        .bind(self.min_spend)
+        .bind(self.max_uses.is_some())
+        .bind(self.max_uses.flatten())
+        .bind(self.max_uses_per_user.is_some())
+        .bind(self.max_uses_per_user.flatten())
+        .bind(self.starts_at)
+        .bind(self.ends_at)
+        .bind(self.is_active)
+        .bind(discount_code_id)
+        .bind(shop_id)
+        .fetch_one(pool)
+        .await?;
+
+        Ok(())
+    }
+
+    pub fn validate(&self) -> Result<&Self, String> {
+        if self.min_spend.unwrap_or(0) < 0 {
+            return Err("min_spend must not be negative".to_string());
+        }
+
+        if self.max_uses.flatten().is_some_and(|max_uses| max_uses < 1)
+            || self
+                .max_uses_per_user
+                .flatten()
+                .is_some_and(|max_uses_per_user| max_uses_per_user < 1)
+        {
+            return Err("max_uses and max_uses_per_user must be at least 1".to_string());
+        }
+
+        if let (Some(starts_at), Some(ends_at)) = (self.starts_at, self.ends_at) {
+            if ends_at <= starts_at {
+                return Err("ends_at must be after starts_at".to_string());
+            }
+        }
+
+        Ok(self)
+    }
+}
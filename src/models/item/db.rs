@@ -19,6 +19,12 @@ pub struct ItemTable {
     pub preorder_start: Option<DateTime<Utc>>,
     pub preorder_end: Option<DateTime<Utc>>,
     pub listing_id: sqlx::types::Uuid,
+    pub weight_grams: Option<i64>,
+    pub barcode: Option<String>,
+    pub low_stock_threshold: Option<i64>,
+    pub preorder_cap: Option<i64>,
+    pub max_per_order: Option<i64>,
+    pub max_per_user: Option<i64>,
 }
 
 impl ItemTable {
@@ -0,0 +1,258 @@
+use std::{
+    collections::{HashMap, HashSet},
+    fmt::Display,
+    time::Duration,
+};
+
+use lettre::transport::smtp::authentication::Credentials;
+use serde::{Deserialize, Serialize};
+use sqlx::{FromRow, PgPool, Row, Type};
+use uuid::Uuid;
+
+use crate::utils::email::send_low_stock_email;
+
+use super::stock::item_stock_query;
+
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum StockAlertLevel {
+    // at or below the item's low stock threshold
+    Low,
+    SoldOut,
+}
+
+impl Display for StockAlertLevel {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let s = match self {
+            Self::Low => "low",
+            Self::SoldOut => "sold_out",
+        };
+        write!(f, "{}", s)
+    }
+}
+
+impl Type<sqlx::Postgres> for StockAlertLevel {
+    fn type_info() -> sqlx::postgres::PgTypeInfo {
+        sqlx::postgres::PgTypeInfo::with_name("stock_alert_level")
+    }
+}
+
+impl sqlx::Encode<'_, sqlx::Postgres> for StockAlertLevel {
+    fn encode_by_ref(
+        &self,
+        buf: &mut <sqlx::Postgres as sqlx::database::HasArguments<'_>>::ArgumentBuffer,
+    ) -> sqlx::encode::IsNull {
+        let s: String = self.to_string();
+        <String as sqlx::Encode<sqlx::Postgres>>::encode(s, buf)
+    }
+}
+
+impl sqlx::Decode<'_, sqlx::Postgres> for StockAlertLevel {
+    fn decode(
+        value: <sqlx::Postgres as sqlx::database::HasValueRef<'_>>::ValueRef,
+    ) -> Result<Self, Box<dyn std::error::Error + 'static + Send + Sync>> {
+        let s: String = <String as sqlx::Decode<sqlx::Postgres>>::decode(value)?;
+        match s.as_str() {
+            "low" => Ok(Self::Low),
+            "sold_out" => Ok(Self::SoldOut),
+            _ => Err("invalid stock alert level".into()),
+        }
+    }
+}
+
+// an item that is sold out or at its low stock threshold. Items that never had any stock are left
+// out, they aren't sold from stock
+#[derive(Debug, Serialize, Deserialize, FromRow)]
+pub struct LowStockItem {
+    pub item_id: Uuid,
+    pub shop_id: Uuid,
+    pub name: String,
+    pub variant_name: Option<String>,
+    pub low_stock_threshold: Option<i64>,
+    pub available: i64,
+    pub level: StockAlertLevel,
+}
+
+impl LowStockItem {
+    fn query() -> String {
+        format!(
+            "SELECT
+            items.id AS item_id,
+            listings.shop_id,
+            items.name,
+            items.variant_name,
+            items.low_stock_threshold,
+            item_stock.lifetime_stock - item_stock.amount_sold AS available,
+            CAST(
+              CASE WHEN item_stock.lifetime_stock - item_stock.amount_sold <= 0
+              THEN 'sold_out' ELSE 'low' END AS stock_alert_level
+            ) AS level
+          FROM
+            items
+            INNER JOIN listings ON items.listing_id = listings.id
+            INNER JOIN ({}) AS item_stock ON items.id = item_stock.item_id
+          WHERE
+            item_stock.lifetime_stock > 0
+            AND item_stock.lifetime_stock - item_stock.amount_sold
+              <= GREATEST(COALESCE(items.low_stock_threshold, 0), 0)",
+            item_stock_query()
+        )
+    }
+
+    pub async fn get_all(pool: &PgPool) -> Result<Vec<Self>, sqlx::Error> {
+        sqlx::query_as::<_, Self>(&Self::query())
+            .fetch_all(pool)
+            .await
+    }
+
+    pub async fn get_by_shop_id(pool: &PgPool, shop_id: Uuid) -> Result<Vec<Self>, sqlx::Error> {
+        let query = format!(
+            "SELECT * FROM ({}) AS low_stock WHERE shop_id = $1 ORDER BY available, name",
+            Self::query()
+        );
+
+        sqlx::query_as::<_, Self>(&query)
+            .bind(shop_id)
+            .fetch_all(pool)
+            .await
+    }
+}
+
+// emails the managers of every shop with items that went low or sold out since the last check.
+// The level each item was reported at is kept, so an item is reported once per drop and again
+// only after it has been restocked. Returns the number of items reported
+pub async fn check_stock_alerts(
+    pool: &PgPool,
+    credential: &Credentials,
+) -> Result<usize, sqlx::Error> {
+    let items = LowStockItem::get_all(pool).await?;
+
+    let reported = sqlx::query("SELECT item_id, level FROM item_stock_alerts")
+        .fetch_all(pool)
+        .await?
+        .into_iter()
+        .map(|row| {
+            (
+                row.get::<Uuid, _>("item_id"),
+                row.get::<StockAlertLevel, _>("level"),
+            )
+        })
+        .collect::<HashMap<Uuid, StockAlertLevel>>();
+
+    let mut transaction = pool.begin().await?;
+
+    // restocked items can be reported again
+    let current = items
+        .iter()
+        .map(|item| item.item_id)
+        .collect::<HashSet<Uuid>>();
+    let restocked = reported
+        .keys()
+        .filter(|item_id| !current.contains(item_id))
+        .copied()
+        .collect::<Vec<Uuid>>();
+
+    sqlx::query("DELETE FROM item_stock_alerts WHERE item_id = ANY($1)")
+        .bind(&restocked)
+        .execute(transaction.as_mut())
+        .await?;
+
+    let mut by_shop: HashMap<Uuid, Vec<&LowStockItem>> = HashMap::new();
+
+    for item in &items {
+        let level = item.level;
+        let is_new = match reported.get(&item.item_id) {
+            None => true,
+            Some(StockAlertLevel::Low) => level == StockAlertLevel::SoldOut,
+            Some(StockAlertLevel::SoldOut) => false,
+        };
+
+        if reported.get(&item.item_id) != Some(&level) {
+            sqlx::query(
+                r#"
+                INSERT INTO item_stock_alerts (item_id, level)
+                VALUES ($1, $2)
+                ON CONFLICT (item_id) DO UPDATE SET level = $2, reported_at = NOW()
+                "#,
+            )
+            .bind(item.item_id)
+            .bind(level)
+            .execute(transaction.as_mut())
+            .await?;
+        }
+
+        if is_new {
+            by_shop.entry(item.shop_id).or_default().push(item);
+        }
+    }
+
+    transaction.commit().await?;
+
+    let mut count = 0;
+
+    // the alerts are already recorded, a shop that can't be emailed mustn't keep the other shops
+    // from hearing about theirs
+    for (shop_id, items) in by_shop {
+        let shop_name = sqlx::query("SELECT name_th FROM shops WHERE id = $1")
+            .bind(shop_id)
+            .fetch_one(pool)
+            .await
+            .map(|row| row.get::<String, _>("name_th"));
+
+        let shop_name = match shop_name {
+            Ok(shop_name) => shop_name,
+            Err(e) => {
+                println!("Error: {}", e);
+                continue;
+            }
+        };
+
+        let managers = sqlx::query(
+            r#"
+            SELECT users.email FROM shop_managers
+            INNER JOIN users ON shop_managers.user_id = users.id
+            WHERE shop_managers.shop_id = $1
+            "#,
+        )
+        .bind(shop_id)
+        .fetch_all(pool)
+        .await;
+
+        let managers = match managers {
+            Ok(managers) => managers
+                .into_iter()
+                .map(|row| row.get::<String, _>("email"))
+                .collect::<Vec<String>>(),
+            Err(e) => {
+                println!("Error: {}", e);
+                continue;
+            }
+        };
+
+        count += items.len();
+
+        if let Err(e) = send_low_stock_email(credential, &managers, &shop_name, &items) {
+            println!("Error: {}", e);
+        }
+    }
+
+    Ok(count)
+}
+
+pub fn spawn_stock_alert_checker(pool: PgPool, credential: Credentials, every: Duration) {
+    actix_rt::spawn(async move {
+        let mut interval = actix_rt::time::interval(every);
+
+        loop {
+            interval.tick().await;
+
+            match check_stock_alerts(&pool, &credential).await {
+                Ok(count) if count > 0 => {
+                    println!("📦 Reported {} items running out of stock", count);
+                }
+                Ok(_) => {}
+                Err(err) => println!("🔥 Failed to check for low stock: {}", err),
+            }
+        }
+    });
+}
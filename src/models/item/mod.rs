@@ -6,12 +6,19 @@ use serde::{Deserialize, Serialize};
 use sqlx::Row;
 use uuid::Uuid;
 
-use self::request::{QueryableItem, SortableItem};
+use self::{
+    request::{QueryableItem, SortableItem},
+    stock::{ItemStock, StockLevels},
+};
 
-use super::{collection::Collection, listing::Listing, shop::Shop};
+use super::{collection::Collection, listing::Listing, order::request::ItemAmount, shop::Shop};
 
 pub(crate) mod db;
+pub(crate) mod low_stock;
+pub(crate) mod preorder;
 pub(crate) mod request;
+pub(crate) mod stock;
+pub(crate) mod waitlist;
 
 #[derive(Debug, Serialize, Deserialize)]
 pub struct IdOnlyItem {
@@ -38,9 +45,18 @@ pub struct DefaultItem {
     pub price: i64,
     pub discounted_price: Option<i64>,
     pub lifetime_stock: i64,
+    // every unit taken by an order, paid or not
     pub amount_sold: i64,
+    // the same stock split up the way the shop counts it, see `ItemStock::levels`
+    pub stock: StockLevels,
     pub preorder_start: Option<DateTime<Utc>>,
     pub preorder_end: Option<DateTime<Utc>>,
+    pub preorder_cap: Option<i64>,
+    pub weight_grams: Option<i64>,
+    pub barcode: Option<String>,
+    pub low_stock_threshold: Option<i64>,
+    pub max_per_order: Option<i64>,
+    pub max_per_user: Option<i64>,
     pub colors: Vec<String>,
     pub image_urls: Vec<String>,
 }
@@ -53,9 +69,18 @@ pub struct DetailedItem {
     pub price: i64,
     pub discounted_price: Option<i64>,
     pub lifetime_stock: i64,
+    // every unit taken by an order, paid or not
     pub amount_sold: i64,
+    // the same stock split up the way the shop counts it, see `ItemStock::levels`
+    pub stock: StockLevels,
     pub preorder_start: Option<DateTime<Utc>>,
     pub preorder_end: Option<DateTime<Utc>>,
+    pub preorder_cap: Option<i64>,
+    pub weight_grams: Option<i64>,
+    pub barcode: Option<String>,
+    pub low_stock_threshold: Option<i64>,
+    pub max_per_order: Option<i64>,
+    pub max_per_user: Option<i64>,
     pub colors: Vec<String>,
     pub image_urls: Vec<String>,
     pub shop: Shop,
@@ -71,33 +96,7 @@ impl From<db::ItemTable> for IdOnlyItem {
 
 impl CompactItem {
     pub async fn from_table(pool: &sqlx::PgPool, item: db::ItemTable) -> Result<Self, sqlx::Error> {
-        // get lifetime_stock from item_stock_updates
-        let lifetime_stock = sqlx::query(
-            r#"
-            SELECT CAST(SUM(stock_added) as INT8) as lifetime_stock FROM item_stock_updates WHERE item_id = $1
-            "#,
-        )
-        .bind(item.id)
-        .fetch_one(pool)
-        .await?;
-
-        let lifetime_stock = lifetime_stock
-            .get::<Option<i64>, _>("lifetime_stock")
-            .unwrap_or(0);
-
-        // get amount_sold from order_items
-        let amount_sold = sqlx::query(
-            r#"
-            SELECT CAST(SUM(amount) as INT8) as amount_sold FROM order_items WHERE item_id = $1 AND order_id IN (SELECT id FROM orders WHERE NOT (shipment_status = 'canceled' OR (created_at > NOW() - INTERVAL '3 minute' AND is_paid = FALSE)))
-            "#,
-        )
-        .bind(item.id)
-        .fetch_one(pool)
-        .await?;
-
-        let amount_sold = amount_sold
-            .get::<Option<i64>, _>("amount_sold")
-            .unwrap_or(0);
+        let stock = ItemStock::get_by_item_id(pool, item.id).await?;
 
         // get colors from item_colors
         let colors = sqlx::query(
@@ -120,8 +119,8 @@ impl CompactItem {
             variant_name: item.variant_name,
             price: item.price,
             discounted_price: item.discounted_price,
-            lifetime_stock,
-            amount_sold,
+            lifetime_stock: stock.lifetime_stock,
+            amount_sold: stock.amount_sold,
             colors,
         })
     }
@@ -129,33 +128,7 @@ impl CompactItem {
 
 impl DefaultItem {
     pub async fn from_table(pool: &sqlx::PgPool, item: db::ItemTable) -> Result<Self, sqlx::Error> {
-        // get lifetime_stock from item_stock_updates
-        let lifetime_stock = sqlx::query(
-            r#"
-            SELECT CAST(SUM(stock_added) as INT8) as lifetime_stock FROM item_stock_updates WHERE item_id = $1
-            "#,
-        )
-        .bind(item.id)
-        .fetch_one(pool)
-        .await?;
-
-        let lifetime_stock = lifetime_stock
-            .get::<Option<i64>, _>("lifetime_stock")
-            .unwrap_or(0);
-
-        // get amount_sold from order_items
-        let amount_sold = sqlx::query(
-            r#"
-            SELECT CAST(SUM(amount) as INT8) as amount_sold FROM order_items WHERE item_id = $1 AND order_id IN (SELECT id FROM orders WHERE NOT (shipment_status = 'canceled' OR (created_at > NOW() - INTERVAL '3 minute' AND is_paid = FALSE)))
-            "#,
-        )
-        .bind(item.id)
-        .fetch_one(pool)
-        .await?;
-
-        let amount_sold = amount_sold
-            .get::<Option<i64>, _>("amount_sold")
-            .unwrap_or(0);
+        let stock = ItemStock::get_by_item_id(pool, item.id).await?;
 
         // get colors from item_colors
         let colors = sqlx::query(
@@ -195,8 +168,15 @@ impl DefaultItem {
             discounted_price: item.discounted_price,
             preorder_start: item.preorder_start,
             preorder_end: item.preorder_end,
-            lifetime_stock,
-            amount_sold,
+            preorder_cap: item.preorder_cap,
+            weight_grams: item.weight_grams,
+            barcode: item.barcode,
+            low_stock_threshold: item.low_stock_threshold,
+            max_per_order: item.max_per_order,
+            max_per_user: item.max_per_user,
+            lifetime_stock: stock.lifetime_stock,
+            amount_sold: stock.amount_sold,
+            stock: stock.levels(),
             colors,
             image_urls: images_url,
         })
@@ -209,33 +189,7 @@ impl DetailedItem {
         item: db::ItemTable,
         descendant_fetch_level: Option<&FetchLevel>,
     ) -> Result<Self, sqlx::Error> {
-        // get lifetime_stock from item_stock_updates
-        let lifetime_stock = sqlx::query(
-            r#"
-            SELECT CAST(SUM(stock_added) as INT8) as lifetime_stock FROM item_stock_updates WHERE item_id = $1
-            "#,
-        )
-        .bind(item.id)
-        .fetch_one(pool)
-        .await?;
-
-        let lifetime_stock = lifetime_stock
-            .get::<Option<i64>, _>("lifetime_stock")
-            .unwrap_or(0);
-
-        // get amount_sold from order_items
-        let amount_sold = sqlx::query(
-            r#"
-            SELECT CAST(SUM(amount) as INT8) as amount_sold FROM order_items WHERE item_id = $1 AND order_id IN (SELECT id FROM orders WHERE NOT (shipment_status = 'canceled' OR (created_at > NOW() - INTERVAL '3 minute' AND is_paid = FALSE)))
-            "#,
-        )
-        .bind(item.id)
-        .fetch_one(pool)
-        .await?;
-
-        let amount_sold = amount_sold
-            .get::<Option<i64>, _>("amount_sold")
-            .unwrap_or(0);
+        let stock = ItemStock::get_by_item_id(pool, item.id).await?;
 
         // get colors from item_colors
         let colors = sqlx::query(
@@ -303,10 +257,17 @@ impl DetailedItem {
             variant_name: item.variant_name,
             price: item.price,
             discounted_price: item.discounted_price,
-            lifetime_stock,
-            amount_sold,
+            lifetime_stock: stock.lifetime_stock,
+            amount_sold: stock.amount_sold,
+            stock: stock.levels(),
             preorder_start: item.preorder_start,
             preorder_end: item.preorder_end,
+            preorder_cap: item.preorder_cap,
+            weight_grams: item.weight_grams,
+            barcode: item.barcode,
+            low_stock_threshold: item.low_stock_threshold,
+            max_per_order: item.max_per_order,
+            max_per_user: item.max_per_user,
             colors,
             image_urls: images_url,
             listing: Listing::get_by_id(
@@ -535,4 +496,119 @@ impl CartItem {
 
         Ok(())
     }
+
+    // returns RowNotFound when the item is not in the cart
+    pub async fn update_user_cart_amount(
+        user_id: sqlx::types::Uuid,
+        item_id: sqlx::types::Uuid,
+        amount: i64,
+        pool: &sqlx::PgPool,
+    ) -> Result<(), sqlx::Error> {
+        sqlx::query(
+            r#"
+            UPDATE user_cart_items SET amount = $1 WHERE user_id = $2 AND item_id = $3
+            RETURNING item_id
+            "#,
+        )
+        .bind(amount)
+        .bind(user_id)
+        .bind(item_id)
+        .fetch_one(pool)
+        .await?;
+
+        Ok(())
+    }
+
+    // returns RowNotFound when the item is not in the cart
+    pub async fn remove_from_user_cart(
+        user_id: sqlx::types::Uuid,
+        item_id: sqlx::types::Uuid,
+        pool: &sqlx::PgPool,
+    ) -> Result<(), sqlx::Error> {
+        sqlx::query(
+            r#"
+            DELETE FROM user_cart_items WHERE user_id = $1 AND item_id = $2
+            RETURNING item_id
+            "#,
+        )
+        .bind(user_id)
+        .bind(item_id)
+        .fetch_one(pool)
+        .await?;
+
+        Ok(())
+    }
+
+    // removes only the given items so that anything added to the cart in the meantime stays
+    pub async fn remove_many_from_user_cart(
+        user_id: sqlx::types::Uuid,
+        item_ids: &[sqlx::types::Uuid],
+        pool: &sqlx::PgPool,
+    ) -> Result<(), sqlx::Error> {
+        sqlx::query(
+            r#"
+            DELETE FROM user_cart_items WHERE user_id = $1 AND item_id = ANY($2)
+            "#,
+        )
+        .bind(user_id)
+        .bind(item_ids)
+        .execute(pool)
+        .await?;
+
+        Ok(())
+    }
+
+    pub async fn clear_user_cart(
+        user_id: sqlx::types::Uuid,
+        pool: &sqlx::PgPool,
+    ) -> Result<(), sqlx::Error> {
+        sqlx::query(
+            r#"
+            DELETE FROM user_cart_items WHERE user_id = $1
+            "#,
+        )
+        .bind(user_id)
+        .execute(pool)
+        .await?;
+
+        Ok(())
+    }
+
+    // the items in the cart grouped by the shop that sells them, in the order the shops first
+    // appear in the cart
+    pub async fn get_user_cart_by_shop(
+        user_id: sqlx::types::Uuid,
+        pool: &sqlx::PgPool,
+    ) -> Result<Vec<(Uuid, Vec<ItemAmount>)>, sqlx::Error> {
+        let res = sqlx::query(
+            r#"
+            SELECT user_cart_items.item_id, user_cart_items.amount, listings.shop_id
+            FROM user_cart_items
+            INNER JOIN items ON user_cart_items.item_id = items.id
+            INNER JOIN listings ON items.listing_id = listings.id
+            WHERE user_cart_items.user_id = $1
+            ORDER BY user_cart_items.created_at, user_cart_items.item_id
+            "#,
+        )
+        .bind(user_id)
+        .fetch_all(pool)
+        .await?;
+
+        let mut shops: Vec<(Uuid, Vec<ItemAmount>)> = vec![];
+
+        for row in res {
+            let shop_id = row.get::<Uuid, _>("shop_id");
+            let item = ItemAmount {
+                item_id: row.get::<Uuid, _>("item_id"),
+                amount: row.get::<i64, _>("amount"),
+            };
+
+            match shops.iter_mut().find(|(id, _)| *id == shop_id) {
+                Some((_, items)) => items.push(item),
+                None => shops.push((shop_id, vec![item])),
+            }
+        }
+
+        Ok(shops)
+    }
 }
@@ -0,0 +1,270 @@
+use std::collections::HashMap;
+
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use sqlx::{FromRow, PgConnection, Row};
+use uuid::Uuid;
+
+use super::stock::{lock_items, StockUpdateError, STOCK_HOLDING_ORDERS};
+
+// when an item can be ordered. An item with a preorder window can't be ordered before it opens,
+// can be ordered beyond its stock (up to preorder_cap) while it is open and is only sold from
+// stock once it has closed. Items without a window are always sold from stock
+#[derive(Debug, Clone, FromRow)]
+pub struct PreorderWindow {
+    pub item_id: Uuid,
+    pub name: String,
+    pub preorder_start: Option<DateTime<Utc>>,
+    pub preorder_end: Option<DateTime<Utc>>,
+    // how many units can be preordered beyond the stock, no limit when None
+    pub preorder_cap: Option<i64>,
+}
+
+impl PreorderWindow {
+    pub async fn get_by_item_ids(
+        connection: &mut PgConnection,
+        item_ids: &[Uuid],
+    ) -> Result<HashMap<Uuid, Self>, sqlx::Error> {
+        let windows = sqlx::query_as::<_, Self>(
+            r#"
+            SELECT id AS item_id, name, preorder_start, preorder_end, preorder_cap FROM items
+            WHERE id = ANY($1)
+            "#,
+        )
+        .bind(item_ids)
+        .fetch_all(connection)
+        .await?;
+
+        Ok(windows
+            .into_iter()
+            .map(|window| (window.item_id, window))
+            .collect())
+    }
+
+    fn has_window(&self) -> bool {
+        self.preorder_start.is_some() || self.preorder_end.is_some()
+    }
+
+    // checks that `amount` can be ordered now with `available` left in stock, and returns
+    // whether it is a preorder
+    pub fn check(&self, amount: i64, available: i64, now: DateTime<Utc>) -> Result<bool, String> {
+        if let Some(preorder_start) = self.preorder_start {
+            if now < preorder_start {
+                return Err(format!(
+                    "{} can only be preordered from {}",
+                    self.name, preorder_start
+                ));
+            }
+        }
+
+        let is_closed = self
+            .preorder_end
+            .map(|preorder_end| now > preorder_end)
+            .unwrap_or(false);
+
+        if !self.has_window() || is_closed {
+            if available < amount {
+                return Err(match (is_closed, self.preorder_end) {
+                    (true, Some(preorder_end)) => format!(
+                        "preorders for {} closed on {} and only {} are left in stock",
+                        self.name,
+                        preorder_end,
+                        available.max(0)
+                    ),
+                    _ => format!("{} only has {} left in stock", self.name, available.max(0)),
+                });
+            }
+
+            return Ok(false);
+        }
+
+        if let Some(preorder_cap) = self.preorder_cap {
+            let left = available + preorder_cap;
+
+            if left < amount {
+                return Err(format!("{} only has {} left to preorder", self.name, left.max(0)));
+            }
+        }
+
+        Ok(true)
+    }
+}
+
+#[derive(Debug, Serialize)]
+pub struct PreorderArrivalOutcome {
+    pub stock_update_id: Uuid,
+    // orders whose preorders of the item are now all covered by the stock
+    pub fulfilled_order_ids: Vec<Uuid>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CreatablePreorderArrival {
+    // units in the batch, added to the stock
+    pub quantity: i64,
+    pub note: Option<String>,
+}
+
+impl CreatablePreorderArrival {
+    pub fn validate(&self) -> Result<&Self, String> {
+        if self.quantity <= 0 {
+            return Err("quantity must be greater than 0".to_string());
+        }
+
+        Ok(self)
+    }
+
+    // adds the batch to the stock and hands it out to the preorders of the item, oldest first. A
+    // preorder line is only marked as arrived once the batch covers all of it, the rest waits for
+    // the next batch
+    pub async fn insert(
+        &self,
+        pool: &sqlx::PgPool,
+        item_id: Uuid,
+        actor_id: Uuid,
+    ) -> Result<PreorderArrivalOutcome, StockUpdateError> {
+        let mut transaction = pool.begin().await?;
+
+        lock_items(transaction.as_mut(), &[item_id]).await?;
+
+        let stock_update_id = sqlx::query_scalar::<_, Uuid>(
+            r#"
+            INSERT INTO item_stock_updates (item_id, stock_added, reason, actor_id, note)
+            VALUES ($1, $2, 'restock', $3, $4)
+            RETURNING id
+            "#,
+        )
+        .bind(item_id)
+        .bind(self.quantity)
+        .bind(actor_id)
+        .bind(&self.note)
+        .fetch_one(transaction.as_mut())
+        .await?;
+
+        let query = format!(
+            "SELECT order_items.id, order_items.order_id,
+              order_items.amount - order_items.refunded_amount AS waiting
+            FROM order_items INNER JOIN orders ON order_items.order_id = orders.id
+            WHERE order_items.item_id = $1
+              AND order_items.is_preorder
+              AND order_items.preorder_arrived_at IS NULL
+              AND order_items.amount > order_items.refunded_amount
+              AND orders.shipment_status <> 'refunded'
+              AND orders.id IN ({STOCK_HOLDING_ORDERS})
+            ORDER BY orders.created_at ASC"
+        );
+
+        let lines = sqlx::query(&query)
+            .bind(item_id)
+            .fetch_all(transaction.as_mut())
+            .await?;
+
+        let mut remaining = self.quantity;
+        let mut arrived_ids = Vec::new();
+        let mut order_ids = Vec::new();
+
+        for line in lines {
+            let waiting = line.get::<i64, _>("waiting");
+
+            if waiting > remaining {
+                break;
+            }
+
+            remaining -= waiting;
+            arrived_ids.push(line.get::<Uuid, _>("id"));
+
+            let order_id = line.get::<Uuid, _>("order_id");
+
+            if !order_ids.contains(&order_id) {
+                order_ids.push(order_id);
+            }
+        }
+
+        sqlx::query("UPDATE order_items SET preorder_arrived_at = NOW() WHERE id = ANY($1)")
+            .bind(&arrived_ids)
+            .execute(transaction.as_mut())
+            .await?;
+
+        transaction.commit().await?;
+
+        Ok(PreorderArrivalOutcome {
+            stock_update_id,
+            fulfilled_order_ids: order_ids,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::Duration;
+
+    use super::*;
+
+    fn window(
+        preorder_start: Option<DateTime<Utc>>,
+        preorder_end: Option<DateTime<Utc>>,
+        preorder_cap: Option<i64>,
+    ) -> PreorderWindow {
+        PreorderWindow {
+            item_id: Uuid::new_v4(),
+            name: "Shirt".to_string(),
+            preorder_start,
+            preorder_end,
+            preorder_cap,
+        }
+    }
+
+    #[test]
+    fn without_a_window_items_are_sold_from_stock() {
+        let now = Utc::now();
+        let window = window(None, None, None);
+
+        assert_eq!(window.check(2, 2, now), Ok(false));
+        assert!(window.check(3, 2, now).is_err());
+    }
+
+    #[test]
+    fn before_the_window_opens() {
+        let now = Utc::now();
+        let window = window(Some(now + Duration::days(1)), None, None);
+
+        assert!(window.check(1, 10, now).is_err());
+    }
+
+    #[test]
+    fn open_window_sells_beyond_the_stock_up_to_the_cap() {
+        let now = Utc::now();
+        let window = window(
+            Some(now - Duration::days(1)),
+            Some(now + Duration::days(1)),
+            Some(5),
+        );
+
+        assert_eq!(window.check(7, 2, now), Ok(true));
+        assert!(window.check(8, 2, now).is_err());
+        // sold out stock eats into the cap
+        assert!(window.check(5, -1, now).is_err());
+    }
+
+    #[test]
+    fn open_window_without_a_cap_has_no_limit() {
+        let now = Utc::now();
+        let window = window(None, Some(now + Duration::days(1)), None);
+
+        assert_eq!(window.check(1000, 0, now), Ok(true));
+    }
+
+    #[test]
+    fn closed_window_is_sold_from_stock() {
+        let now = Utc::now();
+        let window = window(
+            Some(now - Duration::days(7)),
+            Some(now - Duration::days(1)),
+            Some(100),
+        );
+
+        assert_eq!(window.check(2, 2, now), Ok(false));
+        assert!(window
+            .check(3, 2, now)
+            .is_err_and(|err| err.contains("closed")));
+    }
+}
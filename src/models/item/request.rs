@@ -34,7 +34,18 @@ pub struct CreatableItem {
     pub discounted_price: Option<i64>,
     pub preorder_start: Option<DateTime<Utc>>,
     pub preorder_end: Option<DateTime<Utc>>,
+    // units that can be preordered beyond the stock while the window is open, no limit if None
+    pub preorder_cap: Option<i64>,
     pub variant_name: Option<String>,
+    // used by shops that charge shipping by weight
+    pub weight_grams: Option<i64>,
+    // scanned at the point of sale, unique within the shop
+    pub barcode: Option<String>,
+    // shop managers are emailed once the available stock drops to this
+    pub low_stock_threshold: Option<i64>,
+    // the most one order or one buyer across their orders can have, no limit if None
+    pub max_per_order: Option<i64>,
+    pub max_per_user: Option<i64>,
     pub colors: Option<Vec<String>>,
     // if images_url is not None, then it will be added to item_images and first image will be used as listing thumbnail
     pub images_url: Option<Vec<String>>,
@@ -54,6 +65,12 @@ pub struct UpdatableItem {
     pub discounted_price: Option<i64>,
     pub preorder_start: Option<DateTime<Utc>>,
     pub preorder_end: Option<DateTime<Utc>>,
+    pub preorder_cap: Option<i64>,
+    pub weight_grams: Option<i64>,
+    pub barcode: Option<String>,
+    pub low_stock_threshold: Option<i64>,
+    pub max_per_order: Option<i64>,
+    pub max_per_user: Option<i64>,
     // will delete all existing colors and replace with new ones
     pub colors: Option<Vec<String>>,
     // will delete all existing images and replace with new ones
@@ -86,6 +103,12 @@ impl UpdatableItem {
             param_count += 1;
         }
 
+        if let Some(barcode) = &self.barcode {
+            param_segments.push(format!("barcode = ${}", param_count));
+            string_params.push(barcode);
+            param_count += 1;
+        }
+
         if let Some(price) = &self.price {
             param_segments.push(format!("price = ${}", param_count));
             int_params.push(price);
@@ -98,6 +121,36 @@ impl UpdatableItem {
             param_count += 1;
         }
 
+        if let Some(weight_grams) = &self.weight_grams {
+            param_segments.push(format!("weight_grams = ${}", param_count));
+            int_params.push(weight_grams);
+            param_count += 1;
+        }
+
+        if let Some(preorder_cap) = &self.preorder_cap {
+            param_segments.push(format!("preorder_cap = ${}", param_count));
+            int_params.push(preorder_cap);
+            param_count += 1;
+        }
+
+        if let Some(low_stock_threshold) = &self.low_stock_threshold {
+            param_segments.push(format!("low_stock_threshold = ${}", param_count));
+            int_params.push(low_stock_threshold);
+            param_count += 1;
+        }
+
+        if let Some(max_per_order) = &self.max_per_order {
+            param_segments.push(format!("max_per_order = ${}", param_count));
+            int_params.push(max_per_order);
+            param_count += 1;
+        }
+
+        if let Some(max_per_user) = &self.max_per_user {
+            param_segments.push(format!("max_per_user = ${}", param_count));
+            int_params.push(max_per_user);
+            param_count += 1;
+        }
+
         if let Some(preorder_start) = &self.preorder_start {
             param_segments.push(format!("preorder_start = ${}", param_count));
             datetime_params.push(preorder_start);
@@ -242,8 +295,8 @@ impl CreatableItem {
         // insert item
         let item_id = sqlx::query(
             r#"
-            INSERT INTO items (name, listing_id, price, discounted_price, preorder_start, preorder_end, variant_name)
-            VALUES ($1, $2, $3, $4, $5, $6, $7)
+            INSERT INTO items (name, listing_id, price, discounted_price, preorder_start, preorder_end, variant_name, weight_grams, barcode, low_stock_threshold, preorder_cap, max_per_order, max_per_user, shop_id)
+            VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10, $11, $12, $13, (SELECT shop_id FROM listings WHERE id = $2))
             returning id
             "#,
         )
@@ -254,6 +307,12 @@ impl CreatableItem {
         .bind(&self.preorder_start)
         .bind(&self.preorder_end)
         .bind(&self.variant_name)
+        .bind(self.weight_grams)
+        .bind(&self.barcode)
+        .bind(self.low_stock_threshold)
+        .bind(self.preorder_cap)
+        .bind(self.max_per_order)
+        .bind(self.max_per_user)
         .fetch_one(pool)
         .await?;
 
@@ -363,8 +422,8 @@ impl CreatableItem {
             // insert item
             let item_id = sqlx::query(
                 r#"
-                INSERT INTO items (name, listing_id, price, discounted_price, preorder_start, preorder_end, variant_name)
-                VALUES ($1, $2, $3, $4, $5, $6, $7)
+                INSERT INTO items (name, listing_id, price, discounted_price, preorder_start, preorder_end, variant_name, weight_grams, barcode, low_stock_threshold, preorder_cap, max_per_order, max_per_user, shop_id)
+                VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10, $11, $12, $13, (SELECT shop_id FROM listings WHERE id = $2))
                 returning id
                 "#,
             )
@@ -375,6 +434,12 @@ impl CreatableItem {
             .bind(&item.preorder_start)
             .bind(&item.preorder_end)
             .bind(&item.variant_name)
+            .bind(item.weight_grams)
+            .bind(&item.barcode)
+            .bind(item.low_stock_threshold)
+            .bind(item.preorder_cap)
+            .bind(item.max_per_order)
+            .bind(item.max_per_user)
             .fetch_one(transaction.as_mut())
             .await?;
 
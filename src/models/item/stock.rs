@@ -0,0 +1,332 @@
+use std::{collections::HashMap, fmt::Display};
+
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use sqlx::{FromRow, PgConnection, PgExecutor, Type};
+use uuid::Uuid;
+
+// orders whose items are taken out of the available stock. Canceled orders give their items
+// back, and so does an unpaid order once its hold has run out, even before the sweeper gets
+// around to canceling it. Orders without a hold (e.g. cash on delivery) keep their items until
+// they are canceled, and so does an order with a payment slip waiting for review. Refunded items
+// stay sold, the ones that go back on sale are restocked through `item_stock_updates` when the
+// refund is made.
+pub const STOCK_HOLDING_ORDERS: &str = "SELECT id FROM orders
+  WHERE shipment_status <> 'canceled'
+  AND (
+    shipment_status <> 'awaiting_payment'
+    OR reserved_until IS NULL
+    OR reserved_until > NOW()
+    OR EXISTS (
+      SELECT 1 FROM payment_slips
+      WHERE payment_slips.order_id = orders.id AND payment_slips.status = 'pending'
+    )
+  )";
+
+// lifetime stock and the amount taken by orders for every item, meant to be joined on `item_id`.
+// amount_reserved is the part of amount_sold held by orders that are still waiting for payment
+pub fn item_stock_query() -> String {
+    format!(
+        "SELECT
+        items.id AS item_id,
+        CAST(COALESCE(stock_agg.stock_added, 0) AS INT8) AS lifetime_stock,
+        CAST(COALESCE(amount_agg.amount, 0) AS INT8) AS amount_sold,
+        CAST(COALESCE(amount_agg.amount_reserved, 0) AS INT8) AS amount_reserved
+      FROM
+        items
+        LEFT JOIN (
+          SELECT
+            item_id,
+            SUM(stock_added) AS stock_added
+          FROM item_stock_updates
+          GROUP BY item_id
+        ) AS stock_agg ON items.id = stock_agg.item_id
+        LEFT JOIN (
+          SELECT
+            item_id,
+            SUM(amount) AS amount,
+            SUM(amount) FILTER (
+              WHERE order_id IN (SELECT id FROM orders WHERE shipment_status = 'awaiting_payment')
+            ) AS amount_reserved
+          FROM order_items WHERE order_id IN ({STOCK_HOLDING_ORDERS})
+          GROUP BY item_id
+        ) AS amount_agg ON items.id = amount_agg.item_id"
+    )
+}
+
+#[derive(Debug, Clone, Copy, FromRow)]
+pub struct ItemStock {
+    pub item_id: Uuid,
+    pub lifetime_stock: i64,
+    pub amount_sold: i64,
+    pub amount_reserved: i64,
+}
+
+impl ItemStock {
+    pub fn available(&self) -> i64 {
+        self.lifetime_stock - self.amount_sold
+    }
+
+    pub fn levels(&self) -> StockLevels {
+        let sold = self.amount_sold - self.amount_reserved;
+
+        StockLevels {
+            on_hand: self.lifetime_stock - sold,
+            reserved: self.amount_reserved,
+            sold,
+            available: self.available(),
+        }
+    }
+
+    pub async fn get_by_item_id(
+        executor: impl PgExecutor<'_>,
+        item_id: Uuid,
+    ) -> Result<Self, sqlx::Error> {
+        let query = format!(
+            "SELECT * FROM ({}) AS item_stock WHERE item_id = $1",
+            item_stock_query()
+        );
+
+        sqlx::query_as::<_, Self>(&query)
+            .bind(item_id)
+            .fetch_one(executor)
+            .await
+    }
+
+    pub async fn get_by_item_ids(
+        executor: impl PgExecutor<'_>,
+        item_ids: &[Uuid],
+    ) -> Result<HashMap<Uuid, Self>, sqlx::Error> {
+        let query = format!(
+            "SELECT * FROM ({}) AS item_stock WHERE item_id = ANY($1)",
+            item_stock_query()
+        );
+
+        let stocks = sqlx::query_as::<_, Self>(&query)
+            .bind(item_ids)
+            .fetch_all(executor)
+            .await?;
+
+        Ok(stocks
+            .into_iter()
+            .map(|stock| (stock.item_id, stock))
+            .collect())
+    }
+}
+
+// locks the items until the surrounding transaction ends, so concurrent orders for the same item
+// check and take its stock one after another instead of both seeing the last unit. Rows are locked
+// in id order so two orders sharing several items can't deadlock.
+pub async fn lock_items(
+    connection: &mut PgConnection,
+    item_ids: &[Uuid],
+) -> Result<(), sqlx::Error> {
+    sqlx::query(
+        r#"
+        SELECT id FROM items
+        WHERE id = ANY($1)
+        ORDER BY id
+        FOR UPDATE
+        "#,
+    )
+    .bind(item_ids)
+    .execute(connection)
+    .await?;
+
+    Ok(())
+}
+
+// what the stock ledger adds up to. on_hand is still at the shop, including the reserved units
+// that are held for unpaid orders, and available is what can still be ordered
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+pub struct StockLevels {
+    pub on_hand: i64,
+    pub reserved: i64,
+    pub sold: i64,
+    pub available: i64,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum StockUpdateReason {
+    // the stock an item was created with
+    Initial,
+    Restock,
+    // damaged or lost goods taken out of the stock
+    WriteOff,
+    // a count that didn't match the ledger
+    Correction,
+    // refunded items put back on sale
+    Refund,
+}
+
+impl Display for StockUpdateReason {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let s = match self {
+            Self::Initial => "initial",
+            Self::Restock => "restock",
+            Self::WriteOff => "write_off",
+            Self::Correction => "correction",
+            Self::Refund => "refund",
+        };
+        write!(f, "{}", s)
+    }
+}
+
+impl Type<sqlx::Postgres> for StockUpdateReason {
+    fn type_info() -> sqlx::postgres::PgTypeInfo {
+        sqlx::postgres::PgTypeInfo::with_name("stock_update_reason")
+    }
+}
+
+impl sqlx::Encode<'_, sqlx::Postgres> for StockUpdateReason {
+    fn encode_by_ref(
+        &self,
+        buf: &mut <sqlx::Postgres as sqlx::database::HasArguments<'_>>::ArgumentBuffer,
+    ) -> sqlx::encode::IsNull {
+        let s: String = self.to_string();
+        <String as sqlx::Encode<sqlx::Postgres>>::encode(s, buf)
+    }
+}
+
+impl sqlx::Decode<'_, sqlx::Postgres> for StockUpdateReason {
+    fn decode(
+        value: <sqlx::Postgres as sqlx::database::HasValueRef<'_>>::ValueRef,
+    ) -> Result<Self, Box<dyn std::error::Error + 'static + Send + Sync>> {
+        let s: String = <String as sqlx::Decode<sqlx::Postgres>>::decode(value)?;
+        match s.as_str() {
+            "initial" => Ok(Self::Initial),
+            "restock" => Ok(Self::Restock),
+            "write_off" => Ok(Self::WriteOff),
+            "correction" => Ok(Self::Correction),
+            "refund" => Ok(Self::Refund),
+            _ => Err("invalid stock update reason".into()),
+        }
+    }
+}
+
+// one line of an item's stock ledger, the stock of an item is the sum of its quantities
+#[derive(Debug, Serialize, Deserialize, FromRow)]
+pub struct StockUpdate {
+    pub id: Uuid,
+    pub created_at: DateTime<Utc>,
+    pub item_id: Uuid,
+    // negative when stock is taken out
+    #[sqlx(rename = "stock_added")]
+    pub quantity: i64,
+    pub reason: StockUpdateReason,
+    pub actor_id: Option<Uuid>,
+    pub note: Option<String>,
+}
+
+impl StockUpdate {
+    pub async fn get_by_item_id(
+        pool: &sqlx::PgPool,
+        item_id: Uuid,
+    ) -> Result<Vec<Self>, sqlx::Error> {
+        sqlx::query_as::<_, Self>(
+            r#"
+            SELECT id, created_at, item_id, stock_added, reason, actor_id, note
+            FROM item_stock_updates
+            WHERE item_id = $1
+            ORDER BY created_at DESC
+            "#,
+        )
+        .bind(item_id)
+        .fetch_all(pool)
+        .await
+    }
+}
+
+#[derive(Debug)]
+pub enum StockUpdateError {
+    Rejected(String),
+    Database(sqlx::Error),
+}
+
+impl Display for StockUpdateError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Rejected(reason) => write!(f, "{}", reason),
+            Self::Database(err) => write!(f, "{}", err),
+        }
+    }
+}
+
+impl From<sqlx::Error> for StockUpdateError {
+    fn from(err: sqlx::Error) -> Self {
+        Self::Database(err)
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CreatableStockUpdate {
+    // signed, restocks add and write offs take away
+    pub quantity: i64,
+    pub reason: StockUpdateReason,
+    pub note: Option<String>,
+}
+
+impl CreatableStockUpdate {
+    pub fn validate(&self) -> Result<&Self, String> {
+        match self.reason {
+            // written by the item creation and refunds only
+            StockUpdateReason::Initial | StockUpdateReason::Refund => {
+                return Err(format!("stock updates can't be made with reason {}", self.reason))
+            }
+            StockUpdateReason::Restock if self.quantity <= 0 => {
+                return Err("a restock must add stock".to_string())
+            }
+            StockUpdateReason::WriteOff if self.quantity >= 0 => {
+                return Err("a write off must take stock away".to_string())
+            }
+            _ if self.quantity == 0 => return Err("quantity must not be 0".to_string()),
+            _ => (),
+        }
+
+        Ok(self)
+    }
+
+    // stock can only be taken away while the item has that much available, units held for orders
+    // are never written off from under them
+    pub async fn insert(
+        &self,
+        pool: &sqlx::PgPool,
+        item_id: Uuid,
+        actor_id: Uuid,
+    ) -> Result<Uuid, StockUpdateError> {
+        let mut transaction = pool.begin().await?;
+
+        lock_items(transaction.as_mut(), &[item_id]).await?;
+
+        if self.quantity < 0 {
+            let stock = ItemStock::get_by_item_id(transaction.as_mut(), item_id).await?;
+
+            if stock.available() + self.quantity < 0 {
+                return Err(StockUpdateError::Rejected(format!(
+                    "only {} are available to take out of the stock",
+                    stock.available()
+                )));
+            }
+        }
+
+        let id = sqlx::query_scalar::<_, Uuid>(
+            r#"
+            INSERT INTO item_stock_updates (item_id, stock_added, reason, actor_id, note)
+            VALUES ($1, $2, $3, $4, $5)
+            RETURNING id
+            "#,
+        )
+        .bind(item_id)
+        .bind(self.quantity)
+        .bind(self.reason)
+        .bind(actor_id)
+        .bind(&self.note)
+        .fetch_one(transaction.as_mut())
+        .await?;
+
+        transaction.commit().await?;
+
+        Ok(id)
+    }
+}
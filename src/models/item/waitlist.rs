@@ -0,0 +1,208 @@
+use std::time::Duration;
+
+use chrono::{DateTime, Utc};
+use lettre::transport::smtp::authentication::Credentials;
+use serde::{Deserialize, Serialize};
+use sqlx::{FromRow, PgPool, Row};
+use uuid::Uuid;
+
+use crate::utils::email::send_back_in_stock_email;
+
+use super::stock::{item_stock_query, ItemStock};
+
+// a signed in user waiting for an item to come back in stock. Subscriptions stay until the user
+// unsubscribes, they are armed while the item is sold out and emailed when a stock update brings
+// it back
+#[derive(Debug, Serialize, Deserialize, FromRow)]
+pub struct WaitlistSubscription {
+    pub id: Uuid,
+    pub created_at: DateTime<Utc>,
+    pub item_id: Uuid,
+    pub user_id: Uuid,
+    pub is_armed: bool,
+    pub last_notified_at: Option<DateTime<Utc>>,
+}
+
+impl WaitlistSubscription {
+    pub async fn subscribe(
+        pool: &PgPool,
+        item_id: Uuid,
+        user_id: Uuid,
+    ) -> Result<Self, sqlx::Error> {
+        let stock = ItemStock::get_by_item_id(pool, item_id).await?;
+
+        sqlx::query_as::<_, Self>(
+            r#"
+            INSERT INTO item_waitlist (item_id, user_id, is_armed, armed_at)
+            VALUES ($1, $2, $3, NOW())
+            ON CONFLICT (item_id, user_id) DO UPDATE SET item_id = EXCLUDED.item_id
+            RETURNING id, created_at, item_id, user_id, is_armed, last_notified_at
+            "#,
+        )
+        .bind(item_id)
+        .bind(user_id)
+        .bind(stock.available() <= 0)
+        .fetch_one(pool)
+        .await
+    }
+
+    // returns whether there was a subscription to remove
+    pub async fn unsubscribe(
+        pool: &PgPool,
+        item_id: Uuid,
+        user_id: Uuid,
+    ) -> Result<bool, sqlx::Error> {
+        let res = sqlx::query("DELETE FROM item_waitlist WHERE item_id = $1 AND user_id = $2")
+            .bind(item_id)
+            .bind(user_id)
+            .execute(pool)
+            .await?;
+
+        Ok(res.rows_affected() > 0)
+    }
+
+    // used by the link in the emails, which works without signing in. Returns the item id
+    pub async fn unsubscribe_by_token(
+        pool: &PgPool,
+        unsubscribe_token: Uuid,
+    ) -> Result<Option<Uuid>, sqlx::Error> {
+        sqlx::query_scalar::<_, Uuid>(
+            "DELETE FROM item_waitlist WHERE unsubscribe_token = $1 RETURNING item_id",
+        )
+        .bind(unsubscribe_token)
+        .fetch_optional(pool)
+        .await
+    }
+}
+
+// arms the subscriptions of sold out items and emails the armed ones whose item got a stock
+// update that brought it back. Stock that only came back because an unpaid order ran out of time
+// disarms without an email. Returns the number of emails sent
+pub async fn notify_waitlist(
+    pool: &PgPool,
+    credential: &Credentials,
+    api_base_url: &str,
+) -> Result<usize, sqlx::Error> {
+    let mut transaction = pool.begin().await?;
+
+    sqlx::query(&format!(
+        "UPDATE item_waitlist SET is_armed = TRUE, armed_at = NOW()
+        WHERE NOT is_armed AND item_id IN (
+          SELECT item_id FROM ({}) AS item_stock
+          WHERE lifetime_stock - amount_sold <= 0
+        )",
+        item_stock_query()
+    ))
+    .execute(transaction.as_mut())
+    .await?;
+
+    let restocked = sqlx::query(&format!(
+        "UPDATE item_waitlist SET is_armed = FALSE, last_notified_at = NOW()
+        WHERE is_armed
+          AND item_id IN (
+            SELECT item_id FROM ({}) AS item_stock
+            WHERE lifetime_stock - amount_sold > 0
+          )
+          AND EXISTS (
+            SELECT 1 FROM item_stock_updates
+            WHERE item_stock_updates.item_id = item_waitlist.item_id
+              AND item_stock_updates.created_at > item_waitlist.armed_at
+              AND item_stock_updates.stock_added > 0
+          )
+        RETURNING item_id, user_id, unsubscribe_token",
+        item_stock_query()
+    ))
+    .fetch_all(transaction.as_mut())
+    .await?;
+
+    sqlx::query(&format!(
+        "UPDATE item_waitlist SET is_armed = FALSE
+        WHERE is_armed AND item_id IN (
+          SELECT item_id FROM ({}) AS item_stock
+          WHERE lifetime_stock - amount_sold > 0
+        )",
+        item_stock_query()
+    ))
+    .execute(transaction.as_mut())
+    .await?;
+
+    transaction.commit().await?;
+
+    let mut count = 0;
+
+    for subscription in restocked {
+        let item_id = subscription.get::<Uuid, _>("item_id");
+        let unsubscribe_token = subscription.get::<Uuid, _>("unsubscribe_token");
+
+        // the subscriptions are already marked notified, one that can't be emailed mustn't keep
+        // the rest from being emailed
+        let recipient = sqlx::query(
+            r#"
+            SELECT users.email, items.name, items.variant_name
+            FROM users, items
+            WHERE users.id = $1 AND items.id = $2
+            "#,
+        )
+        .bind(subscription.get::<Uuid, _>("user_id"))
+        .bind(item_id)
+        .fetch_one(pool)
+        .await;
+
+        let recipient = match recipient {
+            Ok(recipient) => recipient,
+            Err(e) => {
+                println!("Error: {}", e);
+                continue;
+            }
+        };
+
+        let name = recipient.get::<String, _>("name");
+        let item_name = match recipient.get::<Option<String>, _>("variant_name") {
+            Some(variant_name) => format!("{} ({})", name, variant_name),
+            None => name,
+        };
+
+        let unsubscribe_url = format!(
+            "{}/waitlist/unsubscribe?token={}",
+            api_base_url.trim_end_matches('/'),
+            unsubscribe_token
+        );
+
+        let res = send_back_in_stock_email(
+            credential,
+            recipient.get::<String, _>("email"),
+            &item_name,
+            &unsubscribe_url,
+        );
+
+        match res {
+            Ok(()) => count += 1,
+            Err(e) => println!("Error: {}", e),
+        }
+    }
+
+    Ok(count)
+}
+
+pub fn spawn_waitlist_notifier(
+    pool: PgPool,
+    credential: Credentials,
+    api_base_url: String,
+    every: Duration,
+) {
+    actix_rt::spawn(async move {
+        let mut interval = actix_rt::time::interval(every);
+
+        loop {
+            interval.tick().await;
+
+            match notify_waitlist(&pool, &credential, &api_base_url).await {
+                Ok(count) if count > 0 => {
+                    println!("🔔 Told {} buyers their item is back in stock", count);
+                }
+                Ok(_) => {}
+                Err(err) => println!("🔥 Failed to notify the waitlist: {}", err),
+            }
+        }
+    });
+}
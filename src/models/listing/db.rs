@@ -4,7 +4,7 @@ use serde::{Deserialize, Serialize};
 use sqlx::FromRow;
 use uuid::Uuid;
 
-use crate::models::common::RangeQuery;
+use crate::models::{common::RangeQuery, item::stock::item_stock_query};
 
 use super::request::{QueryableListing, SortableListing};
 
@@ -23,6 +23,8 @@ pub struct ListingTable {
     pub preorder_end: Option<DateTime<Utc>>,
     pub lifetime_stock: Option<i64>,
     pub amount_sold: Option<i64>,
+    pub max_per_order: Option<i64>,
+    pub max_per_user: Option<i64>,
 }
 
 impl ListingTable {
@@ -61,10 +63,11 @@ impl ListingTable {
     }
 
     fn get_default_query() -> String {
-        "SELECT
+        format!(
+            "SELECT
         listings.*,
-        CAST(SUM(stock_added) AS INT8) AS lifetime_stock,
-        CAST(SUM(amount) AS INT8) AS amount_sold,
+        CAST(SUM(item_stock.lifetime_stock) AS INT8) AS lifetime_stock,
+        CAST(SUM(item_stock.amount_sold) AS INT8) AS amount_sold,
         min(price) as price,
         min(discounted_price) as discounted_price,
         MIN(preorder_start) AS preorder_start,
@@ -72,24 +75,10 @@ impl ListingTable {
       FROM
         listings
         INNER JOIN items ON listings.id = items.listing_id
-        LEFT JOIN (
-          SELECT
-            item_id,
-            SUM(stock_added) AS stock_added
-          FROM item_stock_updates
-          GROUP BY item_id
-        ) AS stock_agg ON items.id = stock_agg.item_id
-        LEFT JOIN (
-          SELECT
-            item_id,
-            SUM(amount) AS amount
-          FROM order_items WHERE order_id IN (
-            SELECT id FROM orders WHERE NOT (shipment_status = 'canceled' OR (created_at > NOW() - INTERVAL '3 minute' AND is_paid = FALSE))
-          )
-          GROUP BY item_id
-        ) AS amount_agg ON items.id = amount_agg.item_id
-        "
-        .to_string()
+        LEFT JOIN ({}) AS item_stock ON items.id = item_stock.item_id
+        ",
+            item_stock_query()
+        )
     }
 
     fn get_count_query() -> String {
@@ -46,6 +46,9 @@ pub struct DefaultListing {
     pub discounted_price: Option<i64>,
     pub lifetime_stock: i64,
     pub amount_sold: i64,
+    // limits on all the variants together, see `CreatableOrder::place`
+    pub max_per_order: Option<i64>,
+    pub max_per_user: Option<i64>,
     pub variants: Vec<Item>,
     pub categories: Vec<MultiLangString>,
     pub is_hidden: bool,
@@ -64,6 +67,9 @@ pub struct DetailedListing {
     pub discounted_price: Option<i64>,
     pub lifetime_stock: i64,
     pub amount_sold: i64,
+    // limits on all the variants together, see `CreatableOrder::place`
+    pub max_per_order: Option<i64>,
+    pub max_per_user: Option<i64>,
     pub variants: Vec<Item>,
     pub collections: Vec<Collection>,
     pub categories: Vec<MultiLangString>,
@@ -160,6 +166,8 @@ impl DefaultListing {
             discounted_price: listing.discounted_price,
             lifetime_stock: listing.lifetime_stock.unwrap_or(0),
             amount_sold: listing.amount_sold.unwrap_or(0),
+            max_per_order: listing.max_per_order,
+            max_per_user: listing.max_per_user,
             categories,
             shop: Shop::get_by_id(
                 pool,
@@ -248,6 +256,8 @@ impl DetailedListing {
             discounted_price: listing.discounted_price,
             lifetime_stock: listing.lifetime_stock.unwrap_or(0),
             amount_sold: listing.amount_sold.unwrap_or(0),
+            max_per_order: listing.max_per_order,
+            max_per_user: listing.max_per_user,
             categories,
             shop: Shop::get_by_id(
                 pool,
@@ -34,6 +34,9 @@ pub struct UpdatableListing {
     pub description: Option<String>,
     pub thumbnail_url: Option<String>,
     pub is_hidden: Option<bool>,
+    // the most of all the variants together one order or one buyer can have
+    pub max_per_order: Option<i64>,
+    pub max_per_user: Option<i64>,
 }
 
 impl UpdatableListing {
@@ -48,6 +51,7 @@ impl UpdatableListing {
         let mut param_segments = Vec::new();
         let mut string_params = Vec::new();
         let mut bool_params = Vec::new();
+        let mut int_params = Vec::new();
 
         if let Some(name) = &self.name {
             param_segments.push(format!("name = ${}", param_count));
@@ -73,6 +77,18 @@ impl UpdatableListing {
             param_count += 1;
         }
 
+        if let Some(max_per_order) = &self.max_per_order {
+            param_segments.push(format!("max_per_order = ${}", param_count));
+            int_params.push(max_per_order);
+            param_count += 1;
+        }
+
+        if let Some(max_per_user) = &self.max_per_user {
+            param_segments.push(format!("max_per_user = ${}", param_count));
+            int_params.push(max_per_user);
+            param_count += 1;
+        }
+
         query.push_str(&param_segments.join(", "));
 
         query.push_str(" WHERE id = $");
@@ -89,6 +105,10 @@ impl UpdatableListing {
             query_builder = query_builder.bind(param);
         }
 
+        for param in int_params {
+            query_builder = query_builder.bind(param);
+        }
+
         query_builder = query_builder.bind(listing_id);
 
         query_builder.execute(pool).await?;
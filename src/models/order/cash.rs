@@ -0,0 +1,89 @@
+use std::fmt::Display;
+
+use sqlx::Row;
+use uuid::Uuid;
+
+use super::{
+    db::{OrderStatus, PaymentMethod},
+    status::{transition_order_status, OrderStatusError},
+};
+
+#[derive(Debug)]
+pub enum CashCollectionError {
+    Rejected(String),
+    Status(OrderStatusError),
+}
+
+impl Display for CashCollectionError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Rejected(reason) => write!(f, "{}", reason),
+            Self::Status(err) => write!(f, "{}", err),
+        }
+    }
+}
+
+impl From<OrderStatusError> for CashCollectionError {
+    fn from(err: OrderStatusError) -> Self {
+        Self::Status(err)
+    }
+}
+
+impl From<sqlx::Error> for CashCollectionError {
+    fn from(err: sqlx::Error) -> Self {
+        Self::Status(OrderStatusError::Database(err))
+    }
+}
+
+// hands a cash on delivery order over to the buyer, which pays and verifies it in one go. The
+// collection itself is recorded by the transition to delivered
+pub async fn collect_cash(
+    pool: &sqlx::PgPool,
+    order_id: Uuid,
+    collector_id: Uuid,
+    amount_received: Option<i64>,
+    note: Option<String>,
+) -> Result<(), CashCollectionError> {
+    let mut transaction = pool.begin().await?;
+
+    let order = sqlx::query(
+        r#"
+        SELECT payment_method, total_price FROM orders
+        WHERE id = $1
+        FOR UPDATE
+        "#,
+    )
+    .bind(order_id)
+    .fetch_one(transaction.as_mut())
+    .await?;
+
+    if order.get::<PaymentMethod, _>("payment_method") != PaymentMethod::Cod {
+        return Err(CashCollectionError::Rejected(
+            "only cash on delivery orders are paid at handover".to_string(),
+        ));
+    }
+
+    let total_price = order.get::<i64, _>("total_price");
+
+    if let Some(amount_received) = amount_received {
+        if amount_received != total_price {
+            return Err(CashCollectionError::Rejected(format!(
+                "received {} baht but the order total is {} baht",
+                amount_received, total_price
+            )));
+        }
+    }
+
+    transition_order_status(
+        transaction.as_mut(),
+        order_id,
+        OrderStatus::Delivered,
+        Some(collector_id),
+        note,
+    )
+    .await?;
+
+    transaction.commit().await?;
+
+    Ok(())
+}
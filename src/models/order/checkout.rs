@@ -0,0 +1,211 @@
+use chrono::{DateTime, Utc};
+use mysk_lib::models::common::requests::FetchLevel;
+use serde::{Deserialize, Serialize};
+use sqlx::{FromRow, Row};
+use uuid::Uuid;
+
+use crate::models::item::stock::lock_items;
+
+use super::{
+    db::PaymentMethod,
+    limits::lock_item_listings,
+    provider::{ChargeRequest, PaymentProvider},
+    request::{CreatableOrder, OrderCreationError},
+    Order,
+};
+
+// orders from several shops placed together, paid for with a single payment under the session's
+// reference
+#[derive(Debug, Serialize, Deserialize, FromRow)]
+pub struct CheckoutSessionTable {
+    pub id: Uuid,
+    pub created_at: Option<DateTime<Utc>>,
+    pub ref_id: String,
+    pub buyer_id: Option<Uuid>,
+    pub payment_method: PaymentMethod,
+    // the sum of the member orders' totals
+    pub total_price: i64,
+    pub qr_code_file: Option<String>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct CheckoutSession {
+    pub id: Uuid,
+    pub created_at: Option<DateTime<Utc>>,
+    pub ref_id: String,
+    pub payment_method: PaymentMethod,
+    pub total_price: i64,
+    pub qr_code_file: Option<String>,
+    pub order_ids: Vec<Uuid>,
+}
+
+impl CheckoutSession {
+    pub async fn get_by_id(pool: &sqlx::PgPool, id: Uuid) -> Result<Self, sqlx::Error> {
+        let session = sqlx::query_as::<_, CheckoutSessionTable>(
+            "SELECT * FROM checkout_sessions WHERE id = $1",
+        )
+        .bind(id)
+        .fetch_one(pool)
+        .await?;
+
+        let order_ids = sqlx::query(
+            "SELECT id FROM orders WHERE checkout_session_id = $1 ORDER BY created_at ASC",
+        )
+        .bind(id)
+        .fetch_all(pool)
+        .await?
+        .into_iter()
+        .map(|row| row.get::<Uuid, _>("id"))
+        .collect();
+
+        Ok(Self {
+            id: session.id,
+            created_at: session.created_at,
+            ref_id: session.ref_id,
+            payment_method: session.payment_method,
+            total_price: session.total_price,
+            qr_code_file: session.qr_code_file,
+            order_ids,
+        })
+    }
+}
+
+// short enough for the payment providers' reference numbers, which allow 15 characters
+fn generate_ref_id() -> String {
+    format!("CS{}", &Uuid::new_v4().simple().to_string()[..12].to_uppercase())
+}
+
+// places every order or none of them. A single order is placed on its own and paid under its own
+// reference, more than one share a checkout session and a single payment
+pub async fn checkout(
+    pool: &sqlx::PgPool,
+    payment_provider: &impl PaymentProvider,
+    orders: &[CreatableOrder],
+    user_id: Option<Uuid>,
+    stock_hold: chrono::Duration,
+) -> Result<Vec<Uuid>, OrderCreationError> {
+    if let [order] = orders {
+        let order_id = order
+            .insert(pool, payment_provider, user_id, stock_hold)
+            .await?;
+
+        return Ok(vec![order_id]);
+    }
+
+    let payment_method = match orders.first() {
+        Some(order) => order.payment_method(),
+        None => return Ok(Vec::new()),
+    };
+
+    if orders
+        .iter()
+        .any(|order| order.payment_method() != payment_method)
+    {
+        return Err(OrderCreationError::Rejected(
+            "orders checked out together must use the same payment method".to_string(),
+        ));
+    }
+
+    let mut transaction = pool.begin().await?;
+
+    // each order locks its items and then their listings as it is placed, so a session with the
+    // same shops in the other order would take them the other way around and could deadlock.
+    // Locking all of them up front keeps every session to id order
+    let mut item_ids = orders
+        .iter()
+        .flat_map(|order| order.item_ids())
+        .collect::<Vec<Uuid>>();
+
+    item_ids.sort();
+    item_ids.dedup();
+
+    lock_items(transaction.as_mut(), &item_ids).await?;
+    lock_item_listings(transaction.as_mut(), &item_ids).await?;
+
+    let ref_id = generate_ref_id();
+
+    let session_id = sqlx::query(
+        r#"
+        INSERT INTO checkout_sessions (ref_id, buyer_id, payment_method, total_price)
+        VALUES ($1, $2, $3, 0)
+        RETURNING id
+        "#,
+    )
+    .bind(&ref_id)
+    .bind(user_id)
+    .bind(payment_method)
+    .fetch_one(transaction.as_mut())
+    .await?
+    .get::<Uuid, _>("id");
+
+    let mut order_ids = Vec::new();
+    let mut total_price = 0;
+
+    for order in orders {
+        let placed = order
+            .place(
+                transaction.as_mut(),
+                pool,
+                user_id,
+                stock_hold,
+                Some(session_id),
+            )
+            .await?;
+
+        // a shop paid straight to its own PromptPay number can't share a payment with others
+        if payment_method == PaymentMethod::Promptpay && !placed.shop.use_payment_gateway {
+            return Err(OrderCreationError::Rejected(format!(
+                "{} takes PromptPay payments directly, its order has to be checked out on its own",
+                placed.shop.name_th
+            )));
+        }
+
+        order_ids.push(placed.order_id);
+        total_price += placed.total_price;
+    }
+
+    sqlx::query("UPDATE checkout_sessions SET total_price = $1 WHERE id = $2")
+        .bind(total_price)
+        .bind(session_id)
+        .execute(transaction.as_mut())
+        .await?;
+
+    transaction.commit().await?;
+
+    if payment_method != PaymentMethod::Promptpay {
+        return Ok(order_ids);
+    }
+
+    // the buyer's details come from the first order, they are the same on every order
+    let order = Order::get_by_id(
+        pool,
+        order_ids[0],
+        Some(&FetchLevel::Default),
+        Some(&FetchLevel::Compact),
+    )
+    .await?;
+
+    let charge = ChargeRequest {
+        reference_no: ref_id,
+        amount: total_price,
+        detail: Some(format!("{} orders", order_ids.len())),
+        ..ChargeRequest::from(order)
+    };
+
+    let qr_code = payment_provider.create_charge(&charge).await.ok();
+
+    // every member order shows the same QR code so it is paid whichever order the buyer opens
+    sqlx::query("UPDATE checkout_sessions SET qr_code_file = $1 WHERE id = $2")
+        .bind(&qr_code)
+        .bind(session_id)
+        .execute(pool)
+        .await?;
+
+    sqlx::query("UPDATE orders SET qr_code_file = $1 WHERE checkout_session_id = $2")
+        .bind(&qr_code)
+        .bind(session_id)
+        .execute(pool)
+        .await?;
+
+    Ok(order_ids)
+}
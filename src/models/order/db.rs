@@ -6,34 +6,96 @@ use serde::{Deserialize, Serialize};
 use sqlx::types::Uuid;
 use sqlx::{FromRow, Type};
 
-use super::request::{QueryableOrder, SortableOrder};
+use super::{
+    request::{QueryableOrder, SortableOrder},
+    shipment::ShippingCarrier,
+};
 
-#[derive(Debug, Serialize, Deserialize, Clone, Copy)]
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq)]
 #[serde(rename_all = "snake_case")]
 pub enum OrderStatus {
-    NotShippedOut,
-    Pending,
-    Canceled,
+    AwaitingPayment,
+    Paid,
+    Verified,
+    Packed,
+    Shipped,
+    ReadyForPickup,
     Delivered,
+    Canceled,
+    Refunded,
 }
 
-// impl OrderStatus {
-//     pub fn to_string(&self) -> String {
-//         match self {
-//             Self::NotShippedOut => "not_shipped_out".to_string(),
-//             Self::Pending => "pending".to_string(),
-//             Self::Delivered => "delivered".to_string(),
-//         }
-//     }
-// }
+impl OrderStatus {
+    pub fn can_transition_to(
+        &self,
+        to: OrderStatus,
+        delivery_type: DeliveryType,
+        payment_method: PaymentMethod,
+    ) -> bool {
+        let is_cod = payment_method == PaymentMethod::Cod;
+
+        match (self, to) {
+            (Self::AwaitingPayment, Self::Paid) => !is_cod,
+            // cash on delivery is paid at handover, so the order is packed and sent out unpaid
+            (Self::AwaitingPayment, Self::Packed) => is_cod,
+            (Self::Paid, Self::Verified) => true,
+            (Self::Verified, Self::Packed) => true,
+            (Self::Packed, Self::Shipped) => delivery_type == DeliveryType::Delivery,
+            (Self::Packed, Self::ReadyForPickup) => delivery_type != DeliveryType::Delivery,
+            (Self::Shipped, Self::Delivered) => true,
+            (Self::ReadyForPickup, Self::Delivered) => true,
+            // point of sale orders are handed over at the booth as soon as they are paid
+            (Self::AwaitingPayment, Self::Delivered) => payment_method == PaymentMethod::POSCash,
+            (Self::Verified, Self::Delivered) => delivery_type == DeliveryType::POS,
+            // once money has been received the order can only be refunded
+            (Self::AwaitingPayment, Self::Canceled) => true,
+            (Self::Packed | Self::Shipped | Self::ReadyForPickup, Self::Canceled) => is_cod,
+            (
+                Self::Paid
+                | Self::Verified
+                | Self::Packed
+                | Self::Shipped
+                | Self::ReadyForPickup
+                | Self::Delivered,
+                Self::Refunded,
+            ) => true,
+            _ => false,
+        }
+    }
+
+    // the values of is_paid and is_verified implied by the status, canceled and refunded orders
+    // keep whatever they had before. Cash on delivery orders are only paid once delivered
+    pub fn payment_flags(&self, payment_method: PaymentMethod) -> Option<(bool, bool)> {
+        match self {
+            Self::AwaitingPayment => Some((false, false)),
+            Self::Packed | Self::Shipped | Self::ReadyForPickup
+                if payment_method == PaymentMethod::Cod =>
+            {
+                Some((false, false))
+            }
+            Self::Paid => Some((true, false)),
+            Self::Verified
+            | Self::Packed
+            | Self::Shipped
+            | Self::ReadyForPickup
+            | Self::Delivered => Some((true, true)),
+            Self::Canceled | Self::Refunded => None,
+        }
+    }
+}
 
 impl Display for OrderStatus {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         let s = match self {
-            Self::NotShippedOut => "not_shipped_out",
-            Self::Pending => "pending",
-            Self::Canceled => "canceled",
+            Self::AwaitingPayment => "awaiting_payment",
+            Self::Paid => "paid",
+            Self::Verified => "verified",
+            Self::Packed => "packed",
+            Self::Shipped => "shipped",
+            Self::ReadyForPickup => "ready_for_pickup",
             Self::Delivered => "delivered",
+            Self::Canceled => "canceled",
+            Self::Refunded => "refunded",
         };
         write!(f, "{}", s)
     }
@@ -41,7 +103,7 @@ impl Display for OrderStatus {
 
 impl Default for OrderStatus {
     fn default() -> Self {
-        Self::NotShippedOut
+        Self::AwaitingPayment
     }
 }
 
@@ -68,10 +130,15 @@ impl sqlx::Decode<'_, sqlx::Postgres> for OrderStatus {
     ) -> Result<Self, Box<dyn std::error::Error + 'static + Send + Sync>> {
         let s: String = <String as sqlx::Decode<sqlx::Postgres>>::decode(value)?;
         match s.as_str() {
-            "not_shipped_out" => Ok(Self::NotShippedOut),
-            "pending" => Ok(Self::Pending),
+            "awaiting_payment" => Ok(Self::AwaitingPayment),
+            "paid" => Ok(Self::Paid),
+            "verified" => Ok(Self::Verified),
+            "packed" => Ok(Self::Packed),
+            "shipped" => Ok(Self::Shipped),
+            "ready_for_pickup" => Ok(Self::ReadyForPickup),
             "delivered" => Ok(Self::Delivered),
             "canceled" => Ok(Self::Canceled),
+            "refunded" => Ok(Self::Refunded),
             _ => Err("invalid order status".into()),
         }
     }
@@ -128,7 +195,7 @@ impl sqlx::Decode<'_, sqlx::Postgres> for DeliveryType {
     }
 }
 
-#[derive(Debug, Serialize, Deserialize, Clone, Copy)]
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq)]
 #[serde(rename_all = "snake_case")]
 pub enum PaymentMethod {
     Cod,
@@ -204,6 +271,14 @@ pub struct OrderTable {
     pub contact_phone_number: Option<String>,
     pub ref_id: String,
     pub qr_code_file: Option<String>,
+    pub reserved_until: Option<DateTime<Utc>>,
+    pub discount_code_id: Option<Uuid>,
+    pub discount_amount: i64,
+    pub checkout_session_id: Option<Uuid>,
+    pub pickup_code: Option<String>,
+    pub shipping_carrier: Option<ShippingCarrier>,
+    pub tracking_number: Option<String>,
+    pub is_preorder: bool,
 }
 
 impl OrderTable {
@@ -672,6 +747,12 @@ pub struct OrderItemTable {
     pub order_id: sqlx::types::Uuid,
     pub item_id: sqlx::types::Uuid,
     pub amount: i64,
+    // how many of `amount` have been refunded
+    pub refunded_amount: i64,
+    // ordered while the item's preorder window was open
+    pub is_preorder: bool,
+    // when the preorder batch covering this line arrived
+    pub preorder_arrived_at: Option<DateTime<Utc>>,
 }
 
 impl OrderItemTable {
@@ -691,3 +772,169 @@ impl OrderItemTable {
         Ok(result)
     }
 }
+
+#[derive(Debug, Serialize, Deserialize, FromRow)]
+pub struct OrderStatusHistoryTable {
+    pub id: Uuid,
+    pub created_at: Option<DateTime<Utc>>,
+    pub order_id: Uuid,
+    pub from_status: Option<OrderStatus>,
+    pub to_status: OrderStatus,
+    pub actor_id: Option<Uuid>,
+    pub note: Option<String>,
+}
+
+impl OrderStatusHistoryTable {
+    pub async fn get_by_order_id(
+        pool: &sqlx::PgPool,
+        order_id: Uuid,
+    ) -> Result<Vec<Self>, sqlx::Error> {
+        let result = sqlx::query_as::<_, Self>(
+            r#"
+            SELECT * FROM order_status_history
+            WHERE order_id = $1
+            ORDER BY created_at ASC
+            "#,
+        )
+        .bind(order_id)
+        .fetch_all(pool)
+        .await?;
+        Ok(result)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use DeliveryType::{Delivery, SchoolPickup, POS};
+    use OrderStatus::*;
+    use PaymentMethod::{Cod, POSCash, Promptpay};
+
+    const STATUSES: [OrderStatus; 9] = [
+        AwaitingPayment,
+        Paid,
+        Verified,
+        Packed,
+        Shipped,
+        ReadyForPickup,
+        Delivered,
+        Canceled,
+        Refunded,
+    ];
+
+    #[test]
+    fn prepaid_delivery_goes_through_shipping() {
+        let path = [AwaitingPayment, Paid, Verified, Packed, Shipped, Delivered];
+
+        for pair in path.windows(2) {
+            assert!(pair[0].can_transition_to(pair[1], Delivery, Promptpay));
+        }
+
+        assert!(!Packed.can_transition_to(ReadyForPickup, Delivery, Promptpay));
+        assert!(!AwaitingPayment.can_transition_to(Packed, Delivery, Promptpay));
+    }
+
+    #[test]
+    fn prepaid_pickup_is_made_ready_instead_of_shipped() {
+        let path = [
+            AwaitingPayment,
+            Paid,
+            Verified,
+            Packed,
+            ReadyForPickup,
+            Delivered,
+        ];
+
+        for pair in path.windows(2) {
+            assert!(pair[0].can_transition_to(pair[1], SchoolPickup, Promptpay));
+        }
+
+        assert!(!Packed.can_transition_to(Shipped, SchoolPickup, Promptpay));
+    }
+
+    #[test]
+    fn cash_on_delivery_is_packed_unpaid_and_can_be_canceled_on_the_way() {
+        assert!(AwaitingPayment.can_transition_to(Packed, Delivery, Cod));
+        assert!(!AwaitingPayment.can_transition_to(Paid, Delivery, Cod));
+
+        for status in [Packed, Shipped, ReadyForPickup] {
+            assert!(status.can_transition_to(Canceled, Delivery, Cod));
+            assert!(!status.can_transition_to(Canceled, Delivery, Promptpay));
+        }
+    }
+
+    #[test]
+    fn point_of_sale_orders_are_handed_over_at_the_booth() {
+        assert!(AwaitingPayment.can_transition_to(Delivered, POS, POSCash));
+        assert!(!AwaitingPayment.can_transition_to(Delivered, POS, Promptpay));
+        assert!(Verified.can_transition_to(Delivered, POS, Promptpay));
+        assert!(!Verified.can_transition_to(Delivered, Delivery, Promptpay));
+        assert!(!Verified.can_transition_to(Delivered, SchoolPickup, Promptpay));
+    }
+
+    #[test]
+    fn paid_orders_are_refunded_rather_than_canceled() {
+        for status in [Paid, Verified, Packed, Shipped, ReadyForPickup, Delivered] {
+            assert!(status.can_transition_to(Refunded, Delivery, Promptpay));
+        }
+
+        for status in [Paid, Verified] {
+            assert!(!status.can_transition_to(Canceled, Delivery, Promptpay));
+        }
+
+        assert!(AwaitingPayment.can_transition_to(Canceled, Delivery, Promptpay));
+        assert!(!AwaitingPayment.can_transition_to(Refunded, Delivery, Promptpay));
+    }
+
+    #[test]
+    fn canceled_and_refunded_orders_are_final() {
+        for delivery_type in [Delivery, SchoolPickup, POS] {
+            for payment_method in [Cod, Promptpay, POSCash] {
+                for to in STATUSES {
+                    assert!(!Canceled.can_transition_to(to, delivery_type, payment_method));
+                    assert!(!Refunded.can_transition_to(to, delivery_type, payment_method));
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn no_status_transitions_to_itself_or_back_to_awaiting_payment() {
+        for from in STATUSES {
+            assert!(!from.can_transition_to(from, Delivery, Promptpay));
+            assert!(!from.can_transition_to(AwaitingPayment, Delivery, Promptpay));
+        }
+    }
+
+    #[test]
+    fn payment_flags_follow_the_status() {
+        assert_eq!(
+            AwaitingPayment.payment_flags(Promptpay),
+            Some((false, false))
+        );
+        assert_eq!(Paid.payment_flags(Promptpay), Some((true, false)));
+
+        for status in [Verified, Packed, Shipped, ReadyForPickup, Delivered] {
+            assert_eq!(status.payment_flags(Promptpay), Some((true, true)));
+            assert_eq!(status.payment_flags(POSCash), Some((true, true)));
+        }
+    }
+
+    #[test]
+    fn cash_on_delivery_is_only_paid_once_delivered() {
+        for status in [AwaitingPayment, Packed, Shipped, ReadyForPickup] {
+            assert_eq!(status.payment_flags(Cod), Some((false, false)));
+        }
+
+        assert_eq!(Delivered.payment_flags(Cod), Some((true, true)));
+    }
+
+    #[test]
+    fn canceled_and_refunded_keep_their_flags() {
+        for payment_method in [Cod, Promptpay, POSCash] {
+            assert_eq!(Canceled.payment_flags(payment_method), None);
+            assert_eq!(Refunded.payment_flags(payment_method), None);
+        }
+    }
+}
@@ -5,8 +5,12 @@ use sqlx::{PgPool, Row};
 
 use crate::models::{
     auth::user::User,
+    discount::db::DiscountCodeTable,
     order::{
         db::{DeliveryType, OrderStatus, PaymentMethod},
+        pickup::OrderPickup,
+        shipment::ShipmentTracking,
+        status::OrderStatusHistory,
         OrderItem,
     },
 };
@@ -19,7 +23,17 @@ pub struct DefaultOrder {
     pub is_paid: bool,
     pub is_verified: bool,
     pub shipment_status: OrderStatus,
+    pub status_history: Vec<OrderStatusHistory>,
+    // unpaid orders are canceled once this passes
+    pub reserved_until: Option<DateTime<Utc>>,
     pub total_price: i64,
+    pub discount_code: Option<String>,
+    // already taken off total_price
+    pub discount_amount: i64,
+    // set when the order was paid together with orders from other shops
+    pub checkout_session_id: Option<sqlx::types::Uuid>,
+    // some of the items are preorders and ship once their batch arrives
+    pub is_preorder: bool,
     pub delivery_type: DeliveryType,
     pub items: Vec<OrderItem>,
     pub street_address_line_1: Option<String>,
@@ -27,7 +41,13 @@ pub struct DefaultOrder {
     pub zip_code: Option<i64>,
     pub province: Option<String>,
     pub district: Option<String>,
+    // set once a delivery order is shipped
+    pub tracking: Option<ShipmentTracking>,
     pub pickup_location: Option<Vec<String>>,
+    // shown at the booth to collect a school pickup order
+    pub pickup_code: Option<String>,
+    // who handed the order over, once it has been picked up
+    pub pickup: Option<OrderPickup>,
     pub buyer: Option<User>,
     pub receiver_name: String,
     pub payment_method: PaymentMethod,
@@ -103,38 +123,22 @@ impl DefaultOrder {
             None
         };
 
-        // let promptpay_qr_code_url = match order.payment_method {
-        //     PaymentMethod::Promptpay => {
-        //         // get shop promptpay number and make sure it is not null
-        //         let promptpay_number = sqlx::query(
-        //             r#"
-        //             SELECT promptpay_number
-        //             FROM shops
-        //             INNER JOIN listings ON shops.id = listings.shop_id
-        //             INNER JOIN items ON listings.id = items.listing_id
-        //             INNER JOIN order_items ON items.id = order_items.item_id
-        //             WHERE order_items.order_id = $1
-        //             "#,
-        //         )
-        //         .bind(order.id)
-        //         .fetch_one(pool)
-        //         .await?
-        //         .get::<Option<String>, _>("promptpay_number");
+        let discount_code = match order.discount_code_id {
+            Some(discount_code_id) => Some(
+                DiscountCodeTable::get_by_id(pool, discount_code_id)
+                    .await?
+                    .code,
+            ),
+            None => None,
+        };
 
-        //         match promptpay_number {
-        //             Some(promptpay_number) => {
-        //                 let promptpay_qr_code_url = format!(
-        //                     "https://promptpay.io/{}/{}.png",
-        //                     promptpay_number, order.total_price
-        //                 );
+        let status_history =
+            OrderStatusHistory::get_by_order_id(pool, order.id, descendant_fetch_level).await?;
 
-        //                 Some(promptpay_qr_code_url)
-        //             }
-        //             None => None,
-        //         }
-        //     }
-        //     _ => None,
-        // };
+        let pickup = match order.delivery_type {
+            DeliveryType::SchoolPickup => OrderPickup::get_by_order_id(pool, order.id).await?,
+            _ => None,
+        };
 
         Ok(Self {
             id: order.id,
@@ -143,6 +147,9 @@ impl DefaultOrder {
             is_paid: order.is_paid,
             is_verified: order.is_verified,
             shipment_status: order.shipment_status,
+            status_history,
+            reserved_until: order.reserved_until,
+            is_preorder: order.is_preorder,
             delivery_type: order.delivery_type,
             items,
             street_address_line_1: order.street_address_line_1,
@@ -150,10 +157,16 @@ impl DefaultOrder {
             zip_code: order.zip_code,
             province: order.province,
             district: order.district,
+            tracking: ShipmentTracking::new(order.shipping_carrier, order.tracking_number),
             pickup_location,
+            pickup_code: order.pickup_code,
+            pickup,
             buyer: user,
             receiver_name: order.receiver_name,
             total_price: order.total_price,
+            discount_code,
+            discount_amount: order.discount_amount,
+            checkout_session_id: order.checkout_session_id,
             payment_method: order.payment_method,
             payment_slip_url: order.payment_slip_url,
             promptpay_qr_code_url: order.qr_code_file,
@@ -1,10 +1,10 @@
 use base64::{engine::general_purpose, prelude::*};
 use reqwest::{self, Client};
 use serde::{Deserialize, Serialize};
-use sqlx::Row;
-use uuid::Uuid;
 
-use super::Order;
+use super::provider::{
+    ChargeRequest, PaymentCallback, PaymentProvider, PaymentProviderError, ProviderTransaction,
+};
 
 #[derive(Debug, Deserialize, Serialize)]
 #[serde(rename_all = "camelCase")]
@@ -47,6 +47,21 @@ pub enum ResultCode {
     Unknown,
 }
 
+impl ResultCode {
+    fn code(&self) -> &'static str {
+        match self {
+            ResultCode::Success => "00",
+            ResultCode::InvalidReferenceNo => "11",
+            ResultCode::InvalidGBReferenceNo => "12",
+            ResultCode::InvalidAmount => "14",
+            ResultCode::DuplicateTransaction => "21",
+            ResultCode::OverDue => "22",
+            ResultCode::SystemError => "99",
+            ResultCode::Unknown => "unknown",
+        }
+    }
+}
+
 impl<'de> Deserialize<'de> for ResultCode {
     fn deserialize<D>(deserializer: D) -> Result<ResultCode, D::Error>
     where
@@ -85,146 +100,185 @@ pub struct GbPrimePayWebHookRequest {
 }
 
 impl GbPrimePayQRRequest {
-    // fn new(
-    //     token: String,
-    //     amount: i64,
-    //     reference_no: String,
-    //     detail: Option<String>,
-    //     customer_name: Option<String>,
-    //     customer_email: Option<String>,
-    //     customer_telephone: Option<String>,
-    //     customer_address: Option<String>,
-    // )
-
-    fn new(token: String, order: Order) -> Self {
-        let (
-            amount,
-            reference_no,
-            detail,
-            customer_name,
-            customer_email,
-            customer_telephone,
-            customer_address,
-        ) = match order {
-            Order::Default(order) => (
-                order.total_price,
-                order.ref_id,
-                None,
-                order.receiver_name,
-                order.contact_email,
-                order.contact_phone_number,
-                format!(
-                    "{} {} {} {} {}",
-                    order.street_address_line_1.unwrap_or_default(),
-                    order.street_address_line_2.unwrap_or_default(),
-                    order.district.unwrap_or_default(),
-                    order.province.unwrap_or_default(),
-                    order.zip_code.unwrap_or_default()
-                ),
-            ),
-            Order::Detailed(order) => (
-                order.total_price,
-                order.ref_id,
-                None,
-                order.receiver_name,
-                order.contact_email,
-                order.contact_phone_number,
-                format!(
-                    "{} {} {} {} {}",
-                    order.street_address_line_1.unwrap_or_default(),
-                    order.street_address_line_2.unwrap_or_default(),
-                    order.district.unwrap_or_default(),
-                    order.province.unwrap_or_default(),
-                    order.zip_code.unwrap_or_default()
-                ),
-            ),
-            _ => panic!("Order type not supported"),
-        };
+    fn new(token: String, background_url: String, charge: &ChargeRequest) -> Self {
+        let charge = charge.clone();
 
         Self {
             token,
-            amount,
-            reference_no,
-            background_url: "https://api.shopping.skkornor.org/orders/webhook".to_string(),
-            detail,
-            customer_name: Some(customer_name),
-            customer_email: Some(customer_email),
-            customer_telephone,
-            customer_address: Some(customer_address),
+            amount: charge.amount,
+            reference_no: charge.reference_no,
+            background_url,
+            detail: charge.detail,
+            customer_name: Some(charge.customer_name),
+            customer_email: Some(charge.customer_email),
+            customer_telephone: charge.customer_telephone,
+            customer_address: Some(charge.customer_address),
         }
     }
 }
 
-// fetch API from gbprimepay as application/x-www-form-urlencoded and return as image/png
-// return the image/png as base64
-pub async fn create_qr_code(token: String, order: Order) -> Result<String, reqwest::Error> {
-    let request = GbPrimePayQRRequest::new(token, order);
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct GbPrimePayTransaction {
+    pub amount: f64,
+    pub reference_no: String,
+    pub gbp_reference_no: String,
+    // S is settled, the other statuses are authorized, voided or refunded payments
+    pub status: String,
+}
 
-    // let data = serde_urlencoded::to_string(&request).expect("serialize issue");
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct GbPrimePayResultResponse {
+    pub result_code: ResultCode,
+}
 
-    let mut data = vec![
-        ("token", request.token),
-        ("amount", request.amount.to_string()),
-        ("referenceNo", request.reference_no),
-        ("backgroundUrl", request.background_url),
-    ];
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct GbPrimePayStatusResponse {
+    pub result_code: ResultCode,
+    pub txn: Option<GbPrimePayTransaction>,
+}
 
-    if let Some(detail) = request.detail {
-        data.push(("detail", detail));
-    }
-    if let Some(customer_name) = request.customer_name {
-        data.push(("customerName", customer_name));
-    }
-    if let Some(customer_email) = request.customer_email {
-        data.push(("customerEmail", customer_email));
-    }
-    if let Some(customer_telephone) = request.customer_telephone {
-        data.push(("customerTelephone", customer_telephone));
+fn to_satang(amount: f64) -> i64 {
+    (amount * 100.0).round() as i64
+}
+
+#[derive(Debug, Clone)]
+pub struct GbPrimePayProvider {
+    token: String,
+    secret_key: String,
+    base_url: String,
+    // where GBPrimePay posts the result of a payment, our /orders/webhook
+    callback_url: String,
+}
+
+impl GbPrimePayProvider {
+    pub fn new(token: String, secret_key: String, base_url: String, callback_url: String) -> Self {
+        Self {
+            token,
+            secret_key,
+            base_url: base_url.trim_end_matches('/').to_string(),
+            callback_url,
+        }
     }
-    if let Some(customer_address) = request.customer_address {
-        data.push(("customerAddress", customer_address));
+}
+
+impl PaymentProvider for GbPrimePayProvider {
+    fn name(&self) -> &'static str {
+        "gbprimepay"
     }
 
-    let res = Client::new()
-        .post("https://api.gbprimepay.com/v3/qrcode")
-        .header("Content-Type", "application/x-www-form-urlencoded")
-        .form(&data)
-        .send()
-        .await?;
+    // fetch API from gbprimepay as application/x-www-form-urlencoded and return as image/png
+    // return the image/png as base64
+    async fn create_charge(&self, charge: &ChargeRequest) -> Result<String, PaymentProviderError> {
+        let request =
+            GbPrimePayQRRequest::new(self.token.clone(), self.callback_url.clone(), charge);
+
+        let mut data = vec![
+            ("token", request.token),
+            ("amount", request.amount.to_string()),
+            ("referenceNo", request.reference_no),
+            ("backgroundUrl", request.background_url),
+        ];
+
+        if let Some(detail) = request.detail {
+            data.push(("detail", detail));
+        }
+        if let Some(customer_name) = request.customer_name {
+            data.push(("customerName", customer_name));
+        }
+        if let Some(customer_email) = request.customer_email {
+            data.push(("customerEmail", customer_email));
+        }
+        if let Some(customer_telephone) = request.customer_telephone {
+            data.push(("customerTelephone", customer_telephone));
+        }
+        if let Some(customer_address) = request.customer_address {
+            data.push(("customerAddress", customer_address));
+        }
+
+        let res = Client::new()
+            .post(format!("{}/v3/qrcode", self.base_url))
+            .header("Content-Type", "application/x-www-form-urlencoded")
+            .form(&data)
+            .send()
+            .await?
+            .error_for_status()?;
 
-    // dbg!(&res);
-    let encode = res.bytes().await?.to_vec();
+        let encoded = general_purpose::STANDARD.encode(res.bytes().await?);
+        let encoded = format!("data:image/png;base64,{}", encoded);
 
-    // dbg!(res.status());
-    // dbg!(res.headers());
-    // dbg!(res.text().await?);
+        Ok(encoded)
+    }
 
-    // let encoded = base64::encode(res.bytes().await?);
-    // let encoded = general_purpose::STANDARD.encode(res.bytes().await?);
+    // the webhook itself is not signed so asking GBPrimePay is the only way to know that a
+    // callback really came from them
+    async fn query_status(
+        &self,
+        reference_no: &str,
+    ) -> Result<Option<ProviderTransaction>, PaymentProviderError> {
+        let response = Client::new()
+            .post(format!("{}/v1/check_status_txn", self.base_url))
+            .basic_auth(&self.secret_key, None::<&str>)
+            .json(&serde_json::json!({ "referenceNo": reference_no }))
+            .send()
+            .await?
+            .error_for_status()?
+            .json::<GbPrimePayStatusResponse>()
+            .await?;
 
-    let encoded = general_purpose::STANDARD.encode(encode);
-    let encoded = format!("data:image/png;base64,{}", encoded);
+        Ok(match (response.result_code, response.txn) {
+            (ResultCode::Success, Some(txn)) => Some(ProviderTransaction {
+                reference_no: txn.reference_no,
+                provider_reference_no: txn.gbp_reference_no,
+                amount_satang: to_satang(txn.amount),
+                is_settled: txn.status == "S",
+            }),
+            _ => None,
+        })
+    }
 
-    // dbg!(encoded.len());
+    async fn refund(
+        &self,
+        provider_reference_no: &str,
+        amount_satang: i64,
+        refund_reference: &str,
+    ) -> Result<(), PaymentProviderError> {
+        let response = Client::new()
+            .post(format!("{}/v1/refund", self.base_url))
+            .basic_auth(&self.secret_key, None::<&str>)
+            .json(&serde_json::json!({
+                "gbpReferenceNo": provider_reference_no,
+                "referenceNo": refund_reference,
+                "amount": amount_satang as f64 / 100.0,
+            }))
+            .send()
+            .await?
+            .error_for_status()?
+            .json::<GbPrimePayResultResponse>()
+            .await?;
 
-    Ok(encoded)
+        match response.result_code {
+            ResultCode::Success => Ok(()),
+            result_code => Err(PaymentProviderError::Declined(format!(
+                "GBPrimePay declined the refund with result code {}",
+                result_code.code()
+            ))),
+        }
+    }
 }
 
 impl GbPrimePayWebHookRequest {
-    pub async fn update_order_status(&self, pool: &sqlx::PgPool) -> Result<Uuid, sqlx::Error> {
-        let order_id = sqlx::query(
-            r#"
-            UPDATE orders
-            SET is_paid = true, is_verified = true
-            WHERE ref_id = $1
-            RETURNING id
-            "#,
-        )
-        .bind(self.reference_no.clone())
-        .fetch_one(pool)
-        .await?
-        .get::<Uuid, _>("id");
-
-        Ok(order_id)
+    pub fn to_callback(&self) -> PaymentCallback {
+        PaymentCallback {
+            reference_no: self.reference_no.clone(),
+            provider_reference_no: self.gbp_reference_no.clone(),
+            amount_satang: to_satang(self.amount),
+            is_successful: self.result_code == ResultCode::Success,
+            result_code: Some(self.result_code.code().to_string()),
+            is_retry: matches!(self.retry_flag, Some(GBPRetryFlag::Retry)),
+            payload: serde_json::to_value(self).ok(),
+        }
     }
 }
@@ -0,0 +1,215 @@
+use std::collections::HashMap;
+
+use sqlx::{FromRow, PgConnection, Row};
+use uuid::Uuid;
+
+use super::request::{ItemAmount, OrderCreationError};
+
+#[derive(Debug, FromRow)]
+struct PurchaseLimits {
+    item_id: Uuid,
+    name: String,
+    variant_name: Option<String>,
+    max_per_order: Option<i64>,
+    max_per_user: Option<i64>,
+    listing_id: Uuid,
+    listing_name: String,
+    listing_max_per_order: Option<i64>,
+    listing_max_per_user: Option<i64>,
+}
+
+impl PurchaseLimits {
+    fn item_name(&self) -> String {
+        match &self.variant_name {
+            Some(variant_name) => format!("{} ({})", self.name, variant_name),
+            None => self.name.clone(),
+        }
+    }
+}
+
+fn rejected(reason: String) -> Result<(), OrderCreationError> {
+    Err(OrderCreationError::Rejected(reason))
+}
+
+// locks the listings the items belong to until the surrounding transaction ends, in id order so
+// two orders sharing several listings can't deadlock
+pub async fn lock_item_listings(
+    connection: &mut PgConnection,
+    item_ids: &[Uuid],
+) -> Result<(), sqlx::Error> {
+    sqlx::query(
+        r#"
+        SELECT id FROM listings
+        WHERE id IN (SELECT listing_id FROM items WHERE id = ANY($1))
+        ORDER BY id
+        FOR UPDATE
+        "#,
+    )
+    .bind(item_ids)
+    .execute(connection)
+    .await?;
+
+    Ok(())
+}
+
+// checks the "max 2 per student" limits set on items and on whole listings, which count all the
+// variants of the listing together. Limits per user count the buyer's orders that aren't
+// canceled, minus what was refunded. A guest could get around them with a new email each time,
+// so items limited per user need a signed in buyer. Walk-in point of sale buyers are served in
+// person by the shop and only have the limits per order
+pub async fn check_purchase_limits(
+    connection: &mut PgConnection,
+    items: &[ItemAmount],
+    user_id: Option<Uuid>,
+    is_walk_in: bool,
+) -> Result<(), OrderCreationError> {
+    let mut amounts: HashMap<Uuid, i64> = HashMap::new();
+
+    for item in items {
+        *amounts.entry(item.item_id).or_default() += item.amount;
+    }
+
+    let item_ids = amounts.keys().copied().collect::<Vec<Uuid>>();
+
+    let limits = sqlx::query_as::<_, PurchaseLimits>(
+        r#"
+        SELECT
+            items.id AS item_id, items.name, items.variant_name, items.max_per_order, items.max_per_user,
+            listings.id AS listing_id, listings.name AS listing_name,
+            listings.max_per_order AS listing_max_per_order, listings.max_per_user AS listing_max_per_user
+        FROM items INNER JOIN listings ON items.listing_id = listings.id
+        WHERE items.id = ANY($1)
+        "#,
+    )
+    .bind(&item_ids)
+    .fetch_all(&mut *connection)
+    .await?;
+
+    let mut listing_amounts: HashMap<Uuid, i64> = HashMap::new();
+
+    for limit in &limits {
+        let amount = amounts[&limit.item_id];
+
+        *listing_amounts.entry(limit.listing_id).or_default() += amount;
+
+        if let Some(max_per_order) = limit.max_per_order {
+            if amount > max_per_order {
+                return rejected(format!(
+                    "{} is limited to {} per order",
+                    limit.item_name(),
+                    max_per_order
+                ));
+            }
+        }
+    }
+
+    for limit in &limits {
+        if let Some(max_per_order) = limit.listing_max_per_order {
+            if listing_amounts[&limit.listing_id] > max_per_order {
+                return rejected(format!(
+                    "{} is limited to {} per order across all its options, including {}",
+                    limit.listing_name,
+                    max_per_order,
+                    limit.item_name()
+                ));
+            }
+        }
+    }
+
+    let user_limited = limits
+        .iter()
+        .find(|limit| limit.max_per_user.is_some() || limit.listing_max_per_user.is_some());
+
+    let user_id = match (user_id, user_limited) {
+        (_, None) => return Ok(()),
+        (Some(user_id), Some(_)) => user_id,
+        (None, Some(_)) if is_walk_in => return Ok(()),
+        (None, Some(limit)) => {
+            return rejected(format!(
+                "{} is limited per person, sign in to buy it",
+                limit.item_name()
+            ))
+        }
+    };
+
+    let listing_ids = limits
+        .iter()
+        .map(|limit| limit.listing_id)
+        .collect::<Vec<Uuid>>();
+
+    // the items are already locked, but two orders for different variants of a listing lock
+    // different items. Locking the listings makes them read each other's totals
+    let item_ids = limits
+        .iter()
+        .map(|limit| limit.item_id)
+        .collect::<Vec<Uuid>>();
+
+    lock_item_listings(&mut *connection, &item_ids).await?;
+
+    let previous = sqlx::query(
+        r#"
+        SELECT
+            order_items.item_id, items.listing_id,
+            CAST(SUM(order_items.amount - order_items.refunded_amount) AS INT8) AS amount
+        FROM order_items
+            INNER JOIN orders ON order_items.order_id = orders.id
+            INNER JOIN items ON order_items.item_id = items.id
+        WHERE items.listing_id = ANY($1)
+            AND orders.shipment_status <> 'canceled'
+            AND orders.buyer_id = $2
+        GROUP BY order_items.item_id, items.listing_id
+        "#,
+    )
+    .bind(&listing_ids)
+    .bind(user_id)
+    .fetch_all(&mut *connection)
+    .await?;
+
+    let mut previous_items: HashMap<Uuid, i64> = HashMap::new();
+    let mut previous_listings: HashMap<Uuid, i64> = HashMap::new();
+
+    for row in previous {
+        let amount = row.get::<i64, _>("amount");
+
+        *previous_items
+            .entry(row.get::<Uuid, _>("item_id"))
+            .or_default() += amount;
+        *previous_listings
+            .entry(row.get::<Uuid, _>("listing_id"))
+            .or_default() += amount;
+    }
+
+    for limit in &limits {
+        if let Some(max_per_user) = limit.max_per_user {
+            let already = previous_items.get(&limit.item_id).copied().unwrap_or(0);
+
+            if already + amounts[&limit.item_id] > max_per_user {
+                return rejected(format!(
+                    "{} is limited to {} per person and you already have {} in other orders",
+                    limit.item_name(),
+                    max_per_user,
+                    already
+                ));
+            }
+        }
+
+        if let Some(max_per_user) = limit.listing_max_per_user {
+            let already = previous_listings
+                .get(&limit.listing_id)
+                .copied()
+                .unwrap_or(0);
+
+            if already + listing_amounts[&limit.listing_id] > max_per_user {
+                return rejected(format!(
+                    "{} is limited to {} per person across all its options and you already have {} in other orders, including {}",
+                    limit.listing_name,
+                    max_per_user,
+                    already,
+                    limit.item_name()
+                ));
+            }
+        }
+    }
+
+    Ok(())
+}
@@ -0,0 +1,105 @@
+use std::{
+    collections::HashMap,
+    sync::{Arc, Mutex},
+};
+
+use base64::{engine::general_purpose, prelude::*};
+use uuid::Uuid;
+
+use super::provider::{ChargeRequest, PaymentProvider, PaymentProviderError, ProviderTransaction};
+
+// a provider that never leaves the process, payments only happen when `settle` is called. Used
+// with PAYMENT_PROVIDER=mock so staging can go through the whole payment flow
+#[derive(Debug, Clone, Default)]
+pub struct MockPaymentProvider {
+    // settled payments by order reference, lost on restart like a sandbox would be
+    payments: Arc<Mutex<HashMap<String, ProviderTransaction>>>,
+    // refunds paid out by refund reference, with the payment and the satang they gave back
+    refunds: Arc<Mutex<HashMap<String, (String, i64)>>>,
+}
+
+impl MockPaymentProvider {
+    // pretends the buyer paid, the callback for it still has to go through verification
+    pub fn settle(&self, reference_no: &str, amount_satang: i64) -> ProviderTransaction {
+        let txn = ProviderTransaction {
+            reference_no: reference_no.to_string(),
+            provider_reference_no: format!("MOCK-{}", Uuid::new_v4().simple()),
+            amount_satang,
+            is_settled: true,
+        };
+
+        self.payments
+            .lock()
+            .unwrap()
+            .insert(reference_no.to_string(), txn.clone());
+
+        txn
+    }
+}
+
+impl PaymentProvider for MockPaymentProvider {
+    fn name(&self) -> &'static str {
+        "mock"
+    }
+
+    async fn create_charge(&self, charge: &ChargeRequest) -> Result<String, PaymentProviderError> {
+        let svg = format!(
+            r#"<svg xmlns="http://www.w3.org/2000/svg" width="320" height="80"><text x="10" y="30">MOCK PAYMENT {}</text><text x="10" y="60">{} THB</text></svg>"#,
+            charge.reference_no, charge.amount
+        );
+
+        Ok(format!(
+            "data:image/svg+xml;base64,{}",
+            general_purpose::STANDARD.encode(svg)
+        ))
+    }
+
+    async fn query_status(
+        &self,
+        reference_no: &str,
+    ) -> Result<Option<ProviderTransaction>, PaymentProviderError> {
+        Ok(self.payments.lock().unwrap().get(reference_no).cloned())
+    }
+
+    async fn refund(
+        &self,
+        provider_reference_no: &str,
+        amount_satang: i64,
+        refund_reference: &str,
+    ) -> Result<(), PaymentProviderError> {
+        let payments = self.payments.lock().unwrap();
+
+        let txn = payments
+            .values()
+            .find(|txn| txn.provider_reference_no == provider_reference_no)
+            .ok_or_else(|| {
+                PaymentProviderError::Declined(format!("no payment {}", provider_reference_no))
+            })?;
+
+        let mut refunds = self.refunds.lock().unwrap();
+
+        // a retry of a refund that already went through
+        if refunds.contains_key(refund_reference) {
+            return Ok(());
+        }
+
+        let refunded = refunds
+            .values()
+            .filter(|(payment, _)| payment == provider_reference_no)
+            .map(|(_, amount)| amount)
+            .sum::<i64>();
+
+        if refunded + amount_satang > txn.amount_satang {
+            return Err(PaymentProviderError::Declined(
+                "refund is larger than what is left of the payment".to_string(),
+            ));
+        }
+
+        refunds.insert(
+            refund_reference.to_string(),
+            (provider_reference_no.to_string(), amount_satang),
+        );
+
+        Ok(())
+    }
+}
@@ -12,16 +12,32 @@ use self::{
 
 use super::item::Item;
 
+pub(crate) mod cash;
+pub(crate) mod checkout;
 pub(crate) mod db;
 pub(crate) mod fetch_levels;
 pub(crate) mod gbprimpay;
+pub(crate) mod limits;
+pub(crate) mod mock_provider;
+pub(crate) mod payment;
+pub(crate) mod pickup;
+pub(crate) mod pos;
+pub(crate) mod provider;
+pub(crate) mod refund;
 pub(crate) mod request;
+pub(crate) mod reservation;
+pub(crate) mod shipment;
+pub(crate) mod slip;
+pub(crate) mod status;
 
 #[derive(Debug, Deserialize, Serialize)]
 pub struct OrderItem {
     pub id: Uuid,
     pub item: Item,
     pub amount: i64,
+    pub refunded_amount: i64,
+    pub is_preorder: bool,
+    pub preorder_arrived_at: Option<chrono::DateTime<chrono::Utc>>,
 }
 
 impl OrderItem {
@@ -42,6 +58,9 @@ impl OrderItem {
             id: order_item.id,
             item,
             amount: order_item.amount,
+            refunded_amount: order_item.refunded_amount,
+            is_preorder: order_item.is_preorder,
+            preorder_arrived_at: order_item.preorder_arrived_at,
         })
     }
 
@@ -0,0 +1,156 @@
+use std::fmt::Display;
+
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use sqlx::{FromRow, Row};
+use uuid::Uuid;
+
+use super::{
+    db::{DeliveryType, OrderStatus, PaymentMethod},
+    status::{transition_order_status, OrderStatusError},
+};
+
+// the code the buyer shows at the booth, printed as a QR code or read out. 8 hex characters is
+// enough for the orders waiting at one shop and easy to type when the QR can't be scanned
+pub fn generate_pickup_code() -> String {
+    Uuid::new_v4().simple().to_string()[..8].to_uppercase()
+}
+
+// who handed a pickup order over and when, kept apart from the status history so it stays even
+// if the order is later refunded
+#[derive(Debug, Serialize, Deserialize, FromRow)]
+pub struct OrderPickup {
+    pub id: Uuid,
+    pub created_at: DateTime<Utc>,
+    pub order_id: Uuid,
+    pub handed_over_by: Option<Uuid>,
+    pub note: Option<String>,
+}
+
+impl OrderPickup {
+    pub async fn get_by_order_id(
+        pool: &sqlx::PgPool,
+        order_id: Uuid,
+    ) -> Result<Option<Self>, sqlx::Error> {
+        sqlx::query_as::<_, Self>(
+            r#"
+            SELECT id, created_at, order_id, handed_over_by, note FROM order_pickups
+            WHERE order_id = $1
+            "#,
+        )
+        .bind(order_id)
+        .fetch_optional(pool)
+        .await
+    }
+}
+
+#[derive(Debug)]
+pub enum PickupError {
+    NotFound,
+    Rejected(String),
+    Status(OrderStatusError),
+}
+
+impl Display for PickupError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::NotFound => write!(f, "no pickup order of this shop has this code"),
+            Self::Rejected(reason) => write!(f, "{}", reason),
+            Self::Status(err) => write!(f, "{}", err),
+        }
+    }
+}
+
+impl From<OrderStatusError> for PickupError {
+    fn from(err: OrderStatusError) -> Self {
+        Self::Status(err)
+    }
+}
+
+impl From<sqlx::Error> for PickupError {
+    fn from(err: sqlx::Error) -> Self {
+        Self::Status(OrderStatusError::Database(err))
+    }
+}
+
+// hands the pickup order with this code over to the buyer, the code only works for the shop it
+// was ordered from. Returns the id of the order
+pub async fn hand_over_pickup(
+    pool: &sqlx::PgPool,
+    shop_id: Uuid,
+    pickup_code: &str,
+    staff_id: Uuid,
+    note: Option<String>,
+) -> Result<Uuid, PickupError> {
+    let mut transaction = pool.begin().await?;
+
+    let order = sqlx::query(
+        r#"
+        SELECT id, delivery_type, payment_method, is_paid, shipment_status FROM orders
+        WHERE shop_id = $1 AND pickup_code = $2
+        FOR UPDATE
+        "#,
+    )
+    .bind(shop_id)
+    .bind(pickup_code.trim().to_uppercase())
+    .fetch_optional(transaction.as_mut())
+    .await?;
+
+    let order = match order {
+        Some(order) => order,
+        None => return Err(PickupError::NotFound),
+    };
+
+    let order_id = order.get::<Uuid, _>("id");
+
+    if order.get::<DeliveryType, _>("delivery_type") != DeliveryType::SchoolPickup {
+        return Err(PickupError::Rejected("order is not picked up at school".to_string()));
+    }
+
+    match order.get::<OrderStatus, _>("shipment_status") {
+        OrderStatus::ReadyForPickup => (),
+        OrderStatus::Delivered => {
+            return Err(PickupError::Rejected("order has already been picked up".to_string()))
+        }
+        status => {
+            return Err(PickupError::Rejected(format!(
+                "order is {}, not ready for pickup",
+                status
+            )))
+        }
+    }
+
+    if !order.get::<bool, _>("is_paid") {
+        let reason = match order.get::<PaymentMethod, _>("payment_method") {
+            PaymentMethod::Cod => "order is paid at handover, collect the cash instead",
+            _ => "order has not been paid",
+        };
+
+        return Err(PickupError::Rejected(reason.to_string()));
+    }
+
+    transition_order_status(
+        transaction.as_mut(),
+        order_id,
+        OrderStatus::Delivered,
+        Some(staff_id),
+        note.clone(),
+    )
+    .await?;
+
+    sqlx::query(
+        r#"
+        INSERT INTO order_pickups (order_id, handed_over_by, note)
+        VALUES ($1, $2, $3)
+        "#,
+    )
+    .bind(order_id)
+    .bind(staff_id)
+    .bind(note)
+    .execute(transaction.as_mut())
+    .await?;
+
+    transaction.commit().await?;
+
+    Ok(order_id)
+}
@@ -0,0 +1,354 @@
+use chrono::{DateTime, NaiveDate, Utc};
+use serde::{Deserialize, Serialize};
+use sqlx::{FromRow, Row};
+use uuid::Uuid;
+
+use super::{
+    db::{OrderStatus, PaymentMethod},
+    provider::PaymentProvider,
+    request::{CreatableOrder, ItemAmount, OrderCreationError},
+    status::transition_order_status,
+};
+
+// the shop's booth is in Bangkok, so a day at the cash drawer is a Bangkok day
+pub const POS_TIME_ZONE: &str = "Asia/Bangkok";
+
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum PosPaymentMethod {
+    Cash,
+    // paid with a PromptPay QR code shown at the booth, handed over once the payment is verified
+    Promptpay,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct PosSaleItem {
+    // either the item id or its barcode
+    pub item_id: Option<Uuid>,
+    pub barcode: Option<String>,
+    pub amount: i64,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct CreatablePosSale {
+    pub items: Vec<PosSaleItem>,
+    pub payment_method: PosPaymentMethod,
+    // the change is worked out from this for cash sales
+    pub cash_received: Option<i64>,
+    pub receiver_name: Option<String>,
+    // a receipt is emailed when given
+    pub contact_email: Option<String>,
+    pub contact_phone_number: Option<String>,
+}
+
+#[derive(Debug, Serialize, Deserialize, FromRow)]
+pub struct PosReceiptLine {
+    pub item_id: Uuid,
+    pub name: String,
+    pub variant_name: Option<String>,
+    pub unit_price: i64,
+    pub amount: i64,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct PosReceipt {
+    pub order_id: Uuid,
+    pub ref_id: String,
+    pub created_at: Option<DateTime<Utc>>,
+    pub shop_name: String,
+    pub cashier_id: Uuid,
+    pub items: Vec<PosReceiptLine>,
+    pub total_price: i64,
+    pub payment_method: PosPaymentMethod,
+    pub status: OrderStatus,
+    pub cash_received: Option<i64>,
+    pub change: Option<i64>,
+    // shown to the buyer for PromptPay sales
+    pub qr_code: Option<String>,
+}
+
+// looks an item of the shop up by its barcode, or by its id when the code is a UUID
+pub async fn find_pos_item(
+    pool: &sqlx::PgPool,
+    shop_id: Uuid,
+    code: &str,
+) -> Result<Option<Uuid>, sqlx::Error> {
+    let item_id = code.parse::<Uuid>().ok();
+
+    let result = sqlx::query(
+        r#"
+        SELECT items.id FROM items
+        INNER JOIN listings ON items.listing_id = listings.id
+        WHERE listings.shop_id = $1 AND (items.barcode = $2 OR items.id = $3)
+        "#,
+    )
+    .bind(shop_id)
+    .bind(code)
+    .bind(item_id)
+    .fetch_optional(pool)
+    .await?;
+
+    Ok(result.map(|row| row.get::<Uuid, _>("id")))
+}
+
+impl CreatablePosSale {
+    pub fn validate(&self) -> Result<&Self, String> {
+        if self.items.is_empty() {
+            return Err("items must not be empty".to_string());
+        }
+
+        for item in &self.items {
+            if item.item_id.is_none() && item.barcode.is_none() {
+                return Err("every item needs an item_id or a barcode".to_string());
+            }
+
+            if item.amount <= 0 {
+                return Err("every amount must be positive".to_string());
+            }
+        }
+
+        Ok(self)
+    }
+
+    // places the order and, for cash sales, pays and hands it over straight away
+    pub async fn insert(
+        &self,
+        pool: &sqlx::PgPool,
+        payment_provider: &impl PaymentProvider,
+        shop_id: Uuid,
+        cashier_id: Uuid,
+        stock_hold: chrono::Duration,
+    ) -> Result<PosReceipt, OrderCreationError> {
+        let mut items = Vec::new();
+
+        for item in &self.items {
+            let code = match item.item_id {
+                Some(item_id) => item_id.to_string(),
+                None => item.barcode.clone().unwrap_or_default(),
+            };
+
+            let item_id = find_pos_item(pool, shop_id, &code).await?.ok_or_else(|| {
+                OrderCreationError::Rejected(format!("item {} is not sold by this shop", code))
+            })?;
+
+            items.push(ItemAmount {
+                item_id,
+                amount: item.amount,
+            });
+        }
+
+        let payment_method = match self.payment_method {
+            PosPaymentMethod::Cash => PaymentMethod::POSCash,
+            PosPaymentMethod::Promptpay => PaymentMethod::Promptpay,
+        };
+
+        let order = CreatableOrder::for_pos(
+            items,
+            payment_method,
+            self.receiver_name.clone(),
+            self.contact_email.clone(),
+            self.contact_phone_number.clone(),
+        );
+
+        // the buyer is usually anonymous at the booth, so the order isn't tied to the cashier
+        if self.payment_method == PosPaymentMethod::Promptpay {
+            let order_id = order
+                .insert(pool, payment_provider, None, stock_hold)
+                .await?;
+
+            return PosReceipt::get(pool, order_id, cashier_id, None, None).await;
+        }
+
+        // a cash sale is placed, paid and handed over in one go, so short cash or a failed
+        // handover leaves no order behind
+        let mut transaction = pool.begin().await?;
+
+        let placed = order
+            .place(transaction.as_mut(), pool, None, stock_hold, None)
+            .await?;
+
+        let change = match self.cash_received {
+            Some(cash_received) if cash_received < placed.total_price => {
+                return Err(OrderCreationError::Rejected(format!(
+                    "received {} baht but the total is {} baht",
+                    cash_received, placed.total_price
+                )));
+            }
+            Some(cash_received) => Some(cash_received - placed.total_price),
+            None => None,
+        };
+
+        transition_order_status(
+            transaction.as_mut(),
+            placed.order_id,
+            OrderStatus::Delivered,
+            Some(cashier_id),
+            None,
+        )
+        .await?;
+
+        transaction.commit().await?;
+
+        PosReceipt::get(
+            pool,
+            placed.order_id,
+            cashier_id,
+            self.cash_received,
+            change,
+        )
+        .await
+    }
+}
+
+impl PosReceipt {
+    async fn get(
+        pool: &sqlx::PgPool,
+        order_id: Uuid,
+        cashier_id: Uuid,
+        cash_received: Option<i64>,
+        change: Option<i64>,
+    ) -> Result<Self, OrderCreationError> {
+        let order = sqlx::query(
+            r#"
+            SELECT orders.ref_id, orders.created_at, orders.total_price, orders.payment_method,
+                orders.shipment_status, orders.qr_code_file, shops.name_th
+            FROM orders
+            INNER JOIN shops ON orders.shop_id = shops.id
+            WHERE orders.id = $1
+            "#,
+        )
+        .bind(order_id)
+        .fetch_one(pool)
+        .await?;
+
+        let items = sqlx::query_as::<_, PosReceiptLine>(
+            r#"
+            SELECT
+                items.id AS item_id,
+                items.name,
+                items.variant_name,
+                order_items.unit_price,
+                order_items.amount
+            FROM order_items
+            INNER JOIN items ON order_items.item_id = items.id
+            WHERE order_items.order_id = $1
+            ORDER BY order_items.created_at ASC
+            "#,
+        )
+        .bind(order_id)
+        .fetch_all(pool)
+        .await?;
+
+        let payment_method = match order.get::<PaymentMethod, _>("payment_method") {
+            PaymentMethod::Promptpay => PosPaymentMethod::Promptpay,
+            _ => PosPaymentMethod::Cash,
+        };
+
+        Ok(Self {
+            order_id,
+            ref_id: order.get::<String, _>("ref_id"),
+            created_at: order.get::<Option<DateTime<Utc>>, _>("created_at"),
+            shop_name: order.get::<String, _>("name_th"),
+            cashier_id,
+            items,
+            total_price: order.get::<i64, _>("total_price"),
+            payment_method,
+            status: order.get::<OrderStatus, _>("shipment_status"),
+            cash_received,
+            change,
+            qr_code: order.get::<Option<String>, _>("qr_code_file"),
+        })
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct CashDrawerSummary {
+    pub shop_id: Uuid,
+    pub date: NaiveDate,
+    // cash taken for point of sale orders
+    pub pos_cash_sales: i64,
+    pub pos_cash_orders: i64,
+    // cash taken at handover for cash on delivery orders
+    pub cod_collected: i64,
+    pub cod_orders: i64,
+    // cash paid back out of the drawer
+    pub cash_refunds: i64,
+    // what the drawer should have gained over the day
+    pub expected_cash: i64,
+    // point of sale orders paid by PromptPay, which never go through the drawer
+    pub pos_promptpay_sales: i64,
+}
+
+impl CashDrawerSummary {
+    pub async fn get(
+        pool: &sqlx::PgPool,
+        shop_id: Uuid,
+        date: NaiveDate,
+    ) -> Result<Self, sqlx::Error> {
+        let collected = sqlx::query(
+            r#"
+            SELECT
+                CAST(COALESCE(SUM(cash_collections.amount) FILTER (WHERE orders.payment_method = 'pos_cash'), 0) AS INT8) AS pos_cash_sales,
+                COUNT(*) FILTER (WHERE orders.payment_method = 'pos_cash') AS pos_cash_orders,
+                CAST(COALESCE(SUM(cash_collections.amount) FILTER (WHERE orders.payment_method = 'cod'), 0) AS INT8) AS cod_collected,
+                COUNT(*) FILTER (WHERE orders.payment_method = 'cod') AS cod_orders
+            FROM cash_collections
+            INNER JOIN orders ON cash_collections.order_id = orders.id
+            WHERE orders.shop_id = $1
+            AND CAST(cash_collections.created_at AT TIME ZONE $2 AS DATE) = $3
+            "#,
+        )
+        .bind(shop_id)
+        .bind(POS_TIME_ZONE)
+        .bind(date)
+        .fetch_one(pool)
+        .await?;
+
+        let cash_refunds = sqlx::query(
+            r#"
+            SELECT CAST(COALESCE(SUM(refunds.amount), 0) AS INT8) AS amount
+            FROM refunds
+            INNER JOIN orders ON refunds.order_id = orders.id
+            WHERE orders.shop_id = $1 AND refunds.method = 'cash'
+            AND CAST(refunds.created_at AT TIME ZONE $2 AS DATE) = $3
+            "#,
+        )
+        .bind(shop_id)
+        .bind(POS_TIME_ZONE)
+        .bind(date)
+        .fetch_one(pool)
+        .await?
+        .get::<i64, _>("amount");
+
+        let pos_promptpay_sales = sqlx::query(
+            r#"
+            SELECT CAST(COALESCE(SUM(total_price), 0) AS INT8) AS amount
+            FROM orders
+            WHERE shop_id = $1 AND delivery_type = 'pos' AND payment_method = 'promptpay'
+            AND is_paid = TRUE
+            AND CAST(created_at AT TIME ZONE $2 AS DATE) = $3
+            "#,
+        )
+        .bind(shop_id)
+        .bind(POS_TIME_ZONE)
+        .bind(date)
+        .fetch_one(pool)
+        .await?
+        .get::<i64, _>("amount");
+
+        let pos_cash_sales = collected.get::<i64, _>("pos_cash_sales");
+        let cod_collected = collected.get::<i64, _>("cod_collected");
+
+        Ok(Self {
+            shop_id,
+            date,
+            pos_cash_sales,
+            pos_cash_orders: collected.get::<i64, _>("pos_cash_orders"),
+            cod_collected,
+            cod_orders: collected.get::<i64, _>("cod_orders"),
+            cash_refunds,
+            expected_cash: pos_cash_sales + cod_collected - cash_refunds,
+            pos_promptpay_sales,
+        })
+    }
+}
@@ -0,0 +1,247 @@
+use std::fmt::Display;
+
+use crate::utils::common::config::Config;
+
+use super::{gbprimpay::GbPrimePayProvider, mock_provider::MockPaymentProvider, Order};
+
+// what a provider needs to know to charge the buyer for an order
+#[derive(Debug, Clone)]
+pub struct ChargeRequest {
+    pub reference_no: String,
+    // in baht, the same as the order total
+    pub amount: i64,
+    pub detail: Option<String>,
+    pub customer_name: String,
+    pub customer_email: String,
+    pub customer_telephone: Option<String>,
+    pub customer_address: String,
+}
+
+impl From<Order> for ChargeRequest {
+    fn from(order: Order) -> Self {
+        let (
+            amount,
+            reference_no,
+            detail,
+            customer_name,
+            customer_email,
+            customer_telephone,
+            customer_address,
+        ) = match order {
+            Order::Default(order) => (
+                order.total_price,
+                order.ref_id,
+                None,
+                order.receiver_name,
+                order.contact_email,
+                order.contact_phone_number,
+                format!(
+                    "{} {} {} {} {}",
+                    order.street_address_line_1.unwrap_or_default(),
+                    order.street_address_line_2.unwrap_or_default(),
+                    order.district.unwrap_or_default(),
+                    order.province.unwrap_or_default(),
+                    order.zip_code.unwrap_or_default()
+                ),
+            ),
+            Order::Detailed(order) => (
+                order.total_price,
+                order.ref_id,
+                None,
+                order.receiver_name,
+                order.contact_email,
+                order.contact_phone_number,
+                format!(
+                    "{} {} {} {} {}",
+                    order.street_address_line_1.unwrap_or_default(),
+                    order.street_address_line_2.unwrap_or_default(),
+                    order.district.unwrap_or_default(),
+                    order.province.unwrap_or_default(),
+                    order.zip_code.unwrap_or_default()
+                ),
+            ),
+            _ => panic!("Order type not supported"),
+        };
+
+        Self {
+            reference_no,
+            amount,
+            detail,
+            customer_name,
+            customer_email,
+            customer_telephone,
+            customer_address,
+        }
+    }
+}
+
+// a payment as the provider sees it
+#[derive(Debug, Clone)]
+pub struct ProviderTransaction {
+    pub reference_no: String,
+    pub provider_reference_no: String,
+    pub amount_satang: i64,
+    pub is_settled: bool,
+}
+
+// a payment notification in the shape every provider is reduced to before it touches an order
+#[derive(Debug, Clone)]
+pub struct PaymentCallback {
+    pub reference_no: String,
+    pub provider_reference_no: String,
+    pub amount_satang: i64,
+    pub is_successful: bool,
+    pub result_code: Option<String>,
+    pub is_retry: bool,
+    pub payload: Option<serde_json::Value>,
+}
+
+#[derive(Debug)]
+pub enum PaymentProviderError {
+    Request(reqwest::Error),
+    // the provider answered but refused the request
+    Declined(String),
+}
+
+impl Display for PaymentProviderError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Request(err) => write!(f, "{}", err),
+            Self::Declined(reason) => write!(f, "{}", reason),
+        }
+    }
+}
+
+impl From<reqwest::Error> for PaymentProviderError {
+    fn from(err: reqwest::Error) -> Self {
+        Self::Request(err)
+    }
+}
+
+pub trait PaymentProvider {
+    // stored with every payment transaction so retries are matched to the right provider
+    fn name(&self) -> &'static str;
+
+    // returns the QR code the buyer pays with as a data url
+    async fn create_charge(&self, charge: &ChargeRequest) -> Result<String, PaymentProviderError>;
+
+    // None when the provider has no record of the payment
+    async fn query_status(
+        &self,
+        reference_no: &str,
+    ) -> Result<Option<ProviderTransaction>, PaymentProviderError>;
+
+    // refund_reference stays the same for every attempt at one refund so a retried refund isn't
+    // paid out twice
+    async fn refund(
+        &self,
+        provider_reference_no: &str,
+        amount_satang: i64,
+        refund_reference: &str,
+    ) -> Result<(), PaymentProviderError>;
+
+    // callbacks are never trusted on their own, the provider has to report the same settled
+    // payment. Returns why the callback was rejected, or None when it checks out
+    async fn verify_callback(
+        &self,
+        callback: &PaymentCallback,
+    ) -> Result<Option<String>, PaymentProviderError> {
+        let txn = match self.query_status(&callback.reference_no).await? {
+            Some(txn) => txn,
+            None => return Ok(Some(format!("{} has no record of this payment", self.name()))),
+        };
+
+        if txn.reference_no != callback.reference_no
+            || txn.provider_reference_no != callback.provider_reference_no
+        {
+            return Ok(Some(format!(
+                "{} reports payment {} for this order instead",
+                self.name(),
+                txn.provider_reference_no
+            )));
+        }
+
+        if !txn.is_settled {
+            return Ok(Some(format!("{} reports the payment as not settled", self.name())));
+        }
+
+        if txn.amount_satang != callback.amount_satang {
+            return Ok(Some(format!(
+                "callback amount {} does not match the {} satang {} settled",
+                callback.amount_satang,
+                txn.amount_satang,
+                self.name()
+            )));
+        }
+
+        Ok(None)
+    }
+}
+
+// the provider picked by PAYMENT_PROVIDER, shared by every worker
+#[derive(Debug, Clone)]
+pub enum PaymentGateway {
+    GbPrimePay(GbPrimePayProvider),
+    Mock(MockPaymentProvider),
+}
+
+impl PaymentGateway {
+    pub fn from_config(config: &Config) -> Self {
+        match config.payment_provider.as_str() {
+            "mock" => Self::Mock(MockPaymentProvider::default()),
+            "gbprimepay" => Self::GbPrimePay(GbPrimePayProvider::new(
+                config.gbprimepay_token.clone(),
+                config.gbprimepay_secret_key.clone(),
+                config.gbprimepay_base_url.clone(),
+                config.gbprimepay_callback_url.clone(),
+            )),
+            provider => panic!("unknown payment provider {}", provider),
+        }
+    }
+}
+
+impl PaymentProvider for PaymentGateway {
+    fn name(&self) -> &'static str {
+        match self {
+            Self::GbPrimePay(provider) => provider.name(),
+            Self::Mock(provider) => provider.name(),
+        }
+    }
+
+    async fn create_charge(&self, charge: &ChargeRequest) -> Result<String, PaymentProviderError> {
+        match self {
+            Self::GbPrimePay(provider) => provider.create_charge(charge).await,
+            Self::Mock(provider) => provider.create_charge(charge).await,
+        }
+    }
+
+    async fn query_status(
+        &self,
+        reference_no: &str,
+    ) -> Result<Option<ProviderTransaction>, PaymentProviderError> {
+        match self {
+            Self::GbPrimePay(provider) => provider.query_status(reference_no).await,
+            Self::Mock(provider) => provider.query_status(reference_no).await,
+        }
+    }
+
+    async fn refund(
+        &self,
+        provider_reference_no: &str,
+        amount_satang: i64,
+        refund_reference: &str,
+    ) -> Result<(), PaymentProviderError> {
+        match self {
+            Self::GbPrimePay(provider) => {
+                provider
+                    .refund(provider_reference_no, amount_satang, refund_reference)
+                    .await
+            }
+            Self::Mock(provider) => {
+                provider
+                    .refund(provider_reference_no, amount_satang, refund_reference)
+                    .await
+            }
+        }
+    }
+}
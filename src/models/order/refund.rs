@@ -0,0 +1,822 @@
+use std::{
+    collections::{HashMap, HashSet},
+    fmt::Display,
+};
+
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use sqlx::{FromRow, PgConnection, Row, Type};
+use uuid::Uuid;
+
+use super::{
+    db::OrderStatus,
+    payment::PaymentTransactionTable,
+    provider::{PaymentProvider, PaymentProviderError},
+    status::{transition_order_status, OrderStatusError},
+};
+
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum RefundMethod {
+    // sent back through the payment provider the order was paid with
+    PaymentProvider,
+    BankTransfer,
+    Cash,
+}
+
+impl Display for RefundMethod {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let s = match self {
+            Self::PaymentProvider => "payment_provider",
+            Self::BankTransfer => "bank_transfer",
+            Self::Cash => "cash",
+        };
+        write!(f, "{}", s)
+    }
+}
+
+impl Type<sqlx::Postgres> for RefundMethod {
+    fn type_info() -> sqlx::postgres::PgTypeInfo {
+        sqlx::postgres::PgTypeInfo::with_name("refund_method")
+    }
+}
+
+impl sqlx::Encode<'_, sqlx::Postgres> for RefundMethod {
+    fn encode_by_ref(
+        &self,
+        buf: &mut <sqlx::Postgres as sqlx::database::HasArguments<'_>>::ArgumentBuffer,
+    ) -> sqlx::encode::IsNull {
+        let s: String = self.to_string();
+        <String as sqlx::Encode<sqlx::Postgres>>::encode(s, buf)
+    }
+}
+
+impl sqlx::Decode<'_, sqlx::Postgres> for RefundMethod {
+    fn decode(
+        value: <sqlx::Postgres as sqlx::database::HasValueRef<'_>>::ValueRef,
+    ) -> Result<Self, Box<dyn std::error::Error + 'static + Send + Sync>> {
+        let s: String = <String as sqlx::Decode<sqlx::Postgres>>::decode(value)?;
+        match s.as_str() {
+            "payment_provider" => Ok(Self::PaymentProvider),
+            "bank_transfer" => Ok(Self::BankTransfer),
+            "cash" => Ok(Self::Cash),
+            _ => Err("invalid refund method".into()),
+        }
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum RefundStatus {
+    // waiting for the payment provider to pay out
+    Pending,
+    Completed,
+    // the provider declined, nothing was given back
+    Failed,
+}
+
+impl Display for RefundStatus {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let s = match self {
+            Self::Pending => "pending",
+            Self::Completed => "completed",
+            Self::Failed => "failed",
+        };
+        write!(f, "{}", s)
+    }
+}
+
+impl Type<sqlx::Postgres> for RefundStatus {
+    fn type_info() -> sqlx::postgres::PgTypeInfo {
+        sqlx::postgres::PgTypeInfo::with_name("refund_status")
+    }
+}
+
+impl sqlx::Encode<'_, sqlx::Postgres> for RefundStatus {
+    fn encode_by_ref(
+        &self,
+        buf: &mut <sqlx::Postgres as sqlx::database::HasArguments<'_>>::ArgumentBuffer,
+    ) -> sqlx::encode::IsNull {
+        let s: String = self.to_string();
+        <String as sqlx::Encode<sqlx::Postgres>>::encode(s, buf)
+    }
+}
+
+impl sqlx::Decode<'_, sqlx::Postgres> for RefundStatus {
+    fn decode(
+        value: <sqlx::Postgres as sqlx::database::HasValueRef<'_>>::ValueRef,
+    ) -> Result<Self, Box<dyn std::error::Error + 'static + Send + Sync>> {
+        let s: String = <String as sqlx::Decode<sqlx::Postgres>>::decode(value)?;
+        match s.as_str() {
+            "pending" => Ok(Self::Pending),
+            "completed" => Ok(Self::Completed),
+            "failed" => Ok(Self::Failed),
+            _ => Err("invalid refund status".into()),
+        }
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize, FromRow)]
+pub struct RefundTable {
+    pub id: Uuid,
+    pub created_at: Option<DateTime<Utc>>,
+    pub order_id: Uuid,
+    // in baht, like the order total
+    pub amount: i64,
+    pub method: RefundMethod,
+    // the provider's refund reference, or the transfer reference for manual refunds
+    pub reference: Option<String>,
+    pub reason: Option<String>,
+    pub actor_id: Option<Uuid>,
+    pub status: RefundStatus,
+}
+
+#[derive(Debug, Serialize, Deserialize, FromRow)]
+pub struct RefundItem {
+    pub order_item_id: Uuid,
+    pub item_id: Uuid,
+    pub amount: i64,
+    pub is_restocked: bool,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct Refund {
+    pub id: Uuid,
+    pub created_at: Option<DateTime<Utc>>,
+    pub amount: i64,
+    pub method: RefundMethod,
+    pub reference: Option<String>,
+    pub reason: Option<String>,
+    pub actor_id: Option<Uuid>,
+    pub status: RefundStatus,
+    pub items: Vec<RefundItem>,
+}
+
+impl Refund {
+    pub async fn get_by_order_id(
+        pool: &sqlx::PgPool,
+        order_id: Uuid,
+    ) -> Result<Vec<Self>, sqlx::Error> {
+        let refunds = sqlx::query_as::<_, RefundTable>(
+            "SELECT * FROM refunds WHERE order_id = $1 ORDER BY created_at ASC",
+        )
+        .bind(order_id)
+        .fetch_all(pool)
+        .await?;
+
+        let mut result = Vec::new();
+
+        for refund in refunds {
+            let items = sqlx::query_as::<_, RefundItem>(
+                r#"
+                SELECT
+                    refund_items.order_item_id,
+                    order_items.item_id,
+                    refund_items.amount,
+                    refund_items.is_restocked
+                FROM refund_items
+                INNER JOIN order_items ON refund_items.order_item_id = order_items.id
+                WHERE refund_items.refund_id = $1
+                "#,
+            )
+            .bind(refund.id)
+            .fetch_all(pool)
+            .await?;
+
+            result.push(Self {
+                id: refund.id,
+                created_at: refund.created_at,
+                amount: refund.amount,
+                method: refund.method,
+                reference: refund.reference,
+                reason: refund.reason,
+                actor_id: refund.actor_id,
+                status: refund.status,
+                items,
+            });
+        }
+
+        Ok(result)
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct RefundableItem {
+    pub order_item_id: Uuid,
+    pub amount: i64,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct CreatableRefund {
+    // the lines to give back, every line that is left when None
+    pub items: Option<Vec<RefundableItem>>,
+    // defaults to what the refunded items cost now, or whatever is left of the total when the
+    // whole order is refunded
+    pub amount: Option<i64>,
+    pub method: RefundMethod,
+    pub reference: Option<String>,
+    pub reason: Option<String>,
+    // whether the items go back on sale, false for items that were used up or never returned
+    pub restock: Option<bool>,
+}
+
+#[derive(Debug)]
+pub enum RefundError {
+    Rejected(String),
+    Provider(PaymentProviderError),
+    Status(OrderStatusError),
+}
+
+impl Display for RefundError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Rejected(reason) => write!(f, "{}", reason),
+            Self::Provider(err) => write!(f, "payment provider refund failed: {}", err),
+            Self::Status(err) => write!(f, "{}", err),
+        }
+    }
+}
+
+impl From<PaymentProviderError> for RefundError {
+    fn from(err: PaymentProviderError) -> Self {
+        Self::Provider(err)
+    }
+}
+
+impl From<OrderStatusError> for RefundError {
+    fn from(err: OrderStatusError) -> Self {
+        Self::Status(err)
+    }
+}
+
+impl From<sqlx::Error> for RefundError {
+    fn from(err: sqlx::Error) -> Self {
+        Self::Status(OrderStatusError::Database(err))
+    }
+}
+
+pub enum RefundOutcome {
+    // the order was never paid so it was only canceled
+    Canceled,
+    Refunded { amount: i64 },
+}
+
+// an order line as far as refunds are concerned, `paid` is what the buyer paid for all of it
+struct RefundableLine {
+    amount: i64,
+    refunded_amount: i64,
+    paid: i64,
+}
+
+impl RefundableLine {
+    fn remaining(&self) -> i64 {
+        self.amount - self.refunded_amount
+    }
+
+    // worked out from the units refunded before so refunding every unit adds up to `paid`
+    // exactly, whatever the rounding
+    fn price_of(&self, amount: i64) -> i64 {
+        let paid_until = |refunded: i64| self.paid * refunded / self.amount;
+
+        paid_until(self.refunded_amount + amount) - paid_until(self.refunded_amount)
+    }
+}
+
+impl CreatableRefund {
+    // `items_price` is what the requested lines cost and `refundable` what is left of the order
+    fn amount_to_refund(&self, items_price: i64, refundable: i64) -> Result<i64, String> {
+        let amount = match (self.amount, &self.items) {
+            (Some(amount), _) => amount,
+            (None, Some(_)) => items_price.min(refundable),
+            (None, None) => refundable,
+        };
+
+        if amount > refundable {
+            return Err(format!(
+                "only {} baht of this order is left to refund",
+                refundable
+            ));
+        }
+
+        Ok(amount)
+    }
+
+    pub fn validate(&self) -> Result<&Self, String> {
+        if let Some(items) = &self.items {
+            if items.is_empty() || items.iter().any(|item| item.amount <= 0) {
+                return Err(
+                    "items must not be empty and every amount must be positive".to_string(),
+                );
+            }
+
+            // lines are checked one by one against what is left of them, so each may appear once
+            let mut seen = HashSet::new();
+
+            if let Some(item) = items.iter().find(|item| !seen.insert(item.order_item_id)) {
+                return Err(format!(
+                    "order item {} appears more than once",
+                    item.order_item_id
+                ));
+            }
+        }
+
+        if self.amount.is_some_and(|amount| amount < 0) {
+            return Err("amount must not be negative".to_string());
+        }
+
+        if self.method != RefundMethod::PaymentProvider && self.reference.is_none() {
+            return Err("reference is required for refunds made outside the provider".to_string());
+        }
+
+        Ok(self)
+    }
+
+    // gives money and items back. Without an amount the items are refunded at what the buyer paid
+    // for them. Provider refunds are saved as pending and committed before the provider is asked,
+    // the items only come off the order once it has paid out. A refund still pending from an
+    // earlier attempt is retried instead of starting a new one
+    pub async fn insert(
+        &self,
+        pool: &sqlx::PgPool,
+        payment_provider: &impl PaymentProvider,
+        order_id: Uuid,
+        actor_id: Uuid,
+    ) -> Result<RefundOutcome, RefundError> {
+        let mut transaction = pool.begin().await?;
+
+        let order = sqlx::query(
+            r#"
+            SELECT shipment_status, is_paid, total_price FROM orders
+            WHERE id = $1
+            FOR UPDATE
+            "#,
+        )
+        .bind(order_id)
+        .fetch_one(transaction.as_mut())
+        .await?;
+
+        let status = order.get::<OrderStatus, _>("shipment_status");
+        let is_paid = order.get::<bool, _>("is_paid");
+        let total_price = order.get::<i64, _>("total_price");
+
+        // unpaid orders include cash on delivery orders that are already on their way
+        match (status, is_paid) {
+            (OrderStatus::Canceled | OrderStatus::Refunded, _) => {
+                return Err(RefundError::Rejected(format!("order is already {}", status)))
+            }
+            (_, false) if self.items.is_none() => {
+                transition_order_status(
+                    transaction.as_mut(),
+                    order_id,
+                    OrderStatus::Canceled,
+                    Some(actor_id),
+                    self.reason.clone(),
+                )
+                .await?;
+                transaction.commit().await?;
+
+                return Ok(RefundOutcome::Canceled);
+            }
+            (_, false) => {
+                return Err(RefundError::Rejected(
+                    "unpaid orders can only be canceled as a whole".to_string(),
+                ))
+            }
+            _ => {}
+        }
+
+        // a refund the provider hasn't confirmed yet is finished before another one is started
+        let pending_refund_id =
+            sqlx::query("SELECT id FROM refunds WHERE order_id = $1 AND status = 'pending'")
+                .bind(order_id)
+                .fetch_optional(transaction.as_mut())
+                .await?
+                .map(|row| row.get::<Uuid, _>("id"));
+
+        if let Some(refund_id) = pending_refund_id {
+            transaction.rollback().await?;
+
+            return pay_out_refund(pool, payment_provider, order_id, refund_id, actor_id).await;
+        }
+
+        let lines = sqlx::query(
+            r#"
+            SELECT id, amount, refunded_amount, unit_price * amount - discount_amount AS paid
+            FROM order_items
+            WHERE order_id = $1
+            FOR UPDATE
+            "#,
+        )
+        .bind(order_id)
+        .fetch_all(transaction.as_mut())
+        .await?
+        .into_iter()
+        .map(|row| {
+            (
+                row.get::<Uuid, _>("id"),
+                RefundableLine {
+                    amount: row.get::<i64, _>("amount"),
+                    refunded_amount: row.get::<i64, _>("refunded_amount"),
+                    paid: row.get::<i64, _>("paid"),
+                },
+            )
+        })
+        .collect::<HashMap<Uuid, RefundableLine>>();
+
+        let requested = match &self.items {
+            Some(items) => items
+                .iter()
+                .map(|item| (item.order_item_id, item.amount))
+                .collect::<Vec<_>>(),
+            None => lines
+                .iter()
+                .filter(|(_, line)| line.remaining() > 0)
+                .map(|(id, line)| (*id, line.remaining()))
+                .collect::<Vec<_>>(),
+        };
+
+        let mut items_price = 0;
+
+        for (order_item_id, amount) in &requested {
+            let line = lines.get(order_item_id).ok_or_else(|| {
+                RefundError::Rejected(format!("order item {} is not in this order", order_item_id))
+            })?;
+
+            if *amount > line.remaining() {
+                return Err(RefundError::Rejected(format!(
+                    "order item {} only has {} left to refund",
+                    order_item_id,
+                    line.remaining()
+                )));
+            }
+
+            items_price += line.price_of(*amount);
+        }
+
+        let already_refunded = sqlx::query(
+            r#"
+            SELECT CAST(COALESCE(SUM(amount), 0) AS INT8) AS amount FROM refunds
+            WHERE order_id = $1 AND status <> 'failed'
+            "#,
+        )
+        .bind(order_id)
+        .fetch_one(transaction.as_mut())
+        .await?
+        .get::<i64, _>("amount");
+
+        let refundable = total_price - already_refunded;
+
+        let amount = self
+            .amount_to_refund(items_price, refundable)
+            .map_err(RefundError::Rejected)?;
+
+        let (status, reference) = match self.method {
+            RefundMethod::PaymentProvider => {
+                let payment = PaymentTransactionTable::get_accepted_by_order_id(
+                    transaction.as_mut(),
+                    payment_provider.name(),
+                    order_id,
+                )
+                .await?
+                .ok_or_else(|| {
+                    RefundError::Rejected(
+                        "order was not paid through the payment provider".to_string(),
+                    )
+                })?;
+
+                let status = match amount {
+                    0 => RefundStatus::Completed,
+                    _ => RefundStatus::Pending,
+                };
+
+                (status, self.reference.clone().or(Some(payment)))
+            }
+            _ => (RefundStatus::Completed, self.reference.clone()),
+        };
+
+        let refund_id = sqlx::query(
+            r#"
+            INSERT INTO refunds (order_id, amount, method, reference, reason, actor_id, status)
+            VALUES ($1, $2, $3, $4, $5, $6, $7)
+            RETURNING id
+            "#,
+        )
+        .bind(order_id)
+        .bind(amount)
+        .bind(self.method)
+        .bind(reference)
+        .bind(&self.reason)
+        .bind(actor_id)
+        .bind(status)
+        .fetch_one(transaction.as_mut())
+        .await?
+        .get::<Uuid, _>("id");
+
+        let restock = self.restock.unwrap_or(true);
+
+        for (order_item_id, amount) in &requested {
+            sqlx::query(
+                r#"
+                INSERT INTO refund_items (refund_id, order_item_id, amount, is_restocked)
+                VALUES ($1, $2, $3, $4)
+                "#,
+            )
+            .bind(refund_id)
+            .bind(order_item_id)
+            .bind(amount)
+            .bind(restock)
+            .execute(transaction.as_mut())
+            .await?;
+        }
+
+        if status == RefundStatus::Completed {
+            complete_refund(transaction.as_mut(), order_id, refund_id, actor_id).await?;
+            transaction.commit().await?;
+
+            return Ok(RefundOutcome::Refunded { amount });
+        }
+
+        transaction.commit().await?;
+
+        pay_out_refund(pool, payment_provider, order_id, refund_id, actor_id).await
+    }
+}
+
+// asks the provider to pay out a pending refund and applies it once it has. The refund id is
+// the provider's refund reference so paying out the same refund again is a no-op
+async fn pay_out_refund(
+    pool: &sqlx::PgPool,
+    payment_provider: &impl PaymentProvider,
+    order_id: Uuid,
+    refund_id: Uuid,
+    actor_id: Uuid,
+) -> Result<RefundOutcome, RefundError> {
+    let mut connection = pool.acquire().await?;
+
+    let amount = sqlx::query("SELECT amount FROM refunds WHERE id = $1")
+        .bind(refund_id)
+        .fetch_one(connection.as_mut())
+        .await?
+        .get::<i64, _>("amount");
+
+    let payment = PaymentTransactionTable::get_accepted_by_order_id(
+        connection.as_mut(),
+        payment_provider.name(),
+        order_id,
+    )
+    .await?
+    .ok_or_else(|| {
+        RefundError::Rejected("order was not paid through the payment provider".to_string())
+    })?;
+
+    drop(connection);
+
+    match payment_provider
+        .refund(&payment, amount * 100, &refund_id.to_string())
+        .await
+    {
+        Ok(()) => {}
+        // nothing was paid out, so the refund can be asked for again from scratch
+        Err(PaymentProviderError::Declined(reason)) => {
+            sqlx::query(
+                "UPDATE refunds SET status = 'failed' WHERE id = $1 AND status = 'pending'",
+            )
+            .bind(refund_id)
+            .execute(pool)
+            .await?;
+
+            return Err(RefundError::Provider(PaymentProviderError::Declined(
+                reason,
+            )));
+        }
+        // the provider may have paid out anyway, the refund stays pending and the next refund
+        // of this order retries it
+        Err(err) => return Err(err.into()),
+    }
+
+    let mut transaction = pool.begin().await?;
+
+    sqlx::query("SELECT id FROM orders WHERE id = $1 FOR UPDATE")
+        .bind(order_id)
+        .execute(transaction.as_mut())
+        .await?;
+
+    let status = sqlx::query("SELECT status FROM refunds WHERE id = $1 FOR UPDATE")
+        .bind(refund_id)
+        .fetch_one(transaction.as_mut())
+        .await?
+        .get::<RefundStatus, _>("status");
+
+    // a concurrent retry may have got here first
+    if status == RefundStatus::Pending {
+        complete_refund(transaction.as_mut(), order_id, refund_id, actor_id).await?;
+    }
+
+    transaction.commit().await?;
+
+    Ok(RefundOutcome::Refunded { amount })
+}
+
+// takes the refunded items off their order lines, puts the restocked ones back on sale and moves
+// the order to refunded once every item is returned, even when the amount given back was lower,
+// since shops may keep a fee
+async fn complete_refund(
+    connection: &mut PgConnection,
+    order_id: Uuid,
+    refund_id: Uuid,
+    actor_id: Uuid,
+) -> Result<(), RefundError> {
+    let reason = sqlx::query("SELECT reason FROM refunds WHERE id = $1")
+        .bind(refund_id)
+        .fetch_one(&mut *connection)
+        .await?
+        .get::<Option<String>, _>("reason");
+
+    let items = sqlx::query_as::<_, RefundItem>(
+        r#"
+        SELECT
+            refund_items.order_item_id,
+            order_items.item_id,
+            refund_items.amount,
+            refund_items.is_restocked
+        FROM refund_items
+        INNER JOIN order_items ON refund_items.order_item_id = order_items.id
+        WHERE refund_items.refund_id = $1
+        "#,
+    )
+    .bind(refund_id)
+    .fetch_all(&mut *connection)
+    .await?;
+
+    for item in &items {
+        sqlx::query("UPDATE order_items SET refunded_amount = refunded_amount + $1 WHERE id = $2")
+            .bind(item.amount)
+            .bind(item.order_item_id)
+            .execute(&mut *connection)
+            .await?;
+
+        // refunded orders still count their items as sold, the items only come back on sale
+        // through the stock ledger
+        if item.is_restocked {
+            sqlx::query(
+                r#"
+                INSERT INTO item_stock_updates (item_id, stock_added, reason, actor_id, note)
+                VALUES ($1, $2, 'refund', $3, $4)
+                "#,
+            )
+            .bind(item.item_id)
+            .bind(item.amount)
+            .bind(actor_id)
+            .bind(reason.clone())
+            .execute(&mut *connection)
+            .await?;
+        }
+    }
+
+    sqlx::query("UPDATE refunds SET status = 'completed' WHERE id = $1")
+        .bind(refund_id)
+        .execute(&mut *connection)
+        .await?;
+
+    let is_fully_refunded = sqlx::query(
+        r#"
+        SELECT COALESCE(BOOL_AND(refunded_amount >= amount), TRUE) AS is_fully_refunded
+        FROM order_items
+        WHERE order_id = $1
+        "#,
+    )
+    .bind(order_id)
+    .fetch_one(&mut *connection)
+    .await?
+    .get::<bool, _>("is_fully_refunded");
+
+    if is_fully_refunded {
+        transition_order_status(
+            &mut *connection,
+            order_id,
+            OrderStatus::Refunded,
+            Some(actor_id),
+            reason,
+        )
+        .await?;
+    }
+
+    Ok(())
+}
+
+// puts every item that hasn't been refunded yet back on sale, for orders that are moved to
+// refunded by a plain status change instead of through a refund
+pub async fn restock_unrefunded_items(
+    connection: &mut PgConnection,
+    order_id: Uuid,
+    actor_id: Uuid,
+) -> Result<(), sqlx::Error> {
+    sqlx::query(
+        r#"
+        INSERT INTO item_stock_updates (item_id, stock_added, reason, actor_id)
+        SELECT item_id, amount - refunded_amount, 'refund', $2 FROM order_items
+        WHERE order_id = $1 AND amount > refunded_amount
+        "#,
+    )
+    .bind(order_id)
+    .bind(actor_id)
+    .execute(&mut *connection)
+    .await?;
+
+    sqlx::query("UPDATE order_items SET refunded_amount = amount WHERE order_id = $1")
+        .bind(order_id)
+        .execute(&mut *connection)
+        .await?;
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn line(amount: i64, paid: i64) -> RefundableLine {
+        RefundableLine {
+            amount,
+            refunded_amount: 0,
+            paid,
+        }
+    }
+
+    // refunds the line in the given steps, the way separate refunds would
+    fn refund_in_steps(line: &mut RefundableLine, steps: &[i64]) -> Vec<i64> {
+        steps
+            .iter()
+            .map(|step| {
+                let price = line.price_of(*step);
+                line.refunded_amount += step;
+                price
+            })
+            .collect()
+    }
+
+    fn refund(items: Option<Vec<RefundableItem>>, amount: Option<i64>) -> CreatableRefund {
+        CreatableRefund {
+            items,
+            amount,
+            method: RefundMethod::Cash,
+            reference: None,
+            reason: None,
+            restock: None,
+        }
+    }
+
+    #[test]
+    fn units_refunded_one_by_one_add_up_to_what_was_paid() {
+        let mut line = line(3, 100);
+
+        let prices = refund_in_steps(&mut line, &[1, 1, 1]);
+
+        assert_eq!(prices, vec![33, 33, 34]);
+        assert_eq!(prices.iter().sum::<i64>(), 100);
+        assert_eq!(line.remaining(), 0);
+    }
+
+    #[test]
+    fn uneven_steps_add_up_to_what_was_paid() {
+        assert_eq!(refund_in_steps(&mut line(3, 100), &[2, 1]), vec![66, 34]);
+        assert_eq!(refund_in_steps(&mut line(3, 100), &[1, 2]), vec![33, 67]);
+        assert_eq!(
+            refund_in_steps(&mut line(7, 1000), &[3, 3, 1])
+                .iter()
+                .sum::<i64>(),
+            1000
+        );
+    }
+
+    #[test]
+    fn refunding_the_whole_line_at_once_gives_back_what_was_paid() {
+        assert_eq!(line(3, 100).price_of(3), 100);
+        assert_eq!(line(4, 0).price_of(4), 0);
+    }
+
+    #[test]
+    fn amount_defaults_to_the_items_up_to_what_is_left() {
+        let items = || {
+            Some(vec![RefundableItem {
+                order_item_id: Uuid::new_v4(),
+                amount: 1,
+            }])
+        };
+
+        assert_eq!(refund(items(), None).amount_to_refund(34, 100), Ok(34));
+        // a discounted order can be worth less than its items
+        assert_eq!(refund(items(), None).amount_to_refund(34, 20), Ok(20));
+    }
+
+    #[test]
+    fn amount_defaults_to_what_is_left_of_the_order() {
+        assert_eq!(refund(None, None).amount_to_refund(0, 60), Ok(60));
+    }
+
+    #[test]
+    fn amount_given_by_the_manager_is_capped_by_what_is_left() {
+        assert_eq!(refund(None, Some(50)).amount_to_refund(0, 60), Ok(50));
+        assert!(refund(None, Some(70)).amount_to_refund(0, 60).is_err());
+    }
+}
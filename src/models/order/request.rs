@@ -1,17 +1,37 @@
+use std::{collections::HashMap, fmt::Display};
+
 use mysk_lib::models::common::requests::FetchLevel;
 use serde::{Deserialize, Serialize};
-use sqlx::Row;
+use sqlx::{PgConnection, Row};
 use uuid::Uuid;
 
-use crate::models::address::Address;
+use crate::models::{
+    address::Address,
+    auth::permission::Role,
+    discount::{apply_discount_code, DiscountableItem},
+    item::{
+        preorder::PreorderWindow,
+        stock::{lock_items, ItemStock},
+    },
+    shop::{
+        db::ShopTable,
+        shipping::{ShippableItem, ShippingRules},
+    },
+};
+use crate::utils::promptpay;
 
 use super::{
     db::{DeliveryType, OrderStatus, PaymentMethod},
-    gbprimpay::create_qr_code,
+    limits::check_purchase_limits,
+    pickup::generate_pickup_code,
+    provider::{ChargeRequest, PaymentProvider},
+    refund::restock_unrefunded_items,
+    shipment::{ship_order, ShippingCarrier},
+    status::{record_initial_status, transition_order_status, OrderStatusError},
     Order,
 };
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
 pub struct QueryableOrder {
     pub id: Option<sqlx::types::Uuid>,
     pub shop_ids: Option<Vec<sqlx::types::Uuid>>,
@@ -56,21 +76,186 @@ pub struct CreatableOrder {
     address: Option<Address>,
     receiver_name: String,
     payment_method: PaymentMethod,
-    payment_slip_url: Option<String>,
     contact_email: String,
     contact_phone_number: Option<String>,
+    discount_code: Option<String>,
+}
+
+#[derive(Debug)]
+pub enum OrderCreationError {
+    // the order can't be placed as requested, the message is meant for the buyer
+    Rejected(String),
+    Database(sqlx::Error),
+}
+
+impl Display for OrderCreationError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Rejected(reason) => write!(f, "{}", reason),
+            Self::Database(err) => write!(f, "{}", err),
+        }
+    }
+}
+
+impl From<sqlx::Error> for OrderCreationError {
+    fn from(err: sqlx::Error) -> Self {
+        Self::Database(err)
+    }
+}
+
+impl From<OrderStatusError> for OrderCreationError {
+    fn from(err: OrderStatusError) -> Self {
+        match err {
+            OrderStatusError::Database(err) => Self::Database(err),
+            err => Self::Rejected(err.to_string()),
+        }
+    }
+}
+
+// an order created inside a larger transaction, see `CreatableOrder::place`
+pub struct PlacedOrder {
+    pub order_id: Uuid,
+    pub total_price: i64,
+    pub shop: ShopTable,
+}
+
+// everything needed to place an order except the items, used when the items come from the cart
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CheckoutDetails {
+    pub delivery_type: DeliveryType,
+    address: Option<Address>,
+    receiver_name: String,
+    payment_method: PaymentMethod,
+    contact_email: String,
+    contact_phone_number: Option<String>,
+    // discount codes belong to a shop, so each one is keyed by the id of the shop it is for
+    discount_codes: Option<HashMap<Uuid, String>>,
 }
 
 impl CreatableOrder {
+    pub fn from_checkout(
+        details: &CheckoutDetails,
+        shop_id: Uuid,
+        items: Vec<ItemAmount>,
+    ) -> Self {
+        Self {
+            items,
+            delivery_type: details.delivery_type,
+            address: details.address.clone(),
+            receiver_name: details.receiver_name.clone(),
+            payment_method: details.payment_method,
+            contact_email: details.contact_email.clone(),
+            contact_phone_number: details.contact_phone_number.clone(),
+            discount_code: details
+                .discount_codes
+                .as_ref()
+                .and_then(|codes| codes.get(&shop_id).cloned()),
+        }
+    }
+
+    // a sale made in person by the shop, the buyer's details are optional there
+    pub fn for_pos(
+        items: Vec<ItemAmount>,
+        payment_method: PaymentMethod,
+        receiver_name: Option<String>,
+        contact_email: Option<String>,
+        contact_phone_number: Option<String>,
+    ) -> Self {
+        Self {
+            items,
+            delivery_type: DeliveryType::POS,
+            address: None,
+            receiver_name: receiver_name.unwrap_or_default(),
+            payment_method,
+            contact_email: contact_email.unwrap_or_default(),
+            contact_phone_number,
+            discount_code: None,
+        }
+    }
+
+    pub fn payment_method(&self) -> PaymentMethod {
+        self.payment_method
+    }
+
+    pub fn item_ids(&self) -> Vec<Uuid> {
+        self.items.iter().map(|item| item.item_id).collect()
+    }
+
     pub async fn insert(
         &self,
         pool: &sqlx::PgPool,
-        gb_token: Option<String>,
+        payment_provider: &impl PaymentProvider,
         user_id: Option<Uuid>,
-    ) -> Result<Uuid, sqlx::Error> {
+        stock_hold: chrono::Duration,
+    ) -> Result<Uuid, OrderCreationError> {
+        let mut transaction = pool.begin().await?;
+
+        let placed = self
+            .place(transaction.as_mut(), pool, user_id, stock_hold, None)
+            .await?;
+
+        transaction.commit().await?;
+
+        let order = Order::get_by_id(
+            pool,
+            placed.order_id,
+            Some(&FetchLevel::Default),
+            Some(&FetchLevel::Compact),
+        )
+        .await?;
+
+        // create qr code, shops that take PromptPay without the payment gateway get a QR code of
+        // their own number and confirm the payment from the slip
+        let qr_code = match self.payment_method {
+            PaymentMethod::Promptpay => {
+                match (placed.shop.use_payment_gateway, placed.shop.promptpay_number) {
+                    (false, Some(promptpay_number)) => {
+                        promptpay::payload(&promptpay_number, Some(placed.total_price))
+                            .and_then(|payload| promptpay::png_data_url(&payload))
+                            .ok()
+                    }
+                    _ => payment_provider
+                        .create_charge(&ChargeRequest::from(order))
+                        .await
+                        .ok(),
+                }
+            }
+            _ => None,
+        };
+
+        // update order with qr code
+        sqlx::query(
+            r#"
+            UPDATE orders
+            SET qr_code_file = $1
+            WHERE id = $2
+            "#,
+        )
+        .bind(qr_code)
+        .bind(placed.order_id)
+        .execute(pool)
+        .await?;
+
+        Ok(placed.order_id)
+    }
+
+    // creates the order inside the caller's transaction, the payment QR code is left to the
+    // caller since it can only be made once the order is committed
+    pub async fn place(
+        &self,
+        connection: &mut PgConnection,
+        pool: &sqlx::PgPool,
+        user_id: Option<Uuid>,
+        stock_hold: chrono::Duration,
+        checkout_session_id: Option<Uuid>,
+    ) -> Result<PlacedOrder, OrderCreationError> {
         let mut total_price = 0;
 
-        let mut transaction = pool.begin().await?;
+        let item_ids = self
+            .items
+            .iter()
+            .map(|item| item.item_id)
+            .collect::<Vec<sqlx::types::Uuid>>();
 
         // make sure all the items are not out of stock and they are from the same shop
         let shop_ids = sqlx::query(
@@ -81,85 +266,108 @@ impl CreatableOrder {
             WHERE items.id = ANY($1)
             "#,
         )
-        .bind(
-            &self
-                .items
-                .iter()
-                .map(|item| item.item_id)
-                .collect::<Vec<sqlx::types::Uuid>>(),
-        )
-        .fetch_all(transaction.as_mut())
+        .bind(&item_ids)
+        .fetch_all(&mut *connection)
         .await?
         .into_iter()
         .map(|row| row.get::<sqlx::types::Uuid, _>("shop_id"))
         .collect::<Vec<sqlx::types::Uuid>>();
 
         if shop_ids.len() != 1 {
-            return Err(sqlx::Error::RowNotFound);
+            return Err(OrderCreationError::Rejected(
+                "all items in an order must come from the same shop".to_string(),
+            ));
         }
 
         let shop_id = shop_ids[0];
 
-        let curr_stock = sqlx::query(
-            "SELECT
-            CAST(SUM(stock_added) AS INT8) as life_time_stock_added,
-            CAST(SUM(amount) AS INT8) as life_time_amount
-      FROM
-        items
-        LEFT JOIN (
-          SELECT
-            item_id,
-            SUM(stock_added) AS stock_added
-          FROM item_stock_updates
-          GROUP BY item_id
-        ) AS stock_agg ON items.id = stock_agg.item_id
-        LEFT JOIN (
-          SELECT
-            item_id,
-            SUM(amount) AS amount
-          FROM order_items WHERE order_id IN (
-            SELECT id FROM orders WHERE NOT (shipment_status = 'canceled' OR (created_at > NOW() - INTERVAL '3 minute' AND is_paid = FALSE))
-          )
-          GROUP BY item_id
-        ) AS amount_agg ON items.id = amount_agg.item_id
-      WHERE
-        items.id = ANY($1)
-      GROUP BY
-        items.id",
-        )
-        .bind(
-            &self
-                .items
-                .iter()
-                .map(|item| item.item_id)
-                .collect::<Vec<sqlx::types::Uuid>>(),
-        )
-        .fetch_all(transaction.as_mut())
-        .await?
-        .into_iter()
-        .map(|row| row.get::<Option<i64>, _>("life_time_stock_added").unwrap_or(0) - row.get::<Option<i64>, _>("life_time_amount").unwrap_or(0))
-        .collect::<Vec<i64>>();
+        let shop = ShopTable::get_by_id(pool, shop_id).await?;
+
+        shop.check_order_options(self.payment_method, self.delivery_type)
+            .map_err(OrderCreationError::Rejected)?;
+
+        // the stock has to be read after the lock is taken, each statement sees the rows committed
+        // before it started so a competing order that held the lock is already counted
+        lock_items(&mut *connection, &item_ids).await?;
 
-        for (i, item) in self.items.iter().enumerate() {
-            if curr_stock[i] < item.amount {
-                return Err(sqlx::Error::RowNotFound);
+        let stocks = ItemStock::get_by_item_ids(&mut *connection, &item_ids).await?;
+        let windows = PreorderWindow::get_by_item_ids(&mut *connection, &item_ids).await?;
+        let now = chrono::Utc::now();
+
+        // item ids that are ordered as preorders
+        let mut preorder_item_ids = Vec::new();
+
+        // lines of the same item draw from the same stock, so they are checked together
+        let mut amounts: HashMap<Uuid, i64> = HashMap::new();
+
+        for item in &self.items {
+            *amounts.entry(item.item_id).or_default() += item.amount;
+        }
+
+        for (item_id, amount) in &amounts {
+            let available = stocks
+                .get(item_id)
+                .map(|stock| stock.available())
+                .unwrap_or(0);
+
+            let window = match windows.get(item_id) {
+                Some(window) => window,
+                None => {
+                    return Err(OrderCreationError::Rejected(format!(
+                        "item {} does not exist",
+                        item_id
+                    )))
+                }
+            };
+
+            let is_preorder = window
+                .check(*amount, available, now)
+                .map_err(OrderCreationError::Rejected)?;
+
+            if is_preorder {
+                preorder_item_ids.push(*item_id);
             }
         }
 
+        // the limits are checked while the items and their listings are locked so two orders from
+        // the same buyer can't both slip under them
+        check_purchase_limits(
+            &mut *connection,
+            &self.items,
+            user_id,
+            self.delivery_type == DeliveryType::POS,
+        )
+        .await?;
+
+        let mut discountable_items = Vec::new();
+        let mut shippable_items = Vec::new();
+
         for item in &self.items {
             let item_db = sqlx::query(
                 r#"
-                SELECT LEAST(price, discounted_price) AS price
+                SELECT LEAST(price, discounted_price) AS price, listing_id, weight_grams
                 FROM items
                 WHERE id = $1
                 "#,
             )
             .bind(item.item_id)
-            .fetch_one(transaction.as_mut())
+            .fetch_one(&mut *connection)
             .await?;
 
             total_price += item_db.get::<i64, _>("price") * item.amount;
 
+            discountable_items.push(DiscountableItem {
+                item_id: item.item_id,
+                listing_id: item_db.get::<Uuid, _>("listing_id"),
+                unit_price: item_db.get::<i64, _>("price"),
+                amount: item.amount,
+            });
+
+            shippable_items.push(ShippableItem {
+                amount: item.amount,
+                weight_grams: item_db.get::<Option<i64>, _>("weight_grams"),
+            });
+
             // if listing in the order is hidden, make sure that user is a shop manager
             let listing = sqlx::query(
                 r#"
@@ -170,33 +378,51 @@ impl CreatableOrder {
                 "#,
             )
             .bind(item.item_id)
-            .fetch_one(transaction.as_mut())
+            .fetch_one(&mut *connection)
             .await?;
 
             if listing.get::<bool, _>("is_hidden") {
-                let is_shop_manager = sqlx::query(
-                    r#"
-                    SELECT EXISTS (
-                        SELECT 1
-                        FROM shop_managers
-                        WHERE shop_id = $1 AND user_id = $2
-                    )
-                    "#,
-                )
-                .bind(shop_id)
-                .bind(user_id)
-                .fetch_one(transaction.as_mut())
-                .await?
-                .get::<bool, _>("exists");
-
-                if !is_shop_manager {
-                    return Err(sqlx::Error::RowNotFound);
+                let role = match user_id {
+                    Some(user_id) => Role::for_shop(pool, user_id, shop_id).await?,
+                    None => Role::Buyer,
+                };
+
+                if role < Role::ShopStaff {
+                    return Err(OrderCreationError::Rejected(format!(
+                        "item {} is not for sale",
+                        item.item_id
+                    )));
                 }
             }
         }
 
+        let (discount_code_id, item_discounts) = match &self.discount_code {
+            Some(code) => {
+                let (discount_code, item_discounts) = apply_discount_code(
+                    &mut *connection,
+                    shop_id,
+                    code,
+                    &discountable_items,
+                    user_id,
+                    &self.contact_email,
+                )
+                .await?;
+
+                (Some(discount_code.id), item_discounts)
+            }
+            None => (None, vec![0; discountable_items.len()]),
+        };
+
+        let discount_amount = item_discounts.iter().sum::<i64>();
+
         let shipping_fee = match &self.delivery_type {
-            DeliveryType::Delivery => 70,
+            DeliveryType::Delivery => ShippingRules::get_by_shop_id(&mut *connection, shop_id)
+                .await?
+                .quote(
+                    total_price - discount_amount,
+                    &shippable_items,
+                    self.address.as_ref().map(|address| address.province.as_str()),
+                ),
             _ => 0,
         };
 
@@ -212,11 +438,24 @@ impl CreatableOrder {
                 None => (None, None, None, None, None),
             };
 
+        // cash is only collected on delivery so those orders keep their items until canceled
+        let reserved_until = match self.payment_method {
+            PaymentMethod::Promptpay => Some(chrono::Utc::now() + stock_hold),
+            _ => None,
+        };
+
+        let order_total = total_price - discount_amount + shipping_fee;
+
+        let pickup_code = match self.delivery_type {
+            DeliveryType::SchoolPickup => Some(generate_pickup_code()),
+            _ => None,
+        };
+
         // create order
         let order_id = sqlx::query(
             r#"
-            INSERT INTO orders (buyer_id, street_address_line_1, street_address_line_2, province, district, zip_code, delivery_type, receiver_name, payment_method, total_price, payment_slip_url, contact_email, contact_phone_number, shop_id)
-            VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10, $11, $12, $13, $14)
+            INSERT INTO orders (buyer_id, street_address_line_1, street_address_line_2, province, district, zip_code, delivery_type, receiver_name, payment_method, total_price, payment_slip_url, contact_email, contact_phone_number, shop_id, reserved_until, discount_code_id, discount_amount, checkout_session_id, pickup_code, is_preorder)
+            VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10, $11, $12, $13, $14, $15, $16, $17, $18, $19, $20)
             RETURNING id
             "#,
         )
@@ -229,66 +468,53 @@ impl CreatableOrder {
         .bind(self.delivery_type)
         .bind(self.receiver_name.clone())
         .bind(self.payment_method)
-        .bind(total_price + shipping_fee)
-        .bind(self.payment_slip_url.clone())
+        .bind(order_total)
+        // slips only come in through `submit_slip`, which puts them up for review
+        .bind(None::<String>)
         .bind(self.contact_email.clone())
         .bind(self.contact_phone_number.clone())
         .bind(shop_id)
-        .fetch_one(transaction.as_mut())
+        .bind(reserved_until)
+        .bind(discount_code_id)
+        .bind(discount_amount)
+        .bind(checkout_session_id)
+        .bind(pickup_code)
+        .bind(!preorder_item_ids.is_empty())
+        .fetch_one(&mut *connection)
         .await?
         .get::<sqlx::types::Uuid, _>("id");
 
-        // create order items
-        for item in &self.items {
+        record_initial_status(&mut *connection, order_id, user_id).await?;
+
+        // create order items, with what was paid for them so refunds don't depend on later
+        // price changes
+        for ((item, discountable_item), discount) in self
+            .items
+            .iter()
+            .zip(&discountable_items)
+            .zip(&item_discounts)
+        {
             sqlx::query(
                 r#"
-                INSERT INTO order_items (order_id, item_id, amount)
-                VALUES ($1, $2, $3)
+                INSERT INTO order_items (order_id, item_id, amount, is_preorder, unit_price, discount_amount)
+                VALUES ($1, $2, $3, $4, $5, $6)
                 "#,
             )
             .bind(order_id)
             .bind(item.item_id)
             .bind(item.amount)
-            .execute(transaction.as_mut())
+            .bind(preorder_item_ids.contains(&item.item_id))
+            .bind(discountable_item.unit_price)
+            .bind(discount)
+            .execute(&mut *connection)
             .await?;
         }
-        transaction.commit().await?;
 
-        let order = Order::get_by_id(
-            pool,
+        Ok(PlacedOrder {
             order_id,
-            Some(&FetchLevel::Default),
-            Some(&FetchLevel::Compact),
-        )
-        .await?;
-
-        // create qr code
-        let qr_code = match self.payment_method {
-            PaymentMethod::Promptpay => {
-                let qr_code = create_qr_code(gb_token.unwrap_or("".to_string()), order).await;
-
-                match qr_code {
-                    Ok(qr_code) => Some(qr_code),
-                    Err(_) => None,
-                }
-            }
-            _ => None,
-        };
-
-        // update order with qr code
-        sqlx::query(
-            r#"
-            UPDATE orders
-            SET qr_code_file = $1
-            WHERE id = $2
-            "#,
-        )
-        .bind(qr_code)
-        .bind(order_id)
-        .execute(pool)
-        .await?;
-
-        Ok(order_id)
+            total_price: order_total,
+            shop,
+        })
     }
 
     pub fn validate(&self) -> Result<&Self, String> {
@@ -296,6 +522,16 @@ impl CreatableOrder {
             return Err("items must not be empty".to_string());
         }
 
+        if self.items.iter().any(|item| item.amount <= 0) {
+            return Err("every amount must be positive".to_string());
+        }
+
+        if self.delivery_type == DeliveryType::POS
+            || self.payment_method == PaymentMethod::POSCash
+        {
+            return Err("point of sale orders can only be made by shop managers".to_string());
+        }
+
         if self.receiver_name.is_empty() {
             return Err("receiver_name must not be empty".to_string());
         }
@@ -346,9 +582,13 @@ pub struct UpdatableOrder {
     pub payment_slip_url: Option<String>,
     pub contact_email: Option<String>,
     pub contact_phone_number: Option<String>,
-    pub is_paid: Option<bool>,
-    pub is_verified: Option<bool>,
+    // payment flags follow the status, see `OrderStatus::payment_flags`
     pub shipment_status: Option<OrderStatus>,
+    // required to move a delivery order to shipped, or sent alone to correct the tracking
+    pub shipping_carrier: Option<ShippingCarrier>,
+    pub tracking_number: Option<String>,
+    // stored on the status history entry
+    pub note: Option<String>,
 }
 
 impl UpdatableOrder {
@@ -356,14 +596,13 @@ impl UpdatableOrder {
         &self,
         pool: &sqlx::PgPool,
         order_id: sqlx::types::Uuid,
-    ) -> Result<(), sqlx::Error> {
+        actor_id: Uuid,
+    ) -> Result<(), OrderStatusError> {
         let mut query = String::from("UPDATE orders SET ");
         let mut param_count = 1;
 
         let mut param_segments = Vec::new();
         let mut string_params = Vec::new();
-        let mut bool_params = Vec::new();
-        let mut order_status_params = Vec::new();
 
         if let Some(receiver_name) = &self.receiver_name {
             param_segments.push(format!("receiver_name = ${}", param_count));
@@ -389,46 +628,76 @@ impl UpdatableOrder {
             param_count += 1;
         }
 
-        if let Some(is_paid) = &self.is_paid {
-            param_segments.push(format!("is_paid = ${}", param_count));
-            bool_params.push(is_paid);
-            param_count += 1;
-        }
+        let tracking = match (self.shipping_carrier, &self.tracking_number) {
+            (Some(carrier), Some(tracking_number)) => Some((carrier, tracking_number)),
+            (None, None) => None,
+            _ => {
+                return Err(OrderStatusError::Rejected(
+                    "shipping_carrier and tracking_number are set together".to_string(),
+                ))
+            }
+        };
 
-        if let Some(is_verified) = &self.is_verified {
-            param_segments.push(format!("is_verified = ${}", param_count));
-            bool_params.push(is_verified);
-            param_count += 1;
-        }
+        let mut transaction = pool.begin().await?;
 
-        if let Some(shipment_status) = &self.shipment_status {
-            param_segments.push(format!("shipment_status = ${}", param_count));
-            order_status_params.push(shipment_status);
-            param_count += 1;
-        }
+        if !param_segments.is_empty() {
+            query.push_str(&param_segments.join(", "));
 
-        query.push_str(&param_segments.join(", "));
+            query.push_str(" WHERE id = $");
+            query.push_str(&param_count.to_string());
 
-        query.push_str(" WHERE id = $");
-        query.push_str(&param_count.to_string());
+            let mut query_builder = sqlx::query(&query);
 
-        let mut query_builder = sqlx::query(&query);
+            for param in string_params {
+                query_builder = query_builder.bind(param);
+            }
 
-        for param in string_params {
-            query_builder = query_builder.bind(param);
-        }
+            query_builder = query_builder.bind(order_id);
 
-        for param in bool_params {
-            query_builder = query_builder.bind(param);
+            query_builder.execute(transaction.as_mut()).await?;
         }
 
-        for param in order_status_params {
-            query_builder = query_builder.bind(param);
+        match (self.shipment_status, tracking) {
+            (None | Some(OrderStatus::Shipped), Some((carrier, tracking_number))) => {
+                ship_order(
+                    transaction.as_mut(),
+                    order_id,
+                    carrier,
+                    tracking_number,
+                    actor_id,
+                    self.note.clone(),
+                )
+                .await?;
+            }
+            (Some(OrderStatus::Shipped), None) => {
+                return Err(OrderStatusError::Rejected(
+                    "shipping_carrier and tracking_number are needed to ship an order".to_string(),
+                ))
+            }
+            (Some(_), Some(_)) => {
+                return Err(OrderStatusError::Rejected(
+                    "tracking is only set when the order is shipped".to_string(),
+                ))
+            }
+            _ => (),
         }
 
-        query_builder = query_builder.bind(order_id);
+        if let Some(shipment_status) = self.shipment_status.filter(|_| tracking.is_none()) {
+            transition_order_status(
+                transaction.as_mut(),
+                order_id,
+                shipment_status,
+                Some(actor_id),
+                self.note.clone(),
+            )
+            .await?;
+
+            if shipment_status == OrderStatus::Refunded {
+                restock_unrefunded_items(transaction.as_mut(), order_id, actor_id).await?;
+            }
+        }
 
-        query_builder.execute(pool).await?;
+        transaction.commit().await?;
 
         Ok(())
     }
@@ -0,0 +1,68 @@
+use std::time::Duration;
+
+use sqlx::{PgPool, Row};
+use uuid::Uuid;
+
+use super::{
+    db::OrderStatus,
+    status::{transition_order_status, OrderStatusError},
+};
+
+// cancels unpaid orders whose stock hold has expired so that their items go back on sale, returns
+// the ids of the canceled orders
+pub async fn release_expired_reservations(pool: &PgPool) -> Result<Vec<Uuid>, OrderStatusError> {
+    let mut transaction = pool.begin().await?;
+
+    // orders locked by someone else, e.g. a payment that is being confirmed right now, are left
+    // for the next sweep. Orders with a slip waiting for review are paid for as far as the buyer
+    // knows, they wait for the manager instead
+    let order_ids = sqlx::query(
+        r#"
+        SELECT id FROM orders
+        WHERE shipment_status = 'awaiting_payment' AND reserved_until <= NOW()
+        AND NOT EXISTS (
+            SELECT 1 FROM payment_slips
+            WHERE payment_slips.order_id = orders.id AND payment_slips.status = 'pending'
+        )
+        FOR UPDATE SKIP LOCKED
+        "#,
+    )
+    .fetch_all(transaction.as_mut())
+    .await?
+    .into_iter()
+    .map(|row| row.get::<Uuid, _>("id"))
+    .collect::<Vec<Uuid>>();
+
+    for order_id in &order_ids {
+        transition_order_status(
+            transaction.as_mut(),
+            *order_id,
+            OrderStatus::Canceled,
+            None,
+            Some("payment was not received before the stock hold expired".to_string()),
+        )
+        .await?;
+    }
+
+    transaction.commit().await?;
+
+    Ok(order_ids)
+}
+
+pub fn spawn_reservation_sweeper(pool: PgPool, every: Duration) {
+    actix_rt::spawn(async move {
+        let mut interval = actix_rt::time::interval(every);
+
+        loop {
+            interval.tick().await;
+
+            match release_expired_reservations(&pool).await {
+                Ok(order_ids) if !order_ids.is_empty() => {
+                    println!("🧹 Released the stock held by {} orders", order_ids.len());
+                }
+                Ok(_) => {}
+                Err(err) => println!("🔥 Failed to release expired reservations: {}", err),
+            }
+        }
+    });
+}
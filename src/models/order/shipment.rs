@@ -0,0 +1,313 @@
+use std::fmt::Display;
+
+use serde::{Deserialize, Serialize};
+use sqlx::{PgConnection, Row, Type};
+use uuid::Uuid;
+
+use super::{
+    db::{DeliveryType, OrderStatus},
+    status::{transition_order_status, OrderStatusError},
+};
+
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum ShippingCarrier {
+    ThailandPost,
+    Kerry,
+    Flash,
+    JAndT,
+    // tracked on the carrier's own website, the buyer only gets the number
+    Other,
+}
+
+impl ShippingCarrier {
+    // the names managers type in a tracking import, matched case-insensitively
+    pub fn parse(name: &str) -> Option<Self> {
+        match name.trim().to_lowercase().as_str() {
+            "thailand_post" | "thailand post" | "thaipost" | "ems" => Some(Self::ThailandPost),
+            "kerry" | "kerry express" | "kex" => Some(Self::Kerry),
+            "flash" | "flash express" => Some(Self::Flash),
+            "j_and_t" | "j&t" | "j&t express" | "jt" => Some(Self::JAndT),
+            "other" => Some(Self::Other),
+            _ => None,
+        }
+    }
+
+    pub fn tracking_url(&self, tracking_number: &str) -> Option<String> {
+        match self {
+            Self::ThailandPost => Some(format!(
+                "https://track.thailandpost.co.th/?trackNumber={}",
+                tracking_number
+            )),
+            Self::Kerry => Some(format!(
+                "https://th.kerryexpress.com/th/track/?track={}",
+                tracking_number
+            )),
+            Self::Flash => Some(format!(
+                "https://www.flashexpress.co.th/fle/tracking?se={}",
+                tracking_number
+            )),
+            Self::JAndT => Some(format!(
+                "https://www.jtexpress.co.th/service/track?billcode={}",
+                tracking_number
+            )),
+            Self::Other => None,
+        }
+    }
+}
+
+impl Display for ShippingCarrier {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let s = match self {
+            Self::ThailandPost => "thailand_post",
+            Self::Kerry => "kerry",
+            Self::Flash => "flash",
+            Self::JAndT => "j_and_t",
+            Self::Other => "other",
+        };
+        write!(f, "{}", s)
+    }
+}
+
+impl Type<sqlx::Postgres> for ShippingCarrier {
+    fn type_info() -> sqlx::postgres::PgTypeInfo {
+        sqlx::postgres::PgTypeInfo::with_name("shipping_carrier")
+    }
+}
+
+impl sqlx::Encode<'_, sqlx::Postgres> for ShippingCarrier {
+    fn encode_by_ref(
+        &self,
+        buf: &mut <sqlx::Postgres as sqlx::database::HasArguments<'_>>::ArgumentBuffer,
+    ) -> sqlx::encode::IsNull {
+        let s: String = self.to_string();
+        <String as sqlx::Encode<sqlx::Postgres>>::encode(s, buf)
+    }
+}
+
+impl sqlx::Decode<'_, sqlx::Postgres> for ShippingCarrier {
+    fn decode(
+        value: <sqlx::Postgres as sqlx::database::HasValueRef<'_>>::ValueRef,
+    ) -> Result<Self, Box<dyn std::error::Error + 'static + Send + Sync>> {
+        let s: String = <String as sqlx::Decode<sqlx::Postgres>>::decode(value)?;
+        match s.as_str() {
+            "thailand_post" => Ok(Self::ThailandPost),
+            "kerry" => Ok(Self::Kerry),
+            "flash" => Ok(Self::Flash),
+            "j_and_t" => Ok(Self::JAndT),
+            "other" => Ok(Self::Other),
+            _ => Err("invalid shipping carrier".into()),
+        }
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct ShipmentTracking {
+    pub carrier: ShippingCarrier,
+    pub tracking_number: String,
+    pub tracking_url: Option<String>,
+}
+
+impl ShipmentTracking {
+    pub fn new(carrier: Option<ShippingCarrier>, tracking_number: Option<String>) -> Option<Self> {
+        match (carrier, tracking_number) {
+            (Some(carrier), Some(tracking_number)) => Some(Self {
+                carrier,
+                tracking_url: carrier.tracking_url(&tracking_number),
+                tracking_number,
+            }),
+            _ => None,
+        }
+    }
+}
+
+// attaches the tracking to a delivery order and moves it to shipped if it isn't yet, an order
+// that is already shipped only has its tracking corrected. Returns whether the order was shipped
+// by this call
+pub async fn ship_order(
+    connection: &mut PgConnection,
+    order_id: Uuid,
+    carrier: ShippingCarrier,
+    tracking_number: &str,
+    actor_id: Uuid,
+    note: Option<String>,
+) -> Result<bool, OrderStatusError> {
+    let tracking_number = tracking_number.trim();
+
+    if tracking_number.is_empty() {
+        return Err(OrderStatusError::Rejected("tracking number is empty".to_string()));
+    }
+
+    let order = sqlx::query(
+        r#"
+        SELECT delivery_type, shipment_status FROM orders
+        WHERE id = $1
+        FOR UPDATE
+        "#,
+    )
+    .bind(order_id)
+    .fetch_one(&mut *connection)
+    .await?;
+
+    if order.get::<DeliveryType, _>("delivery_type") != DeliveryType::Delivery {
+        return Err(OrderStatusError::Rejected("only delivery orders are shipped".to_string()));
+    }
+
+    let is_shipped = order.get::<OrderStatus, _>("shipment_status") == OrderStatus::Shipped;
+
+    if !is_shipped {
+        transition_order_status(
+            &mut *connection,
+            order_id,
+            OrderStatus::Shipped,
+            Some(actor_id),
+            note,
+        )
+        .await?;
+    }
+
+    sqlx::query(
+        r#"
+        UPDATE orders
+        SET shipping_carrier = $1, tracking_number = $2
+        WHERE id = $3
+        "#,
+    )
+    .bind(carrier)
+    .bind(tracking_number)
+    .bind(order_id)
+    .execute(&mut *connection)
+    .await?;
+
+    Ok(!is_shipped)
+}
+
+#[derive(Debug, Deserialize)]
+struct TrackingImportRow {
+    ref_id: String,
+    carrier: String,
+    tracking_number: String,
+}
+
+#[derive(Debug, Serialize)]
+pub struct TrackingImportResult {
+    // the line in the file, counting the header
+    pub line: u64,
+    pub ref_id: Option<String>,
+    pub order_id: Option<Uuid>,
+    // false when only the tracking of an already shipped order was changed
+    pub shipped: bool,
+    pub error: Option<String>,
+}
+
+// ships the shop's orders from a CSV with a ref_id, carrier and tracking_number column. Each line
+// is saved on its own and its outcome recorded, so one bad line doesn't hold back the rest of the
+// file
+pub async fn import_tracking(
+    pool: &sqlx::PgPool,
+    shop_id: Uuid,
+    csv: &str,
+    actor_id: Uuid,
+) -> Vec<TrackingImportResult> {
+    let mut reader = csv::ReaderBuilder::new()
+        .trim(csv::Trim::All)
+        .from_reader(csv.as_bytes());
+
+    let headers = match reader.headers() {
+        Ok(headers) => headers.clone(),
+        Err(e) => {
+            return vec![TrackingImportResult {
+                line: 1,
+                ref_id: None,
+                order_id: None,
+                shipped: false,
+                error: Some(e.to_string()),
+            }]
+        }
+    };
+
+    let mut results = Vec::new();
+
+    for record in reader.records() {
+        // quoted fields can span lines, so the line is taken from where the record starts
+        let row = record.and_then(|record| {
+            let line = record.position().map_or(0, |position| position.line());
+
+            record
+                .deserialize::<TrackingImportRow>(Some(&headers))
+                .map(|row| (line, row))
+        });
+
+        let (line, row) = match row {
+            Ok(row) => row,
+            Err(e) => {
+                results.push(TrackingImportResult {
+                    line: e.position().map_or(0, |position| position.line()),
+                    ref_id: None,
+                    order_id: None,
+                    shipped: false,
+                    error: Some(e.to_string()),
+                });
+
+                continue;
+            }
+        };
+
+        let mut result = TrackingImportResult {
+            line,
+            ref_id: Some(row.ref_id.clone()),
+            order_id: None,
+            shipped: false,
+            error: None,
+        };
+
+        match import_tracking_row(pool, shop_id, &row, actor_id, &mut result).await {
+            Ok(shipped) => result.shipped = shipped,
+            Err(e) => result.error = Some(e.to_string()),
+        }
+
+        results.push(result);
+    }
+
+    results
+}
+
+async fn import_tracking_row(
+    pool: &sqlx::PgPool,
+    shop_id: Uuid,
+    row: &TrackingImportRow,
+    actor_id: Uuid,
+    result: &mut TrackingImportResult,
+) -> Result<bool, OrderStatusError> {
+    let carrier = ShippingCarrier::parse(&row.carrier)
+        .ok_or_else(|| OrderStatusError::Rejected(format!("unknown carrier {}", row.carrier)))?;
+
+    let order_id = sqlx::query("SELECT id FROM orders WHERE ref_id = $1 AND shop_id = $2")
+        .bind(&row.ref_id)
+        .bind(shop_id)
+        .fetch_optional(pool)
+        .await?
+        .map(|order| order.get::<Uuid, _>("id"))
+        .ok_or_else(|| {
+            OrderStatusError::Rejected("this shop has no order with this ref_id".to_string())
+        })?;
+
+    result.order_id = Some(order_id);
+
+    // dropping the transaction on an error rolls the line back
+    let mut transaction = pool.begin().await?;
+
+    let shipped = ship_order(
+        transaction.as_mut(),
+        order_id,
+        carrier,
+        &row.tracking_number,
+        actor_id,
+        None,
+    )
+    .await?;
+
+    transaction.commit().await?;
+
+    Ok(shipped)
+}
@@ -0,0 +1,188 @@
+use std::fmt::Display;
+
+use chrono::{DateTime, Utc};
+use mysk_lib::models::common::requests::FetchLevel;
+use serde::{Deserialize, Serialize};
+use sqlx::{PgConnection, Row};
+use uuid::Uuid;
+
+use crate::models::auth::user::User;
+
+use super::db::{DeliveryType, OrderStatus, OrderStatusHistoryTable, PaymentMethod};
+
+#[derive(Debug)]
+pub enum OrderStatusError {
+    InvalidTransition { from: OrderStatus, to: OrderStatus },
+    // the transition is allowed but something it needs is missing
+    Rejected(String),
+    Database(sqlx::Error),
+}
+
+impl Display for OrderStatusError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::InvalidTransition { from, to } => {
+                write!(f, "cannot change order status from {} to {}", from, to)
+            }
+            Self::Rejected(reason) => write!(f, "{}", reason),
+            Self::Database(err) => write!(f, "{}", err),
+        }
+    }
+}
+
+impl From<sqlx::Error> for OrderStatusError {
+    fn from(err: sqlx::Error) -> Self {
+        Self::Database(err)
+    }
+}
+
+// moves the order to the given status and records who did it, the row is locked until the
+// surrounding transaction ends so concurrent transitions can't skip the validation
+pub async fn transition_order_status(
+    connection: &mut PgConnection,
+    order_id: Uuid,
+    to: OrderStatus,
+    actor_id: Option<Uuid>,
+    note: Option<String>,
+) -> Result<OrderStatus, OrderStatusError> {
+    let order = sqlx::query(
+        r#"
+        SELECT shipment_status, delivery_type, payment_method, total_price FROM orders
+        WHERE id = $1
+        FOR UPDATE
+        "#,
+    )
+    .bind(order_id)
+    .fetch_one(&mut *connection)
+    .await?;
+
+    let from = order.get::<OrderStatus, _>("shipment_status");
+    let delivery_type = order.get::<DeliveryType, _>("delivery_type");
+    let payment_method = order.get::<PaymentMethod, _>("payment_method");
+
+    if !from.can_transition_to(to, delivery_type, payment_method) {
+        return Err(OrderStatusError::InvalidTransition { from, to });
+    }
+
+    let (is_paid, is_verified) = match to.payment_flags(payment_method) {
+        Some((is_paid, is_verified)) => (Some(is_paid), Some(is_verified)),
+        None => (None, None),
+    };
+
+    sqlx::query(
+        r#"
+        UPDATE orders
+        SET shipment_status = $1, is_paid = COALESCE($2, is_paid), is_verified = COALESCE($3, is_verified)
+        WHERE id = $4
+        "#,
+    )
+    .bind(to)
+    .bind(is_paid)
+    .bind(is_verified)
+    .bind(order_id)
+    .execute(&mut *connection)
+    .await?;
+
+    sqlx::query(
+        r#"
+        INSERT INTO order_status_history (order_id, from_status, to_status, actor_id, note)
+        VALUES ($1, $2, $3, $4, $5)
+        "#,
+    )
+    .bind(order_id)
+    .bind(from)
+    .bind(to)
+    .bind(actor_id)
+    .bind(note)
+    .execute(&mut *connection)
+    .await?;
+
+    // handing over a cash order is when the cash is collected
+    let is_cash = matches!(payment_method, PaymentMethod::Cod | PaymentMethod::POSCash);
+
+    if is_cash && to == OrderStatus::Delivered {
+        sqlx::query(
+            r#"
+            INSERT INTO cash_collections (order_id, amount, collector_id)
+            VALUES ($1, $2, $3)
+            "#,
+        )
+        .bind(order_id)
+        .bind(order.get::<i64, _>("total_price"))
+        .bind(actor_id)
+        .execute(&mut *connection)
+        .await?;
+    }
+
+    Ok(from)
+}
+
+// the first history entry of a newly created order
+pub async fn record_initial_status(
+    connection: &mut PgConnection,
+    order_id: Uuid,
+    actor_id: Option<Uuid>,
+) -> Result<(), sqlx::Error> {
+    sqlx::query(
+        r#"
+        INSERT INTO order_status_history (order_id, from_status, to_status, actor_id)
+        VALUES ($1, NULL, $2, $3)
+        "#,
+    )
+    .bind(order_id)
+    .bind(OrderStatus::AwaitingPayment)
+    .bind(actor_id)
+    .execute(&mut *connection)
+    .await?;
+
+    Ok(())
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct OrderStatusHistory {
+    pub id: Uuid,
+    pub created_at: Option<DateTime<Utc>>,
+    pub from_status: Option<OrderStatus>,
+    pub to_status: OrderStatus,
+    // None when the change was made by the system, e.g. a payment webhook
+    pub actor: Option<User>,
+    pub note: Option<String>,
+}
+
+impl OrderStatusHistory {
+    pub async fn from_table(
+        pool: &sqlx::PgPool,
+        history: OrderStatusHistoryTable,
+        descendant_fetch_level: Option<&FetchLevel>,
+    ) -> Result<Self, sqlx::Error> {
+        let actor = match history.actor_id {
+            Some(actor_id) => Some(User::from_id(actor_id, pool, descendant_fetch_level).await?),
+            None => None,
+        };
+
+        Ok(Self {
+            id: history.id,
+            created_at: history.created_at,
+            from_status: history.from_status,
+            to_status: history.to_status,
+            actor,
+            note: history.note,
+        })
+    }
+
+    pub async fn get_by_order_id(
+        pool: &sqlx::PgPool,
+        order_id: Uuid,
+        descendant_fetch_level: Option<&FetchLevel>,
+    ) -> Result<Vec<Self>, sqlx::Error> {
+        let history_db = OrderStatusHistoryTable::get_by_order_id(pool, order_id).await?;
+
+        let mut history = Vec::new();
+
+        for entry in history_db {
+            history.push(Self::from_table(pool, entry, descendant_fetch_level).await?);
+        }
+
+        Ok(history)
+    }
+}
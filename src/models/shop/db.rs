@@ -1,9 +1,16 @@
+use std::fmt::Display;
+
 use chrono::{DateTime, Utc};
 use mysk_lib::models::common::requests::{FilterConfig, PaginationConfig, SortingConfig};
 use serde::{Deserialize, Serialize};
-use sqlx::FromRow;
+use sqlx::{types::Json, FromRow, PgConnection};
+
+use crate::models::order::db::{DeliveryType, PaymentMethod};
 
-use super::request::{QueryableShop, SortableShop};
+use super::{
+    request::{QueryableShop, SortableShop},
+    shipping::ShippingRules,
+};
 
 #[derive(Debug, Serialize, Deserialize, FromRow, Default)]
 pub struct ShopTable {
@@ -19,8 +26,11 @@ pub struct ShopTable {
     pub accept_promptpay: bool,
     pub promptpay_number: Option<String>,
     pub accept_cod: bool,
+    // PromptPay payments go through the payment gateway, or straight to promptpay_number
+    pub use_payment_gateway: bool,
     pub accent_color: Option<String>,
     pub background_color: Option<String>,
+    pub shipping_rules: Option<Json<ShippingRules>>,
 }
 
 impl ShopTable {
@@ -36,6 +46,48 @@ impl ShopTable {
         Ok(result)
     }
 
+    pub async fn get_by_ids(
+        pool: &sqlx::PgPool,
+        ids: Vec<sqlx::types::Uuid>,
+    ) -> Result<Vec<Self>, sqlx::Error> {
+        let result = sqlx::query_as::<_, Self>("SELECT * FROM shops WHERE id = ANY($1)")
+            .bind(ids)
+            .fetch_all(pool)
+            .await?;
+
+        Ok(result)
+    }
+
+    // whether checkout may use this payment method and delivery type, POS orders are made by
+    // the shop itself so they don't depend on what buyers are offered
+    pub fn check_order_options(
+        &self,
+        payment_method: PaymentMethod,
+        delivery_type: DeliveryType,
+    ) -> Result<(), String> {
+        let is_payment_accepted = match payment_method {
+            PaymentMethod::Promptpay => self.accept_promptpay,
+            PaymentMethod::Cod => self.accept_cod,
+            PaymentMethod::POSCash => true,
+        };
+
+        if !is_payment_accepted {
+            return Err(format!("{} does not accept {} payments", self.name_th, payment_method));
+        }
+
+        let is_delivery_allowed = match delivery_type {
+            DeliveryType::Delivery => self.is_delivery_allowed,
+            DeliveryType::SchoolPickup => self.is_school_pickup_allowed,
+            DeliveryType::POS => true,
+        };
+
+        if !is_delivery_allowed {
+            return Err(format!("{} does not offer {} orders", self.name_th, delivery_type));
+        }
+
+        Ok(())
+    }
+
     fn get_default_query() -> String {
         "SELECT * FROM shops".to_string()
     }
@@ -328,3 +380,115 @@ impl ShopTable {
         query_builder.fetch_all(pool).await
     }
 }
+
+#[derive(Debug, Serialize, Deserialize, FromRow)]
+pub struct ShopManagerTable {
+    pub id: sqlx::types::Uuid,
+    pub created_at: Option<DateTime<Utc>>,
+    pub shop_id: sqlx::types::Uuid,
+    pub user_id: sqlx::types::Uuid,
+    pub is_owner: bool,
+}
+
+impl ShopManagerTable {
+    pub async fn get_by_shop_id(
+        pool: &sqlx::PgPool,
+        shop_id: sqlx::types::Uuid,
+    ) -> Result<Vec<Self>, sqlx::Error> {
+        let result = sqlx::query_as::<_, Self>(
+            "SELECT * FROM shop_managers WHERE shop_id = $1 ORDER BY is_owner DESC, created_at ASC",
+        )
+        .bind(shop_id)
+        .fetch_all(pool)
+        .await?;
+
+        Ok(result)
+    }
+
+    pub async fn get_by_shop_id_and_user_id(
+        pool: &sqlx::PgPool,
+        shop_id: sqlx::types::Uuid,
+        user_id: sqlx::types::Uuid,
+    ) -> Result<Self, sqlx::Error> {
+        let result = sqlx::query_as::<_, Self>(
+            "SELECT * FROM shop_managers WHERE shop_id = $1 AND user_id = $2",
+        )
+        .bind(shop_id)
+        .bind(user_id)
+        .fetch_one(pool)
+        .await?;
+
+        Ok(result)
+    }
+
+    // every manager of the shop, locked until the transaction ends so changes to who owns the
+    // shop are made one at a time
+    pub async fn lock_by_shop_id(
+        connection: &mut PgConnection,
+        shop_id: sqlx::types::Uuid,
+    ) -> Result<Vec<Self>, sqlx::Error> {
+        let result = sqlx::query_as::<_, Self>(
+            "SELECT * FROM shop_managers WHERE shop_id = $1 ORDER BY id FOR UPDATE",
+        )
+        .bind(shop_id)
+        .fetch_all(connection)
+        .await?;
+
+        Ok(result)
+    }
+
+    // a shop must always be left with at least one owner
+    pub async fn delete(
+        pool: &sqlx::PgPool,
+        shop_id: sqlx::types::Uuid,
+        user_id: sqlx::types::Uuid,
+    ) -> Result<(), ShopManagerError> {
+        let mut transaction = pool.begin().await?;
+
+        let managers = Self::lock_by_shop_id(transaction.as_mut(), shop_id).await?;
+
+        let manager = managers
+            .iter()
+            .find(|manager| manager.user_id == user_id)
+            .ok_or(sqlx::Error::RowNotFound)?;
+
+        let owner_count = managers.iter().filter(|manager| manager.is_owner).count();
+
+        if manager.is_owner && owner_count <= 1 {
+            return Err(ShopManagerError::Rejected(
+                "cannot remove the last owner of a shop".to_string(),
+            ));
+        }
+
+        sqlx::query("DELETE FROM shop_managers WHERE shop_id = $1 AND user_id = $2")
+            .bind(shop_id)
+            .bind(user_id)
+            .execute(transaction.as_mut())
+            .await?;
+
+        transaction.commit().await?;
+
+        Ok(())
+    }
+}
+
+#[derive(Debug)]
+pub enum ShopManagerError {
+    Rejected(String),
+    Database(sqlx::Error),
+}
+
+impl Display for ShopManagerError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Rejected(reason) => write!(f, "{}", reason),
+            Self::Database(err) => write!(f, "{}", err),
+        }
+    }
+}
+
+impl From<sqlx::Error> for ShopManagerError {
+    fn from(err: sqlx::Error) -> Self {
+        Self::Database(err)
+    }
+}
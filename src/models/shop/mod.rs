@@ -1,6 +1,7 @@
 use std::vec;
 
 use async_recursion::async_recursion;
+use chrono::{DateTime, Utc};
 use mysk_lib::models::common::{
     requests::{FetchLevel, FilterConfig, PaginationConfig, SortingConfig},
     string::MultiLangString,
@@ -9,14 +10,16 @@ use serde::{Deserialize, Serialize};
 use sqlx::Row;
 
 use self::{
-    db::ShopTable,
+    db::{ShopManagerTable, ShopTable},
     request::{QueryableShop, SortableShop},
+    shipping::ShippingRules,
 };
 
-use super::{collection::Collection, item::Item, listing::Listing};
+use super::{auth::user::User, collection::Collection, item::Item, listing::Listing};
 
 pub(crate) mod db;
 pub(crate) mod request;
+pub(crate) mod shipping;
 
 #[derive(Debug, Serialize, Deserialize)]
 pub struct IdOnlyShop {
@@ -45,6 +48,8 @@ pub struct DefaultShop {
     pub accept_promptpay: bool,
     pub promptpay_number: Option<String>,
     pub accept_cod: bool,
+    pub use_payment_gateway: bool,
+    pub shipping_rules: ShippingRules,
 }
 
 #[derive(Debug, Serialize, Deserialize)]
@@ -60,6 +65,8 @@ pub struct DetailedShop {
     pub accept_promptpay: bool,
     pub promptpay_number: Option<String>,
     pub accept_cod: bool,
+    pub use_payment_gateway: bool,
+    pub shipping_rules: ShippingRules,
     pub listings: Vec<Listing>,
     pub collections: Vec<Collection>,
     pub items: Vec<Item>,
@@ -97,6 +104,8 @@ impl From<db::ShopTable> for DefaultShop {
             accept_promptpay: shop.accept_promptpay,
             promptpay_number: shop.promptpay_number,
             accept_cod: shop.accept_cod,
+            use_payment_gateway: shop.use_payment_gateway,
+            shipping_rules: shop.shipping_rules.map(|rules| rules.0).unwrap_or_default(),
         }
     }
 }
@@ -180,6 +189,8 @@ impl DetailedShop {
             accept_promptpay: shop.accept_promptpay,
             promptpay_number: shop.promptpay_number,
             accept_cod: shop.accept_cod,
+            use_payment_gateway: shop.use_payment_gateway,
+            shipping_rules: shop.shipping_rules.map(|rules| rules.0).unwrap_or_default(),
             listings,
             items,
             collections,
@@ -224,6 +235,22 @@ impl Shop {
         Self::from_table(pool, shop, fetch_level, descendant_fetch_level).await
     }
 
+    pub async fn get_by_ids(
+        pool: &sqlx::PgPool,
+        ids: Vec<sqlx::types::Uuid>,
+        fetch_level: Option<&FetchLevel>,
+        descendant_fetch_level: Option<&FetchLevel>,
+    ) -> Result<Vec<Self>, sqlx::Error> {
+        let shops = ShopTable::get_by_ids(pool, ids).await?;
+
+        let mut result = vec![];
+        for shop in shops {
+            let data = Self::from_table(pool, shop, fetch_level, descendant_fetch_level).await?;
+            result.push(data);
+        }
+        Ok(result)
+    }
+
     pub async fn query(
         pool: &sqlx::PgPool,
         filter: &Option<FilterConfig<QueryableShop>>,
@@ -256,3 +283,53 @@ impl Serialize for Shop {
         }
     }
 }
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct ShopManager {
+    pub id: sqlx::types::Uuid,
+    pub created_at: Option<DateTime<Utc>>,
+    pub user: User,
+    pub is_owner: bool,
+}
+
+impl ShopManager {
+    pub async fn from_table(
+        pool: &sqlx::PgPool,
+        manager: ShopManagerTable,
+        descendant_fetch_level: Option<&FetchLevel>,
+    ) -> Result<Self, sqlx::Error> {
+        let user = User::from_id(manager.user_id, pool, descendant_fetch_level).await?;
+
+        Ok(Self {
+            id: manager.id,
+            created_at: manager.created_at,
+            user,
+            is_owner: manager.is_owner,
+        })
+    }
+
+    pub async fn get_by_shop_id(
+        pool: &sqlx::PgPool,
+        shop_id: sqlx::types::Uuid,
+        descendant_fetch_level: Option<&FetchLevel>,
+    ) -> Result<Vec<Self>, sqlx::Error> {
+        let managers = ShopManagerTable::get_by_shop_id(pool, shop_id).await?;
+
+        let mut result = vec![];
+        for manager in managers {
+            result.push(Self::from_table(pool, manager, descendant_fetch_level).await?);
+        }
+        Ok(result)
+    }
+
+    pub async fn get_by_shop_id_and_user_id(
+        pool: &sqlx::PgPool,
+        shop_id: sqlx::types::Uuid,
+        user_id: sqlx::types::Uuid,
+        descendant_fetch_level: Option<&FetchLevel>,
+    ) -> Result<Self, sqlx::Error> {
+        let manager = ShopManagerTable::get_by_shop_id_and_user_id(pool, shop_id, user_id).await?;
+
+        Self::from_table(pool, manager, descendant_fetch_level).await
+    }
+}
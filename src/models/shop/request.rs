@@ -1,5 +1,15 @@
-use mysk_lib::models::common::string::FlexibleMultiLangString;
+use mysk_lib::models::common::string::{FlexibleMultiLangString, MultiLangString};
 use serde::{Deserialize, Serialize};
+use sqlx::Row;
+use uuid::Uuid;
+
+use crate::models::auth::user::UserTable;
+use crate::utils::promptpay;
+
+use super::{
+    db::{ShopManagerError, ShopManagerTable},
+    shipping::ShippingRules,
+};
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct QueryableShop {
@@ -24,6 +34,152 @@ pub enum SortableShop {
     CreatedAt,
 }
 
+#[derive(Debug, Serialize, Deserialize)]
+pub struct CreatableShop {
+    pub name: MultiLangString,
+    pub logo_url: Option<String>,
+    pub accent_color: Option<String>,
+    pub background_color: Option<String>,
+    pub is_school_pickup_allowed: Option<bool>,
+    pub pickup_location: Option<String>,
+    pub pickup_description: Option<String>,
+    pub is_delivery_allowed: Option<bool>,
+    pub accept_promptpay: Option<bool>,
+    pub promptpay_number: Option<String>,
+    pub accept_cod: Option<bool>,
+    pub use_payment_gateway: Option<bool>,
+}
+
+impl CreatableShop {
+    // the user creating the shops becomes the owner of every one of them
+    pub async fn bulk_insert(
+        shops: &[CreatableShop],
+        owner_id: Uuid,
+        pool: &sqlx::PgPool,
+    ) -> Result<Vec<Uuid>, sqlx::Error> {
+        let mut transaction = pool.begin().await?;
+
+        let mut ids = Vec::new();
+
+        for shop in shops {
+            let res = sqlx::query(
+                r#"
+                INSERT INTO shops (name_th, name_en, logo_url, accent_color, background_color, is_school_pickup_allowed, pickup_location, pickup_description, is_delivery_allowed, accept_promptpay, promptpay_number, accept_cod, use_payment_gateway)
+                VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10, $11, $12, $13)
+                returning id
+                "#,
+            )
+            .bind(&shop.name.th)
+            .bind(&shop.name.en)
+            .bind(&shop.logo_url)
+            .bind(&shop.accent_color)
+            .bind(&shop.background_color)
+            .bind(shop.is_school_pickup_allowed.unwrap_or(false))
+            .bind(&shop.pickup_location)
+            .bind(&shop.pickup_description)
+            .bind(shop.is_delivery_allowed.unwrap_or(false))
+            .bind(shop.accept_promptpay.unwrap_or(false))
+            .bind(&shop.promptpay_number)
+            .bind(shop.accept_cod.unwrap_or(false))
+            .bind(shop.use_payment_gateway.unwrap_or(true))
+            .fetch_one(transaction.as_mut())
+            .await?;
+
+            let shop_id = res.get::<Uuid, _>("id");
+
+            sqlx::query(
+                r#"
+                INSERT INTO shop_managers (shop_id, user_id, is_owner)
+                VALUES ($1, $2, TRUE)
+                "#,
+            )
+            .bind(shop_id)
+            .bind(owner_id)
+            .execute(transaction.as_mut())
+            .await?;
+
+            ids.push(shop_id);
+        }
+
+        transaction.commit().await?;
+
+        Ok(ids)
+    }
+
+    pub fn validate(&self) -> Result<&Self, String> {
+        if self.name.th.is_empty() {
+            return Err("name.th must not be empty".to_string());
+        }
+
+        if self.accept_promptpay.unwrap_or(false) && self.promptpay_number.is_none() {
+            return Err(
+                "promptpay_number must not be empty when accept_promptpay is true".to_string(),
+            );
+        }
+
+        if let Some(promptpay_number) = &self.promptpay_number {
+            promptpay::payload(promptpay_number, None).map_err(|err| err.to_string())?;
+        }
+
+        Ok(self)
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct CreatableShopManager {
+    // managers are invited by the email they sign in with
+    pub email: String,
+    pub is_owner: Option<bool>,
+}
+
+impl CreatableShopManager {
+    pub async fn insert(
+        &self,
+        pool: &sqlx::PgPool,
+        shop_id: Uuid,
+    ) -> Result<Uuid, ShopManagerError> {
+        let user = match UserTable::get_by_email(pool, &self.email).await {
+            Some(user) => user,
+            None => return Err(ShopManagerError::Database(sqlx::Error::RowNotFound)),
+        };
+
+        let is_owner = self.is_owner.unwrap_or(false);
+
+        let mut transaction = pool.begin().await?;
+
+        let managers = ShopManagerTable::lock_by_shop_id(transaction.as_mut(), shop_id).await?;
+        let owner_count = managers.iter().filter(|manager| manager.is_owner).count();
+
+        let is_demotion = managers
+            .iter()
+            .any(|manager| manager.user_id == user.id && manager.is_owner);
+
+        if is_demotion && !is_owner && owner_count <= 1 {
+            return Err(ShopManagerError::Rejected(
+                "cannot demote the last owner of a shop".to_string(),
+            ));
+        }
+
+        // inviting an existing manager again only updates their ownership
+        sqlx::query(
+            r#"
+            INSERT INTO shop_managers (shop_id, user_id, is_owner)
+            VALUES ($1, $2, $3)
+            ON CONFLICT (shop_id, user_id) DO UPDATE SET is_owner = EXCLUDED.is_owner
+            "#,
+        )
+        .bind(shop_id)
+        .bind(user.id)
+        .bind(is_owner)
+        .execute(transaction.as_mut())
+        .await?;
+
+        transaction.commit().await?;
+
+        Ok(user.id)
+    }
+}
+
 #[derive(Debug, Serialize, Deserialize)]
 #[serde(rename_all = "snake_case")]
 pub struct UpdatableShop {
@@ -33,13 +189,28 @@ pub struct UpdatableShop {
     pub accent_color: Option<String>,
     pub background_color: Option<String>,
     pub logo_url: Option<String>,
+    pub promptpay_number: Option<String>,
     pub accept_promptpay: Option<bool>,
     pub accept_cod: Option<bool>,
+    pub use_payment_gateway: Option<bool>,
     pub is_school_pickup_allowed: Option<bool>,
     pub is_delivery_allowed: Option<bool>,
+    pub shipping_rules: Option<ShippingRules>,
 }
 
 impl UpdatableShop {
+    pub fn validate(&self) -> Result<&Self, String> {
+        if let Some(promptpay_number) = &self.promptpay_number {
+            promptpay::payload(promptpay_number, None).map_err(|err| err.to_string())?;
+        }
+
+        if let Some(shipping_rules) = &self.shipping_rules {
+            shipping_rules.validate()?;
+        }
+
+        Ok(self)
+    }
+
     pub async fn commit_changes(
         &self,
         pool: &sqlx::PgPool,
@@ -100,6 +271,12 @@ impl UpdatableShop {
             param_count += 1;
         }
 
+        if let Some(promptpay_number) = &self.promptpay_number {
+            param_segments.push(format!("promptpay_number = ${}", param_count));
+            string_params.push(promptpay_number);
+            param_count += 1;
+        }
+
         if let Some(accept_promptpay) = &self.accept_promptpay {
             param_segments.push(format!("accept_promptpay = ${}", param_count));
             bool_params.push(accept_promptpay);
@@ -112,6 +289,12 @@ impl UpdatableShop {
             param_count += 1;
         }
 
+        if let Some(use_payment_gateway) = &self.use_payment_gateway {
+            param_segments.push(format!("use_payment_gateway = ${}", param_count));
+            bool_params.push(use_payment_gateway);
+            param_count += 1;
+        }
+
         if let Some(is_school_pickup_allowed) = &self.is_school_pickup_allowed {
             param_segments.push(format!("is_school_pickup_allowed = ${}", param_count));
             bool_params.push(is_school_pickup_allowed);
@@ -124,6 +307,11 @@ impl UpdatableShop {
             param_count += 1;
         }
 
+        if self.shipping_rules.is_some() {
+            param_segments.push(format!("shipping_rules = ${}", param_count));
+            param_count += 1;
+        }
+
         query.push_str(&param_segments.join(", "));
         query.push_str(&format!(" WHERE id = ${}", param_count));
 
@@ -139,6 +327,10 @@ impl UpdatableShop {
             query_builder = query_builder.bind(param);
         }
 
+        if let Some(shipping_rules) = &self.shipping_rules {
+            query_builder = query_builder.bind(sqlx::types::Json(shipping_rules));
+        }
+
         query_builder = query_builder.bind(shop_id);
 
         query_builder.execute(pool).await?;
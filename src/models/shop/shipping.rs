@@ -0,0 +1,314 @@
+use serde::{Deserialize, Serialize};
+use sqlx::{types::Json, PgExecutor, Row};
+use uuid::Uuid;
+
+use crate::models::order::{db::DeliveryType, request::ItemAmount};
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct WeightTier {
+    pub max_weight_grams: i64,
+    pub fee: i64,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ProvinceSurcharge {
+    pub province: String,
+    pub fee: i64,
+}
+
+// how a shop charges for delivery, stored as json on the shop. Every part that is set is added
+// up, unless the order is large enough to ship for free.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ShippingRules {
+    pub flat_fee: i64,
+    pub per_item_fee: i64,
+    // the fee of the lightest tier the parcel fits in is added, heavier parcels use the heaviest
+    // tier.
+    // Items without a weight don't count towards the parcel's weight.
+    pub weight_tiers: Vec<WeightTier>,
+    // compared with the price of the items after discounts, without shipping
+    pub free_shipping_threshold: Option<i64>,
+    pub province_surcharges: Vec<ProvinceSurcharge>,
+}
+
+// shops that never set up shipping keep the fee every shop used to charge
+impl Default for ShippingRules {
+    fn default() -> Self {
+        Self {
+            flat_fee: 70,
+            per_item_fee: 0,
+            weight_tiers: vec![],
+            free_shipping_threshold: None,
+            province_surcharges: vec![],
+        }
+    }
+}
+
+pub struct ShippableItem {
+    pub amount: i64,
+    pub weight_grams: Option<i64>,
+}
+
+impl ShippingRules {
+    pub async fn get_by_shop_id(
+        executor: impl PgExecutor<'_>,
+        shop_id: Uuid,
+    ) -> Result<Self, sqlx::Error> {
+        let res = sqlx::query("SELECT shipping_rules FROM shops WHERE id = $1")
+            .bind(shop_id)
+            .fetch_one(executor)
+            .await?;
+
+        Ok(res
+            .get::<Option<Json<Self>>, _>("shipping_rules")
+            .map(|rules| rules.0)
+            .unwrap_or_default())
+    }
+
+    pub fn quote(&self, subtotal: i64, items: &[ShippableItem], province: Option<&str>) -> i64 {
+        if let Some(threshold) = self.free_shipping_threshold {
+            if subtotal >= threshold {
+                return 0;
+            }
+        }
+
+        let units = items.iter().map(|item| item.amount).sum::<i64>();
+
+        let weight = items
+            .iter()
+            .map(|item| item.weight_grams.unwrap_or(0) * item.amount)
+            .sum::<i64>();
+
+        let weight_fee = self
+            .weight_tiers
+            .iter()
+            .filter(|tier| weight <= tier.max_weight_grams)
+            .min_by_key(|tier| tier.max_weight_grams)
+            .or_else(|| self.weight_tiers.iter().max_by_key(|tier| tier.max_weight_grams))
+            .map(|tier| tier.fee)
+            .unwrap_or(0);
+
+        let province_fee = match province {
+            Some(province) => self
+                .province_surcharges
+                .iter()
+                .find(|surcharge| surcharge.province.trim() == province.trim())
+                .map(|surcharge| surcharge.fee)
+                .unwrap_or(0),
+            None => 0,
+        };
+
+        self.flat_fee + self.per_item_fee * units + weight_fee + province_fee
+    }
+
+    pub fn validate(&self) -> Result<&Self, String> {
+        if self.flat_fee < 0 || self.per_item_fee < 0 {
+            return Err("shipping fees must not be negative".to_string());
+        }
+
+        if self
+            .weight_tiers
+            .iter()
+            .any(|tier| tier.fee < 0 || tier.max_weight_grams <= 0)
+        {
+            return Err("weight tiers need a positive weight and a fee of at least 0".to_string());
+        }
+
+        if self
+            .province_surcharges
+            .iter()
+            .any(|surcharge| surcharge.fee < 0 || surcharge.province.trim().is_empty())
+        {
+            return Err("province surcharges need a province and a fee of at least 0".to_string());
+        }
+
+        Ok(self)
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct ShippingQuoteRequest {
+    pub items: Vec<ItemAmount>,
+    pub delivery_type: DeliveryType,
+    pub province: Option<String>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct ShippingQuote {
+    pub shop_id: Uuid,
+    pub subtotal: i64,
+    pub shipping_fee: i64,
+}
+
+impl ShippingQuoteRequest {
+    // one quote per shop since every shop ships its own order, discount codes are only known
+    // when the order is placed so the free shipping threshold is checked without them
+    pub async fn quote(&self, pool: &sqlx::PgPool) -> Result<Vec<ShippingQuote>, sqlx::Error> {
+        let item_ids = self
+            .items
+            .iter()
+            .map(|item| item.item_id)
+            .collect::<Vec<Uuid>>();
+
+        let rows = sqlx::query(
+            r#"
+            SELECT items.id, LEAST(price, discounted_price) AS price, weight_grams, listings.shop_id
+            FROM items
+            INNER JOIN listings ON items.listing_id = listings.id
+            WHERE items.id = ANY($1)
+            "#,
+        )
+        .bind(&item_ids)
+        .fetch_all(pool)
+        .await?;
+
+        let mut shops: Vec<(Uuid, i64, Vec<ShippableItem>)> = vec![];
+
+        for item in &self.items {
+            let row = rows
+                .iter()
+                .find(|row| row.get::<Uuid, _>("id") == item.item_id)
+                .ok_or(sqlx::Error::RowNotFound)?;
+
+            let shop_id = row.get::<Uuid, _>("shop_id");
+            let price = row.get::<i64, _>("price") * item.amount;
+            let shippable = ShippableItem {
+                amount: item.amount,
+                weight_grams: row.get::<Option<i64>, _>("weight_grams"),
+            };
+
+            match shops.iter_mut().find(|(id, _, _)| *id == shop_id) {
+                Some((_, subtotal, items)) => {
+                    *subtotal += price;
+                    items.push(shippable);
+                }
+                None => shops.push((shop_id, price, vec![shippable])),
+            }
+        }
+
+        let mut quotes = vec![];
+
+        for (shop_id, subtotal, items) in shops {
+            let shipping_fee = match self.delivery_type {
+                DeliveryType::Delivery => ShippingRules::get_by_shop_id(pool, shop_id)
+                    .await?
+                    .quote(subtotal, &items, self.province.as_deref()),
+                _ => 0,
+            };
+
+            quotes.push(ShippingQuote {
+                shop_id,
+                subtotal,
+                shipping_fee,
+            });
+        }
+
+        Ok(quotes)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn item(amount: i64, weight_grams: Option<i64>) -> ShippableItem {
+        ShippableItem {
+            amount,
+            weight_grams,
+        }
+    }
+
+    fn tiered() -> ShippingRules {
+        ShippingRules {
+            flat_fee: 20,
+            per_item_fee: 5,
+            weight_tiers: vec![
+                WeightTier {
+                    max_weight_grams: 2000,
+                    fee: 60,
+                },
+                WeightTier {
+                    max_weight_grams: 500,
+                    fee: 30,
+                },
+            ],
+            free_shipping_threshold: Some(1000),
+            province_surcharges: vec![ProvinceSurcharge {
+                province: "เชียงใหม่".to_string(),
+                fee: 40,
+            }],
+        }
+    }
+
+    #[test]
+    fn default_rules_charge_the_old_flat_fee() {
+        let rules = ShippingRules::default();
+
+        assert_eq!(
+            rules.quote(100, &[item(3, Some(800))], Some("กรุงเทพมหานคร")),
+            70
+        );
+    }
+
+    #[test]
+    fn parts_that_are_set_are_added_up() {
+        // 2 units of 200 grams fit in the 500 gram tier
+        assert_eq!(
+            tiered().quote(200, &[item(2, Some(200))], None),
+            20 + 10 + 30
+        );
+    }
+
+    #[test]
+    fn lightest_tier_the_parcel_fits_in_is_used() {
+        assert_eq!(
+            tiered().quote(200, &[item(1, Some(500))], None),
+            20 + 5 + 30
+        );
+        assert_eq!(
+            tiered().quote(200, &[item(1, Some(501))], None),
+            20 + 5 + 60
+        );
+    }
+
+    #[test]
+    fn parcel_heavier_than_every_tier_uses_the_heaviest() {
+        assert_eq!(
+            tiered().quote(200, &[item(3, Some(1000))], None),
+            20 + 15 + 60
+        );
+    }
+
+    #[test]
+    fn items_without_a_weight_weigh_nothing() {
+        assert_eq!(
+            tiered().quote(200, &[item(1, None), item(1, Some(400))], None),
+            20 + 10 + 30
+        );
+    }
+
+    #[test]
+    fn free_shipping_from_the_threshold() {
+        assert_eq!(
+            tiered().quote(999, &[item(1, Some(100))], None),
+            20 + 5 + 30
+        );
+        assert_eq!(
+            tiered().quote(1000, &[item(1, Some(100))], Some("เชียงใหม่")),
+            0
+        );
+    }
+
+    #[test]
+    fn province_surcharge_matches_the_trimmed_province() {
+        let items = [item(1, Some(100))];
+
+        assert_eq!(
+            tiered().quote(200, &items, Some(" เชียงใหม่ ")),
+            20 + 5 + 30 + 40
+        );
+        assert_eq!(tiered().quote(200, &items, Some("เชียงราย")), 20 + 5 + 30);
+        assert_eq!(tiered().quote(200, &items, None), 20 + 5 + 30);
+    }
+}
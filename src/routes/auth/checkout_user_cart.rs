@@ -0,0 +1,220 @@
+use actix_web::{post, web, HttpResponse, Responder};
+use mysk_lib::models::common::{
+    requests::{FetchLevel, RequestType},
+    response::{ErrorResponseType, ErrorType, MetadataType, PaginationType, ResponseType},
+};
+use uuid::Uuid;
+
+use crate::{
+    models::{
+        auth::user::User,
+        item::CartItem,
+        order::{
+            checkout::checkout,
+            request::{
+                CheckoutDetails, CreatableOrder, OrderCreationError, QueryableOrder, SortableOrder,
+            },
+            Order,
+        },
+    },
+    utils::email::send_invoice_email,
+    AppState,
+};
+
+// places one order per shop in the cart, paid together when there is more than one
+#[post("/auth/user/carts/checkout")]
+pub async fn checkout_user_cart(
+    user: User,
+    data: web::Data<AppState>,
+    request: web::Json<RequestType<CheckoutDetails, QueryableOrder, SortableOrder>>,
+) -> Result<impl Responder, actix_web::Error> {
+    let pool = &data.db;
+    let credential = &data.smtp_credential;
+    let payment_provider = &data.payment_provider;
+    let stock_hold = chrono::Duration::minutes(data.env.stock_hold_minutes);
+    let user_id = user.id();
+
+    let details = match &request.data {
+        Some(data) => data,
+        None => {
+            let response: ErrorResponseType = ErrorResponseType::new(
+                ErrorType {
+                    id: Uuid::new_v4().to_string(),
+                    code: 400,
+                    error_type: "bad_request".to_string(),
+                    detail: "request body is empty".to_string(),
+                    source: "/auth/user/carts/checkout".to_string(),
+                },
+                Some(MetadataType::new(None::<PaginationType>)),
+            );
+
+            return Ok(HttpResponse::BadRequest().json(response));
+        }
+    };
+
+    let cart = match CartItem::get_user_cart_by_shop(user_id, pool).await {
+        Ok(cart) => cart,
+        Err(err) => {
+            let response: ErrorResponseType = ErrorResponseType::new(
+                ErrorType {
+                    id: Uuid::new_v4().to_string(),
+                    code: 500,
+                    error_type: "internal_server_error".to_string(),
+                    detail: err.to_string(),
+                    source: "/auth/user/carts/checkout".to_string(),
+                },
+                Some(MetadataType::new(None::<PaginationType>)),
+            );
+
+            return Ok(HttpResponse::InternalServerError().json(response));
+        }
+    };
+
+    let orders = cart
+        .into_iter()
+        .map(|(shop_id, items)| CreatableOrder::from_checkout(details, shop_id, items))
+        .collect::<Vec<CreatableOrder>>();
+
+    if orders.is_empty() {
+        let response: ErrorResponseType = ErrorResponseType::new(
+            ErrorType {
+                id: Uuid::new_v4().to_string(),
+                code: 400,
+                error_type: "bad_request".to_string(),
+                detail: "cart is empty".to_string(),
+                source: "/auth/user/carts/checkout".to_string(),
+            },
+            Some(MetadataType::new(None::<PaginationType>)),
+        );
+
+        return Ok(HttpResponse::BadRequest().json(response));
+    }
+
+    for order in &orders {
+        if let Err(err) = order.validate() {
+            let response: ErrorResponseType = ErrorResponseType::new(
+                ErrorType {
+                    id: Uuid::new_v4().to_string(),
+                    code: 400,
+                    error_type: "bad_request".to_string(),
+                    detail: err,
+                    source: "/auth/user/carts/checkout".to_string(),
+                },
+                Some(MetadataType::new(None::<PaginationType>)),
+            );
+
+            return Ok(HttpResponse::BadRequest().json(response));
+        }
+    }
+
+    // the cart only empties once every order has been placed
+    let res = checkout(pool, payment_provider, &orders, Some(user_id), stock_hold).await;
+
+    let order_ids = match res {
+        Ok(order_ids) => order_ids,
+        Err(OrderCreationError::Database(err)) => {
+            let response: ErrorResponseType = ErrorResponseType::new(
+                ErrorType {
+                    id: Uuid::new_v4().to_string(),
+                    code: 500,
+                    error_type: "internal_server_error".to_string(),
+                    detail: err.to_string(),
+                    source: "/auth/user/carts/checkout".to_string(),
+                },
+                Some(MetadataType::new(None::<PaginationType>)),
+            );
+
+            return Ok(HttpResponse::InternalServerError().json(response));
+        }
+        Err(err) => {
+            let response: ErrorResponseType = ErrorResponseType::new(
+                ErrorType {
+                    id: Uuid::new_v4().to_string(),
+                    code: 400,
+                    error_type: "bad_request".to_string(),
+                    detail: err.to_string(),
+                    source: "/auth/user/carts/checkout".to_string(),
+                },
+                Some(MetadataType::new(None::<PaginationType>)),
+            );
+
+            return Ok(HttpResponse::BadRequest().json(response));
+        }
+    };
+
+    // the orders are already committed, answering with an error would only make the buyer check
+    // out again and place them twice
+    let res = CartItem::remove_many_from_user_cart(
+        user_id,
+        &orders
+            .iter()
+            .flat_map(|order| order.item_ids())
+            .collect::<Vec<Uuid>>(),
+        pool,
+    )
+    .await;
+
+    if let Err(e) = res {
+        println!("Error: {}", e);
+    }
+
+    let fetch_level = match request.fetch_level.clone() {
+        Some(fetch_level) => fetch_level,
+        None => FetchLevel::Default,
+    };
+
+    let descendant_fetch_level = match request.descendant_fetch_level.clone() {
+        Some(descendant_fetch_level) => descendant_fetch_level,
+        None => FetchLevel::IdOnly,
+    };
+
+    for order_id in order_ids.clone() {
+        let order = Order::get_by_id(
+            pool,
+            order_id,
+            Some(&FetchLevel::Default),
+            Some(&FetchLevel::Compact),
+        )
+        .await;
+
+        let res = match order {
+            Ok(order) => send_invoice_email(credential, order).map_err(|e| e.to_string()),
+            Err(e) => Err(e.to_string()),
+        };
+
+        if let Err(e) = res {
+            println!("Error: {}", e);
+        }
+    }
+
+    let orders = Order::get_by_ids(
+        pool,
+        order_ids,
+        Some(&fetch_level),
+        Some(&descendant_fetch_level),
+    )
+    .await;
+
+    match orders {
+        Ok(orders) => {
+            let response: ResponseType<Vec<Order>> =
+                ResponseType::new(orders, Some(MetadataType::new(None::<PaginationType>)));
+
+            Ok(HttpResponse::Ok().json(response))
+        }
+        Err(err) => {
+            let response: ErrorResponseType = ErrorResponseType::new(
+                ErrorType {
+                    id: Uuid::new_v4().to_string(),
+                    code: 500,
+                    error_type: "internal_server_error".to_string(),
+                    detail: err.to_string(),
+                    source: "/auth/user/carts/checkout".to_string(),
+                },
+                Some(MetadataType::new(None::<PaginationType>)),
+            );
+
+            Ok(HttpResponse::InternalServerError().json(response))
+        }
+    }
+}
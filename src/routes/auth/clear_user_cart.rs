@@ -0,0 +1,41 @@
+use actix_web::{delete, web, HttpResponse, Responder};
+use mysk_lib::models::common::response::{
+    ErrorResponseType, ErrorType, MetadataType, PaginationType, ResponseType,
+};
+use uuid::Uuid;
+
+use crate::{
+    models::{auth::user::User, item::CartItem},
+    AppState,
+};
+
+#[delete("/auth/user/carts")]
+pub async fn clear_user_cart(
+    user: User,
+    data: web::Data<AppState>,
+) -> Result<impl Responder, actix_web::Error> {
+    let pool = &data.db;
+
+    let res = CartItem::clear_user_cart(user.id(), pool).await;
+
+    match res {
+        Ok(_) => Ok(HttpResponse::NoContent().json(ResponseType::new(
+            None::<bool>,
+            Some(MetadataType::new(None::<PaginationType>)),
+        ))),
+        Err(e) => {
+            let response: ErrorResponseType = ErrorResponseType::new(
+                ErrorType {
+                    id: Uuid::new_v4().to_string(),
+                    code: 500,
+                    error_type: "internal_server_error".to_string(),
+                    detail: e.to_string(),
+                    source: "/auth/user/carts".to_string(),
+                },
+                None::<MetadataType>,
+            );
+
+            Ok(HttpResponse::InternalServerError().json(response))
+        }
+    }
+}
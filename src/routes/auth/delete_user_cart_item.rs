@@ -0,0 +1,57 @@
+use actix_web::{delete, web, HttpResponse, Responder};
+use mysk_lib::models::common::response::{
+    ErrorResponseType, ErrorType, MetadataType, PaginationType, ResponseType,
+};
+use uuid::Uuid;
+
+use crate::{
+    models::{auth::user::User, item::CartItem},
+    AppState,
+};
+
+#[delete("/auth/user/carts/{item_id}")]
+pub async fn delete_user_cart_item(
+    user: User,
+    data: web::Data<AppState>,
+    item_id: web::Path<Uuid>,
+) -> Result<impl Responder, actix_web::Error> {
+    let pool = &data.db;
+    let item_id = item_id.into_inner();
+
+    let res = CartItem::remove_from_user_cart(user.id(), item_id, pool).await;
+
+    match res {
+        Ok(_) => Ok(HttpResponse::NoContent().json(ResponseType::new(
+            None::<bool>,
+            Some(MetadataType::new(None::<PaginationType>)),
+        ))),
+        Err(sqlx::Error::RowNotFound) => {
+            let response: ErrorResponseType = ErrorResponseType::new(
+                ErrorType {
+                    id: Uuid::new_v4().to_string(),
+                    code: 404,
+                    error_type: "entity_not_found".to_string(),
+                    detail: format!("item {item_id} is not in the cart"),
+                    source: format!("/auth/user/carts/{item_id}"),
+                },
+                None::<MetadataType>,
+            );
+
+            Ok(HttpResponse::NotFound().json(response))
+        }
+        Err(e) => {
+            let response: ErrorResponseType = ErrorResponseType::new(
+                ErrorType {
+                    id: Uuid::new_v4().to_string(),
+                    code: 500,
+                    error_type: "internal_server_error".to_string(),
+                    detail: e.to_string(),
+                    source: format!("/auth/user/carts/{item_id}"),
+                },
+                None::<MetadataType>,
+            );
+
+            Ok(HttpResponse::InternalServerError().json(response))
+        }
+    }
+}
@@ -0,0 +1,67 @@
+use actix_web::{get, web, HttpResponse, Responder};
+use mysk_lib::models::common::{
+    requests::{FetchLevel, RequestType},
+    response::{ErrorResponseType, ErrorType, MetadataType, PaginationType, ResponseType},
+};
+use uuid::Uuid;
+
+use crate::{
+    models::{
+        auth::guest_cart::OptionalGuestCart,
+        item::{
+            request::{QueryableItem, SortableItem},
+            CartItem,
+        },
+    },
+    AppState,
+};
+
+#[get("/guest/carts")]
+pub async fn get_guest_cart_items(
+    guest_cart: OptionalGuestCart,
+    data: web::Data<AppState>,
+    request_query: web::Query<RequestType<CartItem, QueryableItem, SortableItem>>,
+) -> Result<impl Responder, actix_web::Error> {
+    let pool = &data.db;
+
+    let fetch_level = match request_query.fetch_level.clone() {
+        Some(fetch_level) => fetch_level,
+        None => FetchLevel::Default,
+    };
+
+    let descendant_fetch_level = match request_query.descendant_fetch_level.clone() {
+        Some(descendant_fetch_level) => descendant_fetch_level,
+        None => FetchLevel::IdOnly,
+    };
+
+    // a guest that has not added anything yet simply has an empty cart
+    let items = match guest_cart.0 {
+        Some(guest_cart) => {
+            guest_cart
+                .get_items(pool, Some(&fetch_level), Some(&descendant_fetch_level))
+                .await
+        }
+        None => Ok(vec![]),
+    };
+
+    match items {
+        Ok(items) => Ok(HttpResponse::Ok().json(ResponseType::new(
+            items,
+            Some(MetadataType::new(None::<PaginationType>)),
+        ))),
+        Err(e) => {
+            let response: ErrorResponseType = ErrorResponseType::new(
+                ErrorType {
+                    id: Uuid::new_v4().to_string(),
+                    code: 404,
+                    error_type: "entity_not_found".to_string(),
+                    detail: e.to_string(),
+                    source: "/guest/carts".to_string(),
+                },
+                Some(MetadataType::new(None::<PaginationType>)),
+            );
+
+            Ok(HttpResponse::NotFound().json(response))
+        }
+    }
+}
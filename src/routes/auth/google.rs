@@ -10,6 +10,7 @@ use sqlx::types::Uuid;
 
 use crate::{
     models::auth::{
+        guest_cart::OptionalGuestCart,
         oauth::{verify_id_token, GoogleUserResult, TokenClaims},
         user::UserTable,
     },
@@ -33,6 +34,7 @@ struct GoogleTokenResponse {
 async fn google_oauth_handler(
     data: web::Data<AppState>,
     query: web::Json<OAuthRequest>,
+    guest_cart: OptionalGuestCart,
 ) -> impl Responder {
     // dbg!(query);
 
@@ -99,6 +101,13 @@ async fn google_oauth_handler(
         }
     };
 
+    // a failed merge leaves the guest cart in place and must not keep the user from signing in
+    if let Some(guest_cart) = guest_cart.0 {
+        if let Err(err) = guest_cart.merge_into_user_cart(&data.db, user_id).await {
+            println!("🔥 Failed to merge guest cart {}: {}", guest_cart.id, err);
+        }
+    }
+
     let jwt_secret = data.env.jwt_secret.to_owned();
     let now = Utc::now();
     let iat = now.timestamp() as usize;
@@ -1,6 +1,11 @@
+pub(crate) mod checkout_user_cart;
+pub(crate) mod clear_user_cart;
 pub(crate) mod create_user_addresses;
 pub(crate) mod delete_user_addresses;
+pub(crate) mod delete_user_cart_item;
+pub(crate) mod get_guest_cart_items;
 pub(crate) mod get_user_cart_items;
 pub(crate) mod google;
+pub(crate) mod update_user_cart_item;
 pub(crate) mod user;
 pub(crate) mod user_wishlists;
@@ -0,0 +1,106 @@
+use actix_web::{patch, web, HttpResponse, Responder};
+use mysk_lib::models::common::{
+    requests::RequestType,
+    response::{ErrorResponseType, ErrorType, MetadataType, PaginationType, ResponseType},
+};
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+
+use crate::{
+    models::{
+        auth::user::User,
+        item::{
+            request::{QueryableItem, SortableItem},
+            CartItem,
+        },
+    },
+    AppState,
+};
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct UpdateCartItemRequest {
+    pub amount: i64,
+}
+
+#[patch("/auth/user/carts/{item_id}")]
+pub async fn update_user_cart_item(
+    user: User,
+    data: web::Data<AppState>,
+    item_id: web::Path<Uuid>,
+    request: web::Json<RequestType<UpdateCartItemRequest, QueryableItem, SortableItem>>,
+) -> Result<impl Responder, actix_web::Error> {
+    let pool = &data.db;
+    let item_id = item_id.into_inner();
+    let user_id = user.id();
+
+    let data = match &request.data {
+        Some(data) => data,
+        None => {
+            let response: ErrorResponseType = ErrorResponseType::new(
+                ErrorType {
+                    id: Uuid::new_v4().to_string(),
+                    code: 400,
+                    error_type: "bad_request".to_string(),
+                    detail: "request body is empty".to_string(),
+                    source: format!("/auth/user/carts/{item_id}"),
+                },
+                Some(MetadataType::new(None::<PaginationType>)),
+            );
+
+            return Ok(HttpResponse::BadRequest().json(response));
+        }
+    };
+
+    // removing an item has its own endpoint
+    if data.amount < 1 {
+        let response: ErrorResponseType = ErrorResponseType::new(
+            ErrorType {
+                id: Uuid::new_v4().to_string(),
+                code: 400,
+                error_type: "bad_request".to_string(),
+                detail: "amount must be at least 1".to_string(),
+                source: format!("/auth/user/carts/{item_id}"),
+            },
+            Some(MetadataType::new(None::<PaginationType>)),
+        );
+
+        return Ok(HttpResponse::BadRequest().json(response));
+    }
+
+    let res = CartItem::update_user_cart_amount(user_id, item_id, data.amount, pool).await;
+
+    match res {
+        Ok(_) => Ok(HttpResponse::NoContent().json(ResponseType::new(
+            None::<bool>,
+            Some(MetadataType::new(None::<PaginationType>)),
+        ))),
+        Err(sqlx::Error::RowNotFound) => {
+            let response: ErrorResponseType = ErrorResponseType::new(
+                ErrorType {
+                    id: Uuid::new_v4().to_string(),
+                    code: 404,
+                    error_type: "entity_not_found".to_string(),
+                    detail: format!("item {item_id} is not in the cart"),
+                    source: format!("/auth/user/carts/{item_id}"),
+                },
+                None::<MetadataType>,
+            );
+
+            Ok(HttpResponse::NotFound().json(response))
+        }
+        Err(e) => {
+            let response: ErrorResponseType = ErrorResponseType::new(
+                ErrorType {
+                    id: Uuid::new_v4().to_string(),
+                    code: 500,
+                    error_type: "internal_server_error".to_string(),
+                    detail: e.to_string(),
+                    source: format!("/auth/user/carts/{item_id}"),
+                },
+                None::<MetadataType>,
+            );
+
+            Ok(HttpResponse::InternalServerError().json(response))
+        }
+    }
+}
@@ -3,21 +3,24 @@ use mysk_lib::models::common::{
     requests::{FetchLevel, RequestType},
     response::{ErrorResponseType, ErrorType, MetadataType, PaginationType, ResponseType},
 };
-use sqlx::Row;
 use uuid::Uuid;
 
 use crate::{
     models::{
-        auth::user::User,
+        auth::{
+            permission::{authorize, Role, ShopResource},
+            user::User,
+        },
         collection::{
             request::{CreatableCollection, QueryableCollection, SortableCollection},
             Collection,
         },
     },
+    utils::idempotency::Idempotency,
     AppState,
 };
 
-#[post("/collections")]
+#[post("/collections", wrap = "Idempotency")]
 pub async fn create_collections(
     data: web::Data<AppState>,
     request: web::Json<
@@ -45,43 +48,17 @@ pub async fn create_collections(
         }
     };
 
-    let user_id = match user {
-        User::IdOnly(user) => user.id,
-        User::Compact(user) => user.id,
-        User::Default(user) => user.id,
-        User::Detailed(user) => user.id,
-    };
+    let user_id = user.id();
 
     for collection in data {
-        let res = sqlx::query(
-            r#"
-            SELECT COUNT(id) FROM shop_managers WHERE user_id = $1 AND shop_id = $2
-            "#,
+        authorize(
+            pool,
+            user_id,
+            ShopResource::Shop(collection.shop_id),
+            Role::ShopStaff,
+            "/collections",
         )
-        .bind(user_id)
-        .bind(collection.shop_id)
-        .fetch_one(pool)
-        .await;
-
-        if let Ok(res) = res {
-            if res.get::<Option<i64>, _>("count").unwrap_or(0) == 0 {
-                let response: ErrorResponseType = ErrorResponseType::new(
-                    ErrorType {
-                        id: Uuid::new_v4().to_string(),
-                        code: 403,
-                        error_type: "forbidden".to_string(),
-                        detail: format!(
-                            "user {} is not a manager of shop {}",
-                            user_id, collection.shop_id
-                        ),
-                        source: format!("/collections"),
-                    },
-                    None::<MetadataType>,
-                );
-
-                return Ok(HttpResponse::Forbidden().json(response));
-            }
-        }
+        .await?;
     }
 
     let collection_ids = CreatableCollection::bulk_insert(data.to_vec(), pool).await;
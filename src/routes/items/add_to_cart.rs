@@ -8,7 +8,10 @@ use uuid::Uuid;
 
 use crate::{
     models::{
-        auth::user::User,
+        auth::{
+            guest_cart::{GuestCart, OptionalGuestCart},
+            user::{OptionalUser, User},
+        },
         item::{
             request::{QueryableItem, SortableItem},
             CartItem, Item,
@@ -22,23 +25,24 @@ pub struct AddToCartRequest {
     pub amount: i64,
 }
 
+// sent back to guests so they can keep adding to the same cart through the `X-Guest-Cart` header
+#[derive(Debug, Serialize, Deserialize)]
+pub struct GuestCartResponse {
+    pub guest_cart_token: String,
+}
+
 #[post("/items/{item_id}/add-to-cart")]
 pub async fn add_to_cart(
     data: web::Data<AppState>,
     item_id: web::Path<Uuid>,
     request: web::Json<RequestType<AddToCartRequest, QueryableItem, SortableItem>>,
-    user: User,
+    user: OptionalUser,
+    guest_cart: OptionalGuestCart,
 ) -> Result<impl Responder, actix_web::Error> {
     let pool = &data.db;
+    let jwt_secret = &data.env.jwt_secret;
     let item_id = item_id.into_inner();
 
-    let user_id = match user {
-        User::IdOnly(user) => user.id,
-        User::Compact(user) => user.id,
-        User::Default(user) => user.id,
-        User::Detailed(user) => user.id,
-    };
-
     let data = match &request.data {
         Some(data) => data,
         None => {
@@ -57,6 +61,31 @@ pub async fn add_to_cart(
         }
     };
 
+    if data.amount < 0 {
+        let response: ErrorResponseType = ErrorResponseType::new(
+            ErrorType {
+                id: Uuid::new_v4().to_string(),
+                code: 400,
+                error_type: "bad_request".to_string(),
+                detail: "amount must not be negative".to_string(),
+                source: format!("/items/{item_id}/add-to-cart"),
+            },
+            None::<MetadataType>,
+        );
+
+        return Ok(HttpResponse::BadRequest().json(response));
+    }
+
+    let user_id = match user.0 {
+        Some(User::IdOnly(user)) => user.id,
+        Some(User::Compact(user)) => user.id,
+        Some(User::Default(user)) => user.id,
+        Some(User::Detailed(user)) => user.id,
+        None => {
+            return add_to_guest_cart(pool, jwt_secret, guest_cart, item_id, data.amount).await
+        }
+    };
+
     let cart_item = CartItem {
         item: Item::IdOnly(crate::models::item::IdOnlyItem { id: item_id }),
         amount: data.amount,
@@ -85,3 +114,45 @@ pub async fn add_to_cart(
         }
     }
 }
+
+async fn add_to_guest_cart(
+    pool: &sqlx::PgPool,
+    jwt_secret: &str,
+    guest_cart: OptionalGuestCart,
+    item_id: Uuid,
+    amount: i64,
+) -> Result<HttpResponse, actix_web::Error> {
+    let res = match GuestCart::find_or_create(pool, guest_cart.0).await {
+        Ok(guest_cart) => guest_cart
+            .set_item_amount(pool, item_id, amount)
+            .await
+            .map(|_| guest_cart),
+        Err(err) => Err(err),
+    };
+
+    let token = match res {
+        Ok(guest_cart) => guest_cart.token(jwt_secret).map_err(|err| err.to_string()),
+        Err(err) => Err(err.to_string()),
+    };
+
+    match token {
+        Ok(guest_cart_token) => Ok(HttpResponse::Ok().json(ResponseType::new(
+            GuestCartResponse { guest_cart_token },
+            Some(MetadataType::new(None::<PaginationType>)),
+        ))),
+        Err(e) => {
+            let response: ErrorResponseType = ErrorResponseType::new(
+                ErrorType {
+                    id: Uuid::new_v4().to_string(),
+                    code: 500,
+                    error_type: "internal_server_error".to_string(),
+                    detail: e,
+                    source: format!("/items/{item_id}/add-to-cart"),
+                },
+                None::<MetadataType>,
+            );
+
+            Ok(HttpResponse::InternalServerError().json(response))
+        }
+    }
+}
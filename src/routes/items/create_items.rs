@@ -3,21 +3,24 @@ use mysk_lib::models::common::{
     requests::{FetchLevel, RequestType},
     response::{ErrorResponseType, ErrorType, MetadataType, PaginationType, ResponseType},
 };
-use sqlx::Row;
 use uuid::Uuid;
 
 use crate::{
     models::{
-        auth::user::User,
+        auth::{
+            permission::{authorize, Role, ShopResource},
+            user::User,
+        },
         item::{
             request::{CreatableItem, QueryableItem, SortableItem},
             Item,
         },
     },
+    utils::idempotency::Idempotency,
     AppState,
 };
 
-#[post("/items")]
+#[post("/items", wrap = "Idempotency")]
 pub async fn create_items(
     data: web::Data<AppState>,
     request: web::Json<RequestType<Vec<CreatableItem>, QueryableItem, SortableItem>>,
@@ -43,73 +46,29 @@ pub async fn create_items(
         }
     };
 
-    let user_id = match user {
-        User::IdOnly(user) => user.id,
-        User::Compact(user) => user.id,
-        User::Default(user) => user.id,
-        User::Detailed(user) => user.id,
-    };
+    let user_id = user.id();
 
     for item in data {
-        let res = match item.listing_id {
-            Some(listing_id) => {
-                sqlx::query(
-                    r#"
-                    SELECT COUNT(id) FROM listings WHERE id = $1 AND shop_id IN (SELECT shop_id FROM shop_managers WHERE user_id = $2)
-                    "#,
-                )
-                .bind(listing_id)
-                .bind(user_id)
-                .fetch_one(pool)
-                .await
-            }
-            None => {
-                let shop_id = match item.shop_id {
-                    Some(shop_id) => shop_id,
-                    None => {
-                        let response: ErrorResponseType = ErrorResponseType::new(
-                            ErrorType {
-                                id: Uuid::new_v4().to_string(),
-                                code: 400,
-                                error_type: "bad_request".to_string(),
-                                detail: "missing shop_id".to_string(),
-                                source: format!("/items"),
-                            },
-                            None::<MetadataType>,
-                        );
-
-                        return Ok(HttpResponse::BadRequest().json(response));
-                    }
-                };
-
-                sqlx::query(
-                    r#"
-                    SELECT COUNT(id) FROM shop_managers WHERE user_id = $1 AND shop_id = $2
-                    "#,
-                )
-                .bind(user_id)
-                .bind(shop_id)
-                .fetch_one(pool)
-                .await
-            }
-        };
-
-        if let Ok(res) = res {
-            if res.get::<Option<i64>, _>("count").unwrap_or(0) == 0 {
+        let resource = match (item.listing_id, item.shop_id) {
+            (Some(listing_id), _) => ShopResource::Listing(listing_id),
+            (None, Some(shop_id)) => ShopResource::Shop(shop_id),
+            (None, None) => {
                 let response: ErrorResponseType = ErrorResponseType::new(
                     ErrorType {
                         id: Uuid::new_v4().to_string(),
-                        code: 403,
-                        error_type: "forbidden".to_string(),
-                        detail: format!("user {} is not a manager of a shop", user_id),
+                        code: 400,
+                        error_type: "bad_request".to_string(),
+                        detail: "missing shop_id".to_string(),
                         source: format!("/items"),
                     },
                     None::<MetadataType>,
                 );
 
-                return Ok(HttpResponse::Forbidden().json(response));
+                return Ok(HttpResponse::BadRequest().json(response));
             }
-        }
+        };
+
+        authorize(pool, user_id, resource, Role::ShopStaff, "/items").await?;
     }
 
     let item_ids = CreatableItem::bulk_insert(data.to_vec(), pool).await;
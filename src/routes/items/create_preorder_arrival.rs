@@ -0,0 +1,133 @@
+use actix_web::{post, web, HttpResponse, Responder};
+use mysk_lib::models::common::{
+    requests::{FetchLevel, RequestType},
+    response::{ErrorResponseType, ErrorType, MetadataType, PaginationType, ResponseType},
+};
+use uuid::Uuid;
+
+use crate::{
+    models::{
+        auth::permission::{RequireShopRole, Staff},
+        item::{
+            db::ItemTable,
+            preorder::CreatablePreorderArrival,
+            request::{QueryableItem, SortableItem},
+            stock::StockUpdateError,
+        },
+        order::Order,
+    },
+    utils::email::send_preorder_arrived_email,
+    AppState,
+};
+
+// a batch of a preordered item arrived at the shop, its stock goes to the oldest preorders and
+// their buyers are told
+#[post("/items/{item_id}/preorder-arrivals")]
+pub async fn create_preorder_arrival(
+    data: web::Data<AppState>,
+    item_id: web::Path<Uuid>,
+    request: web::Json<RequestType<CreatablePreorderArrival, QueryableItem, SortableItem>>,
+    shop_role: RequireShopRole<Staff>,
+) -> Result<impl Responder, actix_web::Error> {
+    let pool = &data.db;
+    let credential = &data.smtp_credential;
+    let item_id = item_id.into_inner();
+    let source = format!("/items/{item_id}/preorder-arrivals");
+
+    let arrival = match &request.data {
+        Some(data) => data,
+        None => {
+            let response: ErrorResponseType = ErrorResponseType::new(
+                ErrorType {
+                    id: Uuid::new_v4().to_string(),
+                    code: 400,
+                    error_type: "bad_request".to_string(),
+                    detail: "request body is empty".to_string(),
+                    source,
+                },
+                Some(MetadataType::new(None::<PaginationType>)),
+            );
+
+            return Ok(HttpResponse::BadRequest().json(response));
+        }
+    };
+
+    if let Err(e) = arrival.validate() {
+        let response: ErrorResponseType = ErrorResponseType::new(
+            ErrorType {
+                id: Uuid::new_v4().to_string(),
+                code: 400,
+                error_type: "bad_request".to_string(),
+                detail: e,
+                source,
+            },
+            Some(MetadataType::new(None::<PaginationType>)),
+        );
+
+        return Ok(HttpResponse::BadRequest().json(response));
+    }
+
+    let res = arrival
+        .insert(pool, item_id, shop_role.permission.user_id)
+        .await;
+
+    let outcome = match res {
+        Ok(outcome) => outcome,
+        Err(StockUpdateError::Database(e)) => {
+            let response: ErrorResponseType = ErrorResponseType::new(
+                ErrorType {
+                    id: Uuid::new_v4().to_string(),
+                    code: 500,
+                    error_type: "internal_server_error".to_string(),
+                    detail: e.to_string(),
+                    source,
+                },
+                None::<MetadataType>,
+            );
+
+            return Ok(HttpResponse::InternalServerError().json(response));
+        }
+        Err(e) => {
+            let response: ErrorResponseType = ErrorResponseType::new(
+                ErrorType {
+                    id: Uuid::new_v4().to_string(),
+                    code: 400,
+                    error_type: "bad_request".to_string(),
+                    detail: e.to_string(),
+                    source,
+                },
+                None::<MetadataType>,
+            );
+
+            return Ok(HttpResponse::BadRequest().json(response));
+        }
+    };
+
+    if let Ok(item) = ItemTable::get_by_id(pool, item_id).await {
+        let item_name = match item.variant_name {
+            Some(variant_name) => format!("{} ({})", item.name, variant_name),
+            None => item.name,
+        };
+
+        for order_id in &outcome.fulfilled_order_ids {
+            let order = Order::get_by_id(
+                pool,
+                *order_id,
+                Some(&FetchLevel::Default),
+                Some(&FetchLevel::Compact),
+            )
+            .await;
+
+            if let Ok(order) = order {
+                if let Err(e) = send_preorder_arrived_email(credential, order, &item_name) {
+                    println!("Error: {}", e);
+                }
+            }
+        }
+    }
+
+    Ok(HttpResponse::Ok().json(ResponseType::new(
+        outcome,
+        Some(MetadataType::new(None::<PaginationType>)),
+    )))
+}
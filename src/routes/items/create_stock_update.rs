@@ -0,0 +1,139 @@
+use actix_web::{post, web, HttpResponse, Responder};
+use mysk_lib::models::common::{
+    requests::{FetchLevel, RequestType},
+    response::{ErrorResponseType, ErrorType, MetadataType, PaginationType, ResponseType},
+};
+use uuid::Uuid;
+
+use crate::{
+    models::{
+        auth::permission::{RequireShopRole, Staff},
+        item::{
+            request::{QueryableItem, SortableItem},
+            stock::{CreatableStockUpdate, StockUpdateError},
+            Item,
+        },
+    },
+    AppState,
+};
+
+// restocks, write offs and count corrections, answered with the item and its new stock
+#[post("/items/{item_id}/stock-updates")]
+pub async fn create_stock_update(
+    data: web::Data<AppState>,
+    item_id: web::Path<Uuid>,
+    request: web::Json<RequestType<CreatableStockUpdate, QueryableItem, SortableItem>>,
+    shop_role: RequireShopRole<Staff>,
+) -> Result<impl Responder, actix_web::Error> {
+    let pool = &data.db;
+    let item_id = item_id.into_inner();
+    let source = format!("/items/{item_id}/stock-updates");
+
+    let stock_update = match &request.data {
+        Some(data) => data,
+        None => {
+            let response: ErrorResponseType = ErrorResponseType::new(
+                ErrorType {
+                    id: Uuid::new_v4().to_string(),
+                    code: 400,
+                    error_type: "bad_request".to_string(),
+                    detail: "request body is empty".to_string(),
+                    source,
+                },
+                Some(MetadataType::new(None::<PaginationType>)),
+            );
+
+            return Ok(HttpResponse::BadRequest().json(response));
+        }
+    };
+
+    if let Err(e) = stock_update.validate() {
+        let response: ErrorResponseType = ErrorResponseType::new(
+            ErrorType {
+                id: Uuid::new_v4().to_string(),
+                code: 400,
+                error_type: "bad_request".to_string(),
+                detail: e,
+                source,
+            },
+            Some(MetadataType::new(None::<PaginationType>)),
+        );
+
+        return Ok(HttpResponse::BadRequest().json(response));
+    }
+
+    let res = stock_update
+        .insert(pool, item_id, shop_role.permission.user_id)
+        .await;
+
+    match res {
+        Ok(_) => (),
+        Err(StockUpdateError::Database(e)) => {
+            let response: ErrorResponseType = ErrorResponseType::new(
+                ErrorType {
+                    id: Uuid::new_v4().to_string(),
+                    code: 500,
+                    error_type: "internal_server_error".to_string(),
+                    detail: e.to_string(),
+                    source,
+                },
+                None::<MetadataType>,
+            );
+
+            return Ok(HttpResponse::InternalServerError().json(response));
+        }
+        Err(e) => {
+            let response: ErrorResponseType = ErrorResponseType::new(
+                ErrorType {
+                    id: Uuid::new_v4().to_string(),
+                    code: 400,
+                    error_type: "bad_request".to_string(),
+                    detail: e.to_string(),
+                    source,
+                },
+                None::<MetadataType>,
+            );
+
+            return Ok(HttpResponse::BadRequest().json(response));
+        }
+    }
+
+    let fetch_level = match request.fetch_level.clone() {
+        Some(fetch_level) => fetch_level,
+        None => FetchLevel::Default,
+    };
+
+    let descendant_fetch_level = match request.descendant_fetch_level.clone() {
+        Some(descendant_fetch_level) => descendant_fetch_level,
+        None => FetchLevel::IdOnly,
+    };
+
+    let item = Item::get_by_id(
+        pool,
+        item_id,
+        Some(&fetch_level),
+        Some(&descendant_fetch_level),
+    )
+    .await;
+
+    match item {
+        Ok(item) => Ok(HttpResponse::Ok().json(ResponseType::new(
+            item,
+            Some(MetadataType::new(None::<PaginationType>)),
+        ))),
+        Err(e) => {
+            let response: ErrorResponseType = ErrorResponseType::new(
+                ErrorType {
+                    id: Uuid::new_v4().to_string(),
+                    code: 500,
+                    error_type: "internal_server_error".to_string(),
+                    detail: e.to_string(),
+                    source,
+                },
+                None::<MetadataType>,
+            );
+
+            Ok(HttpResponse::InternalServerError().json(response))
+        }
+    }
+}
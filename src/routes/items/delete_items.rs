@@ -3,12 +3,14 @@ use mysk_lib::models::common::{
     requests::RequestType,
     response::{ErrorResponseType, ErrorType, MetadataType, PaginationType, ResponseType},
 };
-use sqlx::Row;
 use uuid::Uuid;
 
 use crate::{
     models::{
-        auth::user::User,
+        auth::{
+            permission::{authorize, Role, ShopResource},
+            user::User,
+        },
         item::{
             db::ItemTable,
             request::{QueryableItem, SortableItem},
@@ -43,40 +45,17 @@ pub async fn delete_items(
         }
     };
 
-    let user_id = match user {
-        User::IdOnly(user) => user.id,
-        User::Compact(user) => user.id,
-        User::Default(user) => user.id,
-        User::Detailed(user) => user.id,
-    };
+    let user_id = user.id();
 
     for item_id in data {
-        let res = sqlx::query(
-            r#"
-            SELECT COUNT(id) FROM shop_managers WHERE user_id = $1 AND shop_id IN (SELECT shop_id FROM listings WHERE id IN (SELECT listing_id FROM items WHERE id = $2)))
-            "#,
+        authorize(
+            pool,
+            user_id,
+            ShopResource::Item(*item_id),
+            Role::ShopStaff,
+            "/items",
         )
-        .bind(user_id)
-        .bind(item_id)
-        .fetch_one(pool)
-        .await;
-
-        if let Ok(res) = res {
-            if res.get::<Option<i64>, _>("count").unwrap_or(0) == 0 {
-                let response: ErrorResponseType = ErrorResponseType::new(
-                    ErrorType {
-                        id: Uuid::new_v4().to_string(),
-                        code: 403,
-                        error_type: "forbidden".to_string(),
-                        detail: format!("user {} is not a manager of shop {}", user_id, item_id),
-                        source: format!("/items"),
-                    },
-                    None::<MetadataType>,
-                );
-
-                return Ok(HttpResponse::Forbidden().json(response));
-            }
-        }
+        .await?;
     }
 
     let res = ItemTable::bulk_delete(pool, data.to_vec()).await;
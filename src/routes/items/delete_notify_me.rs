@@ -0,0 +1,51 @@
+use actix_web::{delete, web, HttpResponse, Responder};
+use mysk_lib::models::common::response::{ErrorResponseType, ErrorType, MetadataType};
+use uuid::Uuid;
+
+use crate::{
+    models::{auth::user::User, item::waitlist::WaitlistSubscription},
+    AppState,
+};
+
+#[delete("/items/{item_id}/notify-me")]
+pub async fn delete_notify_me(
+    data: web::Data<AppState>,
+    item_id: web::Path<Uuid>,
+    user: User,
+) -> Result<impl Responder, actix_web::Error> {
+    let pool = &data.db;
+    let item_id = item_id.into_inner();
+    let source = format!("/items/{item_id}/notify-me");
+
+    match WaitlistSubscription::unsubscribe(pool, item_id, user.id()).await {
+        Ok(true) => Ok(HttpResponse::NoContent().finish()),
+        Ok(false) => {
+            let response: ErrorResponseType = ErrorResponseType::new(
+                ErrorType {
+                    id: Uuid::new_v4().to_string(),
+                    code: 404,
+                    error_type: "not_found".to_string(),
+                    detail: "not on the waitlist of this item".to_string(),
+                    source,
+                },
+                None::<MetadataType>,
+            );
+
+            Ok(HttpResponse::NotFound().json(response))
+        }
+        Err(e) => {
+            let response: ErrorResponseType = ErrorResponseType::new(
+                ErrorType {
+                    id: Uuid::new_v4().to_string(),
+                    code: 500,
+                    error_type: "internal_server_error".to_string(),
+                    detail: e.to_string(),
+                    source,
+                },
+                None::<MetadataType>,
+            );
+
+            Ok(HttpResponse::InternalServerError().json(response))
+        }
+    }
+}
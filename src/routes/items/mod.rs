@@ -1,6 +1,12 @@
 pub(crate) mod add_to_cart;
 pub(crate) mod create_items;
+pub(crate) mod create_preorder_arrival;
+pub(crate) mod create_stock_update;
 pub(crate) mod delete_items;
+pub(crate) mod delete_notify_me;
 pub(crate) mod item_detail;
+pub(crate) mod notify_me;
 pub(crate) mod query_items;
+pub(crate) mod query_stock_updates;
+pub(crate) mod unsubscribe_waitlist;
 pub(crate) mod update_item_by_id;
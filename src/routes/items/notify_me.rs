@@ -0,0 +1,58 @@
+use actix_web::{post, web, HttpResponse, Responder};
+use mysk_lib::models::common::response::{
+    ErrorResponseType, ErrorType, MetadataType, PaginationType, ResponseType,
+};
+use uuid::Uuid;
+
+use crate::{
+    models::{auth::user::User, item::waitlist::WaitlistSubscription},
+    AppState,
+};
+
+// puts the signed in user on the item's waitlist, they are emailed every time it comes back in
+// stock until they unsubscribe. Subscribing again keeps the existing subscription
+#[post("/items/{item_id}/notify-me")]
+pub async fn notify_me(
+    data: web::Data<AppState>,
+    item_id: web::Path<Uuid>,
+    user: User,
+) -> Result<impl Responder, actix_web::Error> {
+    let pool = &data.db;
+    let item_id = item_id.into_inner();
+    let source = format!("/items/{item_id}/notify-me");
+
+    match WaitlistSubscription::subscribe(pool, item_id, user.id()).await {
+        Ok(subscription) => Ok(HttpResponse::Ok().json(ResponseType::new(
+            subscription,
+            Some(MetadataType::new(None::<PaginationType>)),
+        ))),
+        Err(sqlx::Error::RowNotFound) => {
+            let response: ErrorResponseType = ErrorResponseType::new(
+                ErrorType {
+                    id: Uuid::new_v4().to_string(),
+                    code: 404,
+                    error_type: "not_found".to_string(),
+                    detail: "item not found".to_string(),
+                    source,
+                },
+                None::<MetadataType>,
+            );
+
+            Ok(HttpResponse::NotFound().json(response))
+        }
+        Err(e) => {
+            let response: ErrorResponseType = ErrorResponseType::new(
+                ErrorType {
+                    id: Uuid::new_v4().to_string(),
+                    code: 500,
+                    error_type: "internal_server_error".to_string(),
+                    detail: e.to_string(),
+                    source,
+                },
+                None::<MetadataType>,
+            );
+
+            Ok(HttpResponse::InternalServerError().json(response))
+        }
+    }
+}
@@ -0,0 +1,46 @@
+use actix_web::{get, web, HttpResponse, Responder};
+use mysk_lib::models::common::response::{
+    ErrorResponseType, ErrorType, MetadataType, PaginationType, ResponseType,
+};
+use uuid::Uuid;
+
+use crate::{
+    models::{
+        auth::permission::{RequireShopRole, Staff},
+        item::stock::StockUpdate,
+    },
+    AppState,
+};
+
+// the stock ledger of an item, newest first
+#[get("/items/{item_id}/stock-updates")]
+pub async fn query_stock_updates(
+    data: web::Data<AppState>,
+    item_id: web::Path<Uuid>,
+    _permission: RequireShopRole<Staff>,
+) -> Result<impl Responder, actix_web::Error> {
+    let pool = &data.db;
+    let item_id = item_id.into_inner();
+    let source = format!("/items/{item_id}/stock-updates");
+
+    match StockUpdate::get_by_item_id(pool, item_id).await {
+        Ok(stock_updates) => Ok(HttpResponse::Ok().json(ResponseType::new(
+            stock_updates,
+            Some(MetadataType::new(None::<PaginationType>)),
+        ))),
+        Err(e) => {
+            let response: ErrorResponseType = ErrorResponseType::new(
+                ErrorType {
+                    id: Uuid::new_v4().to_string(),
+                    code: 500,
+                    error_type: "internal_server_error".to_string(),
+                    detail: e.to_string(),
+                    source,
+                },
+                None::<MetadataType>,
+            );
+
+            Ok(HttpResponse::InternalServerError().json(response))
+        }
+    }
+}
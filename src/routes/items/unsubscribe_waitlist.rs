@@ -0,0 +1,35 @@
+use actix_web::{get, web, HttpResponse, Responder};
+use serde::Deserialize;
+use uuid::Uuid;
+
+use crate::{models::item::waitlist::WaitlistSubscription, AppState};
+
+#[derive(Debug, Deserialize)]
+pub struct UnsubscribeQuery {
+    token: Uuid,
+}
+
+// opened from the link in a back in stock email, so it answers with a page instead of json
+#[get("/waitlist/unsubscribe")]
+pub async fn unsubscribe_waitlist(
+    data: web::Data<AppState>,
+    query: web::Query<UnsubscribeQuery>,
+) -> Result<impl Responder, actix_web::Error> {
+    let pool = &data.db;
+
+    let message = match WaitlistSubscription::unsubscribe_by_token(pool, query.token).await {
+        Ok(Some(_)) => "You will no longer be emailed when this item is back in stock.",
+        Ok(None) => "You are already unsubscribed.",
+        Err(e) => {
+            println!("Error: {}", e);
+
+            return Ok(HttpResponse::InternalServerError()
+                .content_type("text/html; charset=utf-8")
+                .body("<p>Something went wrong, please try the link again later.</p>"));
+        }
+    };
+
+    Ok(HttpResponse::Ok()
+        .content_type("text/html; charset=utf-8")
+        .body(format!("<p>{}</p>", message)))
+}
@@ -3,12 +3,11 @@ use mysk_lib::models::common::{
     requests::{FetchLevel, RequestType},
     response::{ErrorResponseType, ErrorType, MetadataType, PaginationType, ResponseType},
 };
-use sqlx::Row;
 use uuid::Uuid;
 
 use crate::{
     models::{
-        auth::user::User,
+        auth::permission::{RequireShopRole, Staff},
         item::{
             request::{QueryableItem, SortableItem, UpdatableItem},
             Item,
@@ -22,7 +21,7 @@ pub async fn update_item_by_id(
     data: web::Data<AppState>,
     item_id: web::Path<Uuid>,
     request: web::Json<RequestType<UpdatableItem, QueryableItem, SortableItem>>,
-    user: User,
+    _permission: RequireShopRole<Staff>,
 ) -> Result<impl Responder, actix_web::Error> {
     let pool: &sqlx::Pool<sqlx::Postgres> = &data.db;
     let item_id = item_id.into_inner();
@@ -45,40 +44,6 @@ pub async fn update_item_by_id(
         }
     };
 
-    let user_id = match user {
-        User::IdOnly(user) => user.id,
-        User::Compact(user) => user.id,
-        User::Default(user) => user.id,
-        User::Detailed(user) => user.id,
-    };
-
-    let res = sqlx::query(
-        r#"
-        SELECT COUNT(id) FROM shop_managers WHERE user_id = $1 AND shop_id IN (SELECT shop_id FROM listings WHERE id IN (SELECT listing_id FROM items WHERE id = $2)))
-        "#,
-    )
-    .bind(user_id)
-    .bind(item_id)
-    .fetch_one(pool)
-    .await;
-
-    if let Ok(res) = res {
-        if res.get::<Option<i64>, _>("count").unwrap_or(0) == 0 {
-            let response: ErrorResponseType = ErrorResponseType::new(
-                ErrorType {
-                    id: Uuid::new_v4().to_string(),
-                    code: 403,
-                    error_type: "forbidden".to_string(),
-                    detail: format!("user {} is not a manager of shop {}", user_id, item_id),
-                    source: format!("/items/{item_id}"),
-                },
-                None::<MetadataType>,
-            );
-
-            return Ok(HttpResponse::Forbidden().json(response));
-        }
-    }
-
     let res = data.commit_changes(pool, item_id).await;
 
     if res.is_err() {
@@ -3,12 +3,11 @@ use mysk_lib::models::common::{
     requests::{FetchLevel, RequestType},
     response::{ErrorResponseType, ErrorType, MetadataType, PaginationType, ResponseType},
 };
-use sqlx::Row;
 use uuid::Uuid;
 
 use crate::{
     models::{
-        auth::user::User,
+        auth::permission::{RequireShopRole, Staff},
         listing::{
             request::{QueryableListing, SortableListing, UpdatableListing},
             Listing,
@@ -22,7 +21,7 @@ pub async fn update_listing_by_id(
     data: web::Data<AppState>,
     listing_id: web::Path<Uuid>,
     request: web::Json<RequestType<UpdatableListing, QueryableListing, SortableListing>>,
-    user: User,
+    _permission: RequireShopRole<Staff>,
 ) -> Result<impl Responder, actix_web::Error> {
     let pool = &data.db;
     let listing_id = listing_id.into_inner();
@@ -45,40 +44,6 @@ pub async fn update_listing_by_id(
         }
     };
 
-    let user_id = match user {
-        User::IdOnly(user) => user.id,
-        User::Compact(user) => user.id,
-        User::Default(user) => user.id,
-        User::Detailed(user) => user.id,
-    };
-
-    let res = sqlx::query(
-        r#"
-        SELECT COUNT(id) FROM shop_managers INNER JOIN listings ON listings.shop_id = shop_managers.shop_id WHERE user_id = $1 AND listings.id = $2
-        "#,
-    )
-    .bind(user_id)
-    .bind(listing_id)
-    .fetch_one(pool)
-    .await;
-
-    if let Ok(res) = res {
-        if res.get::<Option<i64>, _>("count").unwrap_or(0) == 0 {
-            let response: ErrorResponseType = ErrorResponseType::new(
-                ErrorType {
-                    id: Uuid::new_v4().to_string(),
-                    code: 403,
-                    error_type: "forbidden".to_string(),
-                    detail: format!("user {} is not a manager of shop {}", user_id, listing_id),
-                    source: format!("/collections"),
-                },
-                None::<MetadataType>,
-            );
-
-            return Ok(HttpResponse::Forbidden().json(response));
-        }
-    }
-
     let res = data.commit_changes(pool, listing_id).await;
 
     if res.is_err() {
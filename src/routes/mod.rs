@@ -23,6 +23,11 @@ pub fn config(cfg: &mut web::ServiceConfig) {
     cfg.service(auth::create_user_addresses::create_user_addresses);
     cfg.service(auth::delete_user_addresses::delete_user_addresses);
     cfg.service(auth::get_user_cart_items::get_user_cart_items);
+    cfg.service(auth::checkout_user_cart::checkout_user_cart);
+    cfg.service(auth::update_user_cart_item::update_user_cart_item);
+    cfg.service(auth::delete_user_cart_item::delete_user_cart_item);
+    cfg.service(auth::clear_user_cart::clear_user_cart);
+    cfg.service(auth::get_guest_cart_items::get_guest_cart_items);
 
     cfg.service(items::item_detail::item_detail);
     cfg.service(items::query_items::query_items);
@@ -30,6 +35,12 @@ pub fn config(cfg: &mut web::ServiceConfig) {
     cfg.service(items::create_items::create_items);
     cfg.service(items::delete_items::delete_items);
     cfg.service(items::update_item_by_id::update_item_by_id);
+    cfg.service(items::create_stock_update::create_stock_update);
+    cfg.service(items::query_stock_updates::query_stock_updates);
+    cfg.service(items::create_preorder_arrival::create_preorder_arrival);
+    cfg.service(items::notify_me::notify_me);
+    cfg.service(items::delete_notify_me::delete_notify_me);
+    cfg.service(items::unsubscribe_waitlist::unsubscribe_waitlist);
 
     cfg.service(listings::listing_detail::listing_detail);
     cfg.service(listings::query_listings::query_listings);
@@ -47,13 +58,36 @@ pub fn config(cfg: &mut web::ServiceConfig) {
     cfg.service(shops::shop_detail::shop_detail);
     cfg.service(shops::query_shops::query_shops);
     cfg.service(shops::update_shop_by_id::update_shop_by_id);
+    cfg.service(shops::create_shops::create_shops);
+    cfg.service(shops::query_shop_managers::query_shop_managers);
+    cfg.service(shops::create_shop_managers::create_shop_managers);
+    cfg.service(shops::delete_shop_manager::delete_shop_manager);
+    cfg.service(shops::query_discount_codes::query_discount_codes);
+    cfg.service(shops::create_discount_codes::create_discount_codes);
+    cfg.service(shops::update_discount_code::update_discount_code);
+    cfg.service(shops::query_payment_slips::query_payment_slips);
+    cfg.service(shops::review_payment_slip::review_payment_slip);
+    cfg.service(shops::lookup_pos_item::lookup_pos_item);
+    cfg.service(shops::create_pos_sale::create_pos_sale);
+    cfg.service(shops::query_cash_drawer::query_cash_drawer);
+    cfg.service(shops::scan_pickup::scan_pickup);
+    cfg.service(shops::import_tracking::import_tracking);
+    cfg.service(shops::query_low_stock_items::query_low_stock_items);
 
     cfg.service(orders::order_detail::order_detail);
+    cfg.service(orders::promptpay_qr::promptpay_qr);
+    cfg.service(orders::pickup_qr::pickup_qr);
     cfg.service(orders::query_orders::query_orders);
+    cfg.service(orders::quote_shipping::quote_shipping);
     cfg.service(orders::create_orders::create_orders);
     cfg.service(orders::update_order_by_id::update_order_by_id);
     cfg.service(orders::upload_slip_payment::upload_slip_payment);
     cfg.service(orders::order_confirm_webhook::update_order_webhook);
+    cfg.service(orders::simulate_payment::simulate_payment);
+    cfg.service(orders::create_refund::create_refund);
+    cfg.service(orders::query_refunds::query_refunds);
+    cfg.service(orders::collect_cash::collect_cash);
+    cfg.service(orders::checkout_session_detail::checkout_session_detail);
 
     cfg.service(category::all_categories::all_categories);
     // cfg.service(
@@ -0,0 +1,55 @@
+use actix_web::{get, web, HttpResponse, Responder};
+use mysk_lib::models::common::response::{
+    ErrorResponseType, ErrorType, MetadataType, PaginationType, ResponseType,
+};
+use uuid::Uuid;
+
+use crate::{
+    models::{
+        auth::{permission::authorize_order_access, user::OptionalUser},
+        order::checkout::CheckoutSession,
+    },
+    AppState,
+};
+
+// the combined payment of orders checked out together
+#[get("/checkout-sessions/{session_id}")]
+pub async fn checkout_session_detail(
+    data: web::Data<AppState>,
+    session_id: web::Path<Uuid>,
+    user: OptionalUser,
+) -> Result<impl Responder, actix_web::Error> {
+    let pool = &data.db;
+    let session_id = session_id.into_inner();
+    let source = format!("/checkout-sessions/{session_id}");
+
+    let session = match CheckoutSession::get_by_id(pool, session_id).await {
+        Ok(session) => session,
+        Err(e) => {
+            let response: ErrorResponseType = ErrorResponseType::new(
+                ErrorType {
+                    id: Uuid::new_v4().to_string(),
+                    code: 404,
+                    error_type: "entity_not_found".to_string(),
+                    detail: e.to_string(),
+                    source,
+                },
+                None::<MetadataType>,
+            );
+
+            return Ok(HttpResponse::NotFound().json(response));
+        }
+    };
+
+    // every order of a session has the same buyer, so any of them decides who may see it
+    if let Some(order_id) = session.order_ids.first() {
+        let user_id = user.0.map(|user| user.id());
+
+        authorize_order_access(pool, user_id, *order_id, &source).await?;
+    }
+
+    Ok(HttpResponse::Ok().json(ResponseType::new(
+        session,
+        Some(MetadataType::new(None::<PaginationType>)),
+    )))
+}
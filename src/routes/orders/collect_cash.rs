@@ -0,0 +1,141 @@
+use actix_web::{post, web, HttpResponse, Responder};
+use mysk_lib::models::common::{
+    requests::{FetchLevel, RequestType},
+    response::{ErrorResponseType, ErrorType, MetadataType, PaginationType, ResponseType},
+};
+use serde::Deserialize;
+use uuid::Uuid;
+
+use crate::{
+    models::{
+        auth::permission::{RequireShopRole, Staff},
+        order::{
+            cash::{collect_cash as collect_order_cash, CashCollectionError},
+            request::{QueryableOrder, SortableOrder},
+            status::OrderStatusError,
+            Order,
+        },
+    },
+    utils::email::send_receipt_email,
+    AppState,
+};
+
+#[derive(Debug, Deserialize)]
+pub struct CashCollection {
+    // checked against the order total when given
+    amount_received: Option<i64>,
+    note: Option<String>,
+}
+
+// marks the cash of a cash on delivery order as collected when it is handed over
+#[post("/orders/{order_id}/cash-collected")]
+pub async fn collect_cash(
+    data: web::Data<AppState>,
+    order_id: web::Path<Uuid>,
+    request: web::Json<RequestType<CashCollection, QueryableOrder, SortableOrder>>,
+    shop_role: RequireShopRole<Staff>,
+) -> Result<impl Responder, actix_web::Error> {
+    let pool = &data.db;
+    let credential = &data.smtp_credential;
+    let order_id = order_id.into_inner();
+    let source = format!("/orders/{order_id}/cash-collected");
+
+    let (amount_received, note) = match &request.data {
+        Some(data) => (data.amount_received, data.note.clone()),
+        None => (None, None),
+    };
+
+    let res = collect_order_cash(
+        pool,
+        order_id,
+        shop_role.permission.user_id,
+        amount_received,
+        note,
+    )
+    .await;
+
+    match res {
+        Ok(()) => (),
+        Err(CashCollectionError::Status(OrderStatusError::Database(e))) => {
+            let response: ErrorResponseType = ErrorResponseType::new(
+                ErrorType {
+                    id: Uuid::new_v4().to_string(),
+                    code: 500,
+                    error_type: "internal_server_error".to_string(),
+                    detail: e.to_string(),
+                    source,
+                },
+                None::<MetadataType>,
+            );
+
+            return Ok(HttpResponse::InternalServerError().json(response));
+        }
+        Err(e) => {
+            let response: ErrorResponseType = ErrorResponseType::new(
+                ErrorType {
+                    id: Uuid::new_v4().to_string(),
+                    code: 400,
+                    error_type: "bad_request".to_string(),
+                    detail: e.to_string(),
+                    source,
+                },
+                None::<MetadataType>,
+            );
+
+            return Ok(HttpResponse::BadRequest().json(response));
+        }
+    }
+
+    let order = Order::get_by_id(
+        pool,
+        order_id,
+        Some(&FetchLevel::Default),
+        Some(&FetchLevel::Compact),
+    )
+    .await;
+
+    if let Ok(order) = order {
+        if let Err(e) = send_receipt_email(credential, order) {
+            println!("Error: {}", e);
+        }
+    }
+
+    let fetch_level = match request.fetch_level.clone() {
+        Some(fetch_level) => fetch_level,
+        None => FetchLevel::Default,
+    };
+
+    let descendant_fetch_level = match request.descendant_fetch_level.clone() {
+        Some(descendant_fetch_level) => descendant_fetch_level,
+        None => FetchLevel::IdOnly,
+    };
+
+    let order = Order::get_by_id(
+        pool,
+        order_id,
+        Some(&fetch_level),
+        Some(&descendant_fetch_level),
+    )
+    .await;
+
+    match order {
+        Ok(order) => Ok(HttpResponse::Ok().json(ResponseType::new(
+            order,
+            Some(MetadataType::new(None::<PaginationType>)),
+        ))),
+        Err(e) => {
+            let response: ErrorResponseType = ErrorResponseType::new(
+                ErrorType {
+                    id: Uuid::new_v4().to_string(),
+                    code: 500,
+                    error_type: "internal_server_error".to_string(),
+                    detail: e.to_string(),
+                    source,
+                },
+                None::<MetadataType>,
+            );
+
+            Ok(HttpResponse::InternalServerError().json(response))
+        }
+    }
+}
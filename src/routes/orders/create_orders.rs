@@ -9,16 +9,17 @@ use crate::{
     models::{
         auth::user::{OptionalUser, User},
         order::{
+            checkout::checkout,
             db::DeliveryType,
-            request::{CreatableOrder, QueryableOrder, SortableOrder},
+            request::{CreatableOrder, OrderCreationError, QueryableOrder, SortableOrder},
             Order,
         },
     },
-    utils::email::send_invoice_email,
+    utils::{email::send_invoice_email, idempotency::Idempotency},
     AppState,
 };
 
-#[post("/orders")]
+#[post("/orders", wrap = "Idempotency")]
 pub async fn create_orders(
     data: web::Data<AppState>,
     request: web::Json<RequestType<Vec<CreatableOrder>, QueryableOrder, SortableOrder>>,
@@ -26,7 +27,8 @@ pub async fn create_orders(
 ) -> Result<impl Responder, actix_web::Error> {
     let pool = &data.db;
     let credential = &data.smtp_credential;
-    let gb_token = &data.env.gbprimepay_token;
+    let payment_provider = &data.payment_provider;
+    let stock_hold = chrono::Duration::minutes(data.env.stock_hold_minutes);
 
     let data = match &request.data {
         Some(data) => data,
@@ -65,39 +67,41 @@ pub async fn create_orders(
 
     // let item_ids: Result<!, _> = CreatableItem::bulk_insert(data.to_vec(), pool).await;
 
-    let mut order_ids = Vec::new();
-
     for order in data {
         // make sure that contact email is valid
-        let order = match order.validate() {
-            Ok(order) => order,
-            Err(err) => {
-                let response: ErrorResponseType = ErrorResponseType::new(
-                    ErrorType {
-                        id: Uuid::new_v4().to_string(),
-                        code: 400,
-                        error_type: "bad_request".to_string(),
-                        detail: err,
-                        source: "/orders".to_string(),
-                    },
-                    Some(MetadataType::new(None::<PaginationType>)),
-                );
-
-                return Ok(HttpResponse::BadRequest().json(response));
-            }
-        };
-
-        let order_id = order
-            .insert(pool, Some(gb_token.to_string()), user_id)
-            .await;
+        if let Err(err) = order.validate() {
+            let response: ErrorResponseType = ErrorResponseType::new(
+                ErrorType {
+                    id: Uuid::new_v4().to_string(),
+                    code: 400,
+                    error_type: "bad_request".to_string(),
+                    detail: err,
+                    source: "/orders".to_string(),
+                },
+                Some(MetadataType::new(None::<PaginationType>)),
+            );
 
-        order_ids.push(order_id);
+            return Ok(HttpResponse::BadRequest().json(response));
+        }
     }
 
-    let order_ids: Result<Vec<Uuid>, _> = order_ids.into_iter().collect();
-
-    let order_ids = match order_ids {
+    // all of the orders are placed or none of them are
+    let order_ids = match checkout(pool, payment_provider, data, user_id, stock_hold).await {
         Ok(order_ids) => order_ids,
+        Err(OrderCreationError::Database(err)) => {
+            let response: ErrorResponseType = ErrorResponseType::new(
+                ErrorType {
+                    id: Uuid::new_v4().to_string(),
+                    code: 500,
+                    error_type: "internal_server_error".to_string(),
+                    detail: err.to_string(),
+                    source: "/orders".to_string(),
+                },
+                Some(MetadataType::new(None::<PaginationType>)),
+            );
+
+            return Ok(HttpResponse::InternalServerError().json(response));
+        }
         Err(err) => {
             let response: ErrorResponseType = ErrorResponseType::new(
                 ErrorType {
@@ -105,7 +109,7 @@ pub async fn create_orders(
                     code: 400,
                     error_type: "bad_request".to_string(),
                     detail: err.to_string(),
-                    source: format!("/orders"),
+                    source: "/orders".to_string(),
                 },
                 Some(MetadataType::new(None::<PaginationType>)),
             );
@@ -134,38 +138,15 @@ pub async fn create_orders(
         .await;
 
         let order = match order {
+            Ok(order) => order,
             Err(err) => {
-                let response: ErrorResponseType = ErrorResponseType::new(
-                    ErrorType {
-                        id: Uuid::new_v4().to_string(),
-                        code: 500,
-                        error_type: "internal_server_error".to_string(),
-                        detail: err.to_string(),
-                        source: format!("/orders"),
-                    },
-                    Some(MetadataType::new(None::<PaginationType>)),
-                );
-
-                return Ok(HttpResponse::InternalServerError().json(response));
+                println!("Error: {}", err);
+                continue;
             }
-            Ok(order) => order,
         };
 
-        let res = send_invoice_email(credential, order);
-
-        if let Err(err) = res {
-            let response: ErrorResponseType = ErrorResponseType::new(
-                ErrorType {
-                    id: Uuid::new_v4().to_string(),
-                    code: 500,
-                    error_type: "internal_server_error".to_string(),
-                    detail: err.to_string(),
-                    source: format!("/orders"),
-                },
-                Some(MetadataType::new(None::<PaginationType>)),
-            );
-
-            return Ok(HttpResponse::InternalServerError().json(response));
+        if let Err(err) = send_invoice_email(credential, order) {
+            println!("Error: {}", err);
         }
     }
 
@@ -0,0 +1,178 @@
+use actix_web::{post, web, HttpResponse, Responder};
+use mysk_lib::models::common::{
+    requests::{FetchLevel, RequestType},
+    response::{ErrorResponseType, ErrorType, MetadataType, PaginationType, ResponseType},
+};
+use uuid::Uuid;
+
+use crate::{
+    models::{
+        auth::permission::{RequireShopRole, Staff},
+        order::{
+            refund::{CreatableRefund, RefundError, RefundOutcome},
+            request::{QueryableOrder, SortableOrder},
+            status::OrderStatusError,
+            Order,
+        },
+    },
+    utils::email::send_refund_email,
+    AppState,
+};
+
+#[post("/orders/{order_id}/refunds")]
+pub async fn create_refund(
+    data: web::Data<AppState>,
+    order_id: web::Path<Uuid>,
+    request: web::Json<RequestType<CreatableRefund, QueryableOrder, SortableOrder>>,
+    shop_role: RequireShopRole<Staff>,
+) -> Result<impl Responder, actix_web::Error> {
+    let pool = &data.db;
+    let credential = &data.smtp_credential;
+    let payment_provider = &data.payment_provider;
+    let order_id = order_id.into_inner();
+    let source = format!("/orders/{order_id}/refunds");
+
+    let refund = match &request.data {
+        Some(data) => data,
+        None => {
+            let response: ErrorResponseType = ErrorResponseType::new(
+                ErrorType {
+                    id: Uuid::new_v4().to_string(),
+                    code: 400,
+                    error_type: "bad_request".to_string(),
+                    detail: "request body is empty".to_string(),
+                    source,
+                },
+                Some(MetadataType::new(None::<PaginationType>)),
+            );
+
+            return Ok(HttpResponse::BadRequest().json(response));
+        }
+    };
+
+    if let Err(e) = refund.validate() {
+        let response: ErrorResponseType = ErrorResponseType::new(
+            ErrorType {
+                id: Uuid::new_v4().to_string(),
+                code: 400,
+                error_type: "bad_request".to_string(),
+                detail: e,
+                source,
+            },
+            Some(MetadataType::new(None::<PaginationType>)),
+        );
+
+        return Ok(HttpResponse::BadRequest().json(response));
+    }
+
+    let res = refund
+        .insert(pool, payment_provider, order_id, shop_role.permission.user_id)
+        .await;
+
+    let refunded_amount = match res {
+        Ok(RefundOutcome::Canceled) => None,
+        Ok(RefundOutcome::Refunded { amount }) => Some(amount),
+        // a declined refund is marked failed, one the provider didn't answer stays pending and is
+        // retried by the next refund of this order
+        Err(RefundError::Provider(e)) => {
+            let response: ErrorResponseType = ErrorResponseType::new(
+                ErrorType {
+                    id: Uuid::new_v4().to_string(),
+                    code: 502,
+                    error_type: "bad_gateway".to_string(),
+                    detail: e.to_string(),
+                    source,
+                },
+                None::<MetadataType>,
+            );
+
+            return Ok(HttpResponse::BadGateway().json(response));
+        }
+        Err(RefundError::Status(OrderStatusError::Database(e))) => {
+            let response: ErrorResponseType = ErrorResponseType::new(
+                ErrorType {
+                    id: Uuid::new_v4().to_string(),
+                    code: 500,
+                    error_type: "internal_server_error".to_string(),
+                    detail: e.to_string(),
+                    source,
+                },
+                None::<MetadataType>,
+            );
+
+            return Ok(HttpResponse::InternalServerError().json(response));
+        }
+        Err(e) => {
+            let response: ErrorResponseType = ErrorResponseType::new(
+                ErrorType {
+                    id: Uuid::new_v4().to_string(),
+                    code: 400,
+                    error_type: "bad_request".to_string(),
+                    detail: e.to_string(),
+                    source,
+                },
+                None::<MetadataType>,
+            );
+
+            return Ok(HttpResponse::BadRequest().json(response));
+        }
+    };
+
+    let order = Order::get_by_id(
+        pool,
+        order_id,
+        Some(&FetchLevel::Default),
+        Some(&FetchLevel::Compact),
+    )
+    .await;
+
+    if let Ok(order) = order {
+        if let Err(e) = send_refund_email(
+            credential,
+            order,
+            refunded_amount,
+            refund.reason.as_deref(),
+        ) {
+            println!("Error: {}", e);
+        }
+    }
+
+    let fetch_level = match request.fetch_level.clone() {
+        Some(fetch_level) => fetch_level,
+        None => FetchLevel::Default,
+    };
+
+    let descendant_fetch_level = match request.descendant_fetch_level.clone() {
+        Some(descendant_fetch_level) => descendant_fetch_level,
+        None => FetchLevel::IdOnly,
+    };
+
+    let order = Order::get_by_id(
+        pool,
+        order_id,
+        Some(&fetch_level),
+        Some(&descendant_fetch_level),
+    )
+    .await;
+
+    match order {
+        Ok(order) => Ok(HttpResponse::Ok().json(ResponseType::new(
+            order,
+            Some(MetadataType::new(None::<PaginationType>)),
+        ))),
+        Err(e) => {
+            let response: ErrorResponseType = ErrorResponseType::new(
+                ErrorType {
+                    id: Uuid::new_v4().to_string(),
+                    code: 500,
+                    error_type: "internal_server_error".to_string(),
+                    detail: e.to_string(),
+                    source,
+                },
+                None::<MetadataType>,
+            );
+
+            Ok(HttpResponse::InternalServerError().json(response))
+        }
+    }
+}
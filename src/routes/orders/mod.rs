@@ -1,6 +1,14 @@
+pub(crate) mod checkout_session_detail;
+pub(crate) mod collect_cash;
 pub(crate) mod create_orders;
+pub(crate) mod create_refund;
 pub(crate) mod order_confirm_webhook;
 pub(crate) mod order_detail;
+pub(crate) mod pickup_qr;
+pub(crate) mod promptpay_qr;
 pub(crate) mod query_orders;
+pub(crate) mod query_refunds;
+pub(crate) mod quote_shipping;
+pub(crate) mod simulate_payment;
 pub(crate) mod update_order_by_id;
 pub(crate) mod upload_slip_payment;
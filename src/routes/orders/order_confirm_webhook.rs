@@ -3,7 +3,8 @@ use mysk_lib::models::common::requests::FetchLevel;
 
 use crate::{
     models::order::{
-        gbprimpay::{GbPrimePayWebHookRequest, ResultCode},
+        gbprimpay::GbPrimePayWebHookRequest,
+        payment::{WebhookError, WebhookOutcome},
         Order,
     },
     utils::email::send_receipt_email,
@@ -21,6 +22,7 @@ pub async fn update_order_webhook(
 ) -> Result<impl Responder, actix_web::Error> {
     let pool: &sqlx::Pool<sqlx::Postgres> = &data.db;
     let credential = &data.smtp_credential;
+    let payment_provider = &data.payment_provider;
 
     // let json_string = std::str::from_utf8(&body).unwrap();
 
@@ -30,43 +32,45 @@ pub async fn update_order_webhook(
 
     let data = request.into_inner();
 
-    if data.result_code != ResultCode::Success {
-        return Ok(HttpResponse::Ok().finish());
-    }
-
-    let res = data.update_order_status(pool).await;
+    let res = data.to_callback().process(pool, payment_provider).await;
 
     match res {
+        // a non-200 response makes GBPrimePay send the callback again later
+        Err(WebhookError::Verification(e)) => {
+            println!("Error: {}", e);
+            Ok(HttpResponse::ServiceUnavailable().finish())
+        }
         Err(e) => {
             println!("Error: {}", e);
             Ok(HttpResponse::InternalServerError().finish())
         }
-        Ok(order_id) => {
-            let order = Order::get_by_id(
-                pool,
-                order_id,
-                Some(&FetchLevel::Default),
-                Some(&FetchLevel::Compact),
-            )
-            .await;
-
-            match order {
-                Err(e) => {
-                    println!("Error: {}", e);
-                    Ok(HttpResponse::InternalServerError().finish())
-                }
-                Ok(order) => {
-                    let res = send_receipt_email(credential, order);
+        Ok(WebhookOutcome::AlreadySettled) => Ok(HttpResponse::Ok().finish()),
+        Ok(WebhookOutcome::Ignored(reason)) => {
+            println!("Ignored GBPrimePay callback {}: {}", data.gbp_reference_no, reason);
+            Ok(HttpResponse::Ok().finish())
+        }
+        Ok(WebhookOutcome::Settled(order_ids)) => {
+            for order_id in order_ids {
+                let order = Order::get_by_id(
+                    pool,
+                    order_id,
+                    Some(&FetchLevel::Default),
+                    Some(&FetchLevel::Compact),
+                )
+                .await;
 
-                    match res {
-                        Err(e) => {
+                // the payment is already recorded, failing here would only make GBPrimePay retry
+                match order {
+                    Err(e) => println!("Error: {}", e),
+                    Ok(order) => {
+                        if let Err(e) = send_receipt_email(credential, order) {
                             println!("Error: {}", e);
-                            Ok(HttpResponse::InternalServerError().finish())
                         }
-                        Ok(_) => Ok(HttpResponse::Ok().finish()),
                     }
                 }
             }
+
+            Ok(HttpResponse::Ok().finish())
         }
     }
 }
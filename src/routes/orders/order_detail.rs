@@ -5,7 +5,13 @@ use mysk_lib::models::common::{
 };
 use uuid::Uuid;
 
-use crate::{models::order::Order, AppState};
+use crate::{
+    models::{
+        auth::{permission::authorize_order_access, user::OptionalUser},
+        order::Order,
+    },
+    AppState,
+};
 
 #[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
 pub struct PlaceholderOrder;
@@ -15,10 +21,15 @@ pub async fn order_detail(
     data: web::Data<AppState>,
     order_id: web::Path<Uuid>,
     request_query: web::Query<RequestType<Order, PlaceholderOrder, PlaceholderOrder>>,
+    user: OptionalUser,
 ) -> Result<impl Responder, actix_web::Error> {
     let pool = &data.db;
     let order_id = order_id.into_inner();
 
+    let user_id = user.0.map(|user| user.id());
+
+    authorize_order_access(pool, user_id, order_id, &format!("/orders/{order_id}")).await?;
+
     let fetch_level = match request_query.fetch_level.clone() {
         Some(fetch_level) => fetch_level,
         None => FetchLevel::Default,
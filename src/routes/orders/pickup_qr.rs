@@ -0,0 +1,94 @@
+use actix_web::{get, web, HttpResponse, Responder};
+use mysk_lib::models::common::response::{ErrorResponseType, ErrorType, MetadataType};
+use serde::Deserialize;
+use uuid::Uuid;
+
+use crate::{
+    models::{
+        auth::{permission::authorize_order_access, user::OptionalUser},
+        order::db::OrderTable,
+    },
+    utils::promptpay,
+    AppState,
+};
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum QrFormat {
+    Png,
+    Svg,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct PickupQrQuery {
+    format: Option<QrFormat>,
+}
+
+fn bad_request(detail: &str, source: String) -> HttpResponse {
+    let response: ErrorResponseType = ErrorResponseType::new(
+        ErrorType {
+            id: Uuid::new_v4().to_string(),
+            code: 400,
+            error_type: "bad_request".to_string(),
+            detail: detail.to_string(),
+            source,
+        },
+        None::<MetadataType>,
+    );
+
+    HttpResponse::BadRequest().json(response)
+}
+
+fn internal_server_error(detail: String, source: String) -> HttpResponse {
+    let response: ErrorResponseType = ErrorResponseType::new(
+        ErrorType {
+            id: Uuid::new_v4().to_string(),
+            code: 500,
+            error_type: "internal_server_error".to_string(),
+            detail,
+            source,
+        },
+        None::<MetadataType>,
+    );
+
+    HttpResponse::InternalServerError().json(response)
+}
+
+// the pickup code as a QR code for the booth to scan, only the buyer and the shop can see it
+#[get("/orders/{order_id}/pickup-qr")]
+pub async fn pickup_qr(
+    data: web::Data<AppState>,
+    order_id: web::Path<Uuid>,
+    query: web::Query<PickupQrQuery>,
+    user: OptionalUser,
+) -> Result<impl Responder, actix_web::Error> {
+    let pool = &data.db;
+    let order_id = order_id.into_inner();
+    let source = format!("/orders/{order_id}/pickup-qr");
+
+    let user_id = user.0.map(|user| user.id());
+
+    authorize_order_access(pool, user_id, order_id, &source).await?;
+
+    let order = match OrderTable::get_by_id(pool, order_id).await {
+        Ok(order) => order,
+        Err(e) => return Ok(internal_server_error(e.to_string(), source)),
+    };
+
+    let pickup_code = match order.pickup_code {
+        Some(pickup_code) => pickup_code,
+        None => return Ok(bad_request("order is not picked up at school", source)),
+    };
+
+    let image = match query.format {
+        Some(QrFormat::Svg) => promptpay::render_svg(&pickup_code)
+            .map(|svg| HttpResponse::Ok().content_type("image/svg+xml").body(svg)),
+        _ => promptpay::render_png(&pickup_code)
+            .map(|png| HttpResponse::Ok().content_type("image/png").body(png)),
+    };
+
+    match image {
+        Ok(image) => Ok(image),
+        Err(e) => Ok(internal_server_error(e.to_string(), source)),
+    }
+}
@@ -0,0 +1,126 @@
+use actix_web::{get, web, HttpResponse, Responder};
+use mysk_lib::models::common::response::{ErrorResponseType, ErrorType, MetadataType};
+use serde::Deserialize;
+use sqlx::Row;
+use uuid::Uuid;
+
+use crate::{
+    models::{
+        auth::{permission::authorize_order_access, user::OptionalUser},
+        order::db::{OrderTable, PaymentMethod},
+        shop::db::ShopTable,
+    },
+    utils::promptpay,
+    AppState,
+};
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum QrFormat {
+    Png,
+    Svg,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct PromptPayQrQuery {
+    format: Option<QrFormat>,
+}
+
+fn bad_request(detail: &str, source: String) -> HttpResponse {
+    let response: ErrorResponseType = ErrorResponseType::new(
+        ErrorType {
+            id: Uuid::new_v4().to_string(),
+            code: 400,
+            error_type: "bad_request".to_string(),
+            detail: detail.to_string(),
+            source,
+        },
+        None::<MetadataType>,
+    );
+
+    HttpResponse::BadRequest().json(response)
+}
+
+fn internal_server_error(detail: String, source: String) -> HttpResponse {
+    let response: ErrorResponseType = ErrorResponseType::new(
+        ErrorType {
+            id: Uuid::new_v4().to_string(),
+            code: 500,
+            error_type: "internal_server_error".to_string(),
+            detail,
+            source,
+        },
+        None::<MetadataType>,
+    );
+
+    HttpResponse::InternalServerError().json(response)
+}
+
+// the QR code of an order paid straight to the shop's promptpay number, rendered on request so
+// it can be printed at any size
+#[get("/orders/{order_id}/promptpay-qr")]
+pub async fn promptpay_qr(
+    data: web::Data<AppState>,
+    order_id: web::Path<Uuid>,
+    query: web::Query<PromptPayQrQuery>,
+    user: OptionalUser,
+) -> Result<impl Responder, actix_web::Error> {
+    let pool = &data.db;
+    let order_id = order_id.into_inner();
+    let source = format!("/orders/{order_id}/promptpay-qr");
+
+    let user_id = user.0.map(|user| user.id());
+
+    authorize_order_access(pool, user_id, order_id, &source).await?;
+
+    let order = match OrderTable::get_by_id(pool, order_id).await {
+        Ok(order) => order,
+        Err(e) => return Ok(internal_server_error(e.to_string(), source)),
+    };
+
+    if order.payment_method != PaymentMethod::Promptpay {
+        return Ok(bad_request("order is not paid with PromptPay", source));
+    }
+
+    let shop = sqlx::query("SELECT shop_id FROM orders WHERE id = $1")
+        .bind(order_id)
+        .fetch_one(pool)
+        .await;
+
+    let shop = match shop {
+        Ok(shop) => ShopTable::get_by_id(pool, shop.get::<Uuid, _>("shop_id")).await,
+        Err(e) => Err(e),
+    };
+
+    let shop = match shop {
+        Ok(shop) => shop,
+        Err(e) => return Ok(internal_server_error(e.to_string(), source)),
+    };
+
+    let promptpay_number = match (shop.use_payment_gateway, shop.promptpay_number) {
+        (false, Some(promptpay_number)) => promptpay_number,
+        _ => {
+            return Ok(bad_request(
+                "order is paid through the payment gateway, use promptpay_qr_code_url",
+                source,
+            ))
+        }
+    };
+
+    let payload = match promptpay::payload(&promptpay_number, Some(order.total_price)) {
+        Ok(payload) => payload,
+        Err(e) => return Ok(internal_server_error(e.to_string(), source)),
+    };
+
+    let image = match query.format {
+        Some(QrFormat::Svg) => promptpay::render_svg(&payload)
+            .map(|svg| HttpResponse::Ok().content_type("image/svg+xml").body(svg)),
+        _ => promptpay::render_png(&payload)
+            .map(|png| HttpResponse::Ok().content_type("image/png").body(png)),
+    };
+
+    match image {
+        Ok(image) => Ok(image),
+        Err(e) => Ok(internal_server_error(e.to_string(), source)),
+    }
+}
@@ -1,14 +1,20 @@
 use actix_web::{get, web, HttpRequest, HttpResponse, Responder};
 use mysk_lib::models::common::{
-    requests::{FetchLevel, RequestType},
+    requests::{FetchLevel, FilterConfig, RequestType},
     response::{ErrorResponseType, ErrorType, MetadataType, PaginationType, ResponseType},
 };
 use uuid::Uuid;
 
 use crate::{
-    models::order::{
-        request::{QueryableOrder, SortableOrder},
-        Order,
+    models::{
+        auth::{
+            permission::{authorize, Role, ShopResource},
+            user::User,
+        },
+        order::{
+            request::{QueryableOrder, SortableOrder},
+            Order,
+        },
     },
     AppState,
 };
@@ -17,12 +23,13 @@ use crate::{
 pub async fn query_orders(
     data: web::Data<AppState>,
     request: HttpRequest,
+    user: User,
 ) -> Result<impl Responder, actix_web::Error> {
     let request_query = serde_qs::from_str::<RequestType<Order, QueryableOrder, SortableOrder>>(
         request.query_string(),
     );
 
-    let request_query = match request_query {
+    let mut request_query = match request_query {
         Ok(request_query) => request_query,
         Err(e) => {
             let response: ErrorResponseType = ErrorResponseType::new(
@@ -41,6 +48,47 @@ pub async fn query_orders(
     };
 
     let pool = &data.db;
+    let user_id = user.id();
+
+    let shop_ids = request_query
+        .filter
+        .as_ref()
+        .and_then(|filter| filter.data.as_ref())
+        .and_then(|data| data.shop_ids.clone());
+
+    // shop staff can see the orders of their shops, everyone else only sees their own orders
+    match shop_ids {
+        Some(shop_ids) => {
+            for shop_id in shop_ids {
+                authorize(
+                    pool,
+                    user_id,
+                    ShopResource::Shop(shop_id),
+                    Role::ShopStaff,
+                    "/orders",
+                )
+                .await?;
+            }
+        }
+        None => {
+            let is_platform_admin = Role::is_platform_admin(pool, user_id)
+                .await
+                .unwrap_or(false);
+
+            // without a shop filter buyers get their own orders
+            if !is_platform_admin {
+                let filter = request_query.filter.get_or_insert(FilterConfig {
+                    data: None,
+                    q: None,
+                });
+
+                filter
+                    .data
+                    .get_or_insert_with(QueryableOrder::default)
+                    .buyer_ids = Some(vec![user_id]);
+            }
+        }
+    }
 
     let fetch_level = match request_query.fetch_level.clone() {
         Some(fetch_level) => fetch_level,
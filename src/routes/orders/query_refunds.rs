@@ -0,0 +1,49 @@
+use actix_web::{get, web, HttpResponse, Responder};
+use mysk_lib::models::common::response::{
+    ErrorResponseType, ErrorType, MetadataType, PaginationType, ResponseType,
+};
+use uuid::Uuid;
+
+use crate::{
+    models::{
+        auth::{permission::authorize_order_access, user::OptionalUser},
+        order::refund::Refund,
+    },
+    AppState,
+};
+
+#[get("/orders/{order_id}/refunds")]
+pub async fn query_refunds(
+    data: web::Data<AppState>,
+    order_id: web::Path<Uuid>,
+    user: OptionalUser,
+) -> Result<impl Responder, actix_web::Error> {
+    let pool = &data.db;
+    let order_id = order_id.into_inner();
+    let source = format!("/orders/{order_id}/refunds");
+
+    let user_id = user.0.map(|user| user.id());
+
+    authorize_order_access(pool, user_id, order_id, &source).await?;
+
+    match Refund::get_by_order_id(pool, order_id).await {
+        Ok(refunds) => Ok(HttpResponse::Ok().json(ResponseType::new(
+            refunds,
+            Some(MetadataType::new(None::<PaginationType>)),
+        ))),
+        Err(e) => {
+            let response: ErrorResponseType = ErrorResponseType::new(
+                ErrorType {
+                    id: Uuid::new_v4().to_string(),
+                    code: 500,
+                    error_type: "internal_server_error".to_string(),
+                    detail: e.to_string(),
+                    source,
+                },
+                None::<MetadataType>,
+            );
+
+            Ok(HttpResponse::InternalServerError().json(response))
+        }
+    }
+}
@@ -0,0 +1,90 @@
+use actix_web::{post, web, HttpResponse, Responder};
+use mysk_lib::models::common::{
+    requests::RequestType,
+    response::{ErrorResponseType, ErrorType, MetadataType, PaginationType, ResponseType},
+};
+use uuid::Uuid;
+
+use crate::{
+    models::{
+        order::request::{QueryableOrder, SortableOrder},
+        shop::shipping::ShippingQuoteRequest,
+    },
+    AppState,
+};
+
+#[post("/orders/shipping-quote")]
+pub async fn quote_shipping(
+    data: web::Data<AppState>,
+    request: web::Json<RequestType<ShippingQuoteRequest, QueryableOrder, SortableOrder>>,
+) -> Result<impl Responder, actix_web::Error> {
+    let pool = &data.db;
+
+    let data = match &request.data {
+        Some(data) => data,
+        None => {
+            let response: ErrorResponseType = ErrorResponseType::new(
+                ErrorType {
+                    id: Uuid::new_v4().to_string(),
+                    code: 400,
+                    error_type: "bad_request".to_string(),
+                    detail: "request body is empty".to_string(),
+                    source: "/orders/shipping-quote".to_string(),
+                },
+                Some(MetadataType::new(None::<PaginationType>)),
+            );
+
+            return Ok(HttpResponse::BadRequest().json(response));
+        }
+    };
+
+    if data.items.is_empty() || data.items.iter().any(|item| item.amount <= 0) {
+        let response: ErrorResponseType = ErrorResponseType::new(
+            ErrorType {
+                id: Uuid::new_v4().to_string(),
+                code: 400,
+                error_type: "bad_request".to_string(),
+                detail: "items must not be empty and every amount must be positive".to_string(),
+                source: "/orders/shipping-quote".to_string(),
+            },
+            Some(MetadataType::new(None::<PaginationType>)),
+        );
+
+        return Ok(HttpResponse::BadRequest().json(response));
+    }
+
+    match data.quote(pool).await {
+        Ok(quotes) => Ok(HttpResponse::Ok().json(ResponseType::new(
+            quotes,
+            Some(MetadataType::new(None::<PaginationType>)),
+        ))),
+        Err(sqlx::Error::RowNotFound) => {
+            let response: ErrorResponseType = ErrorResponseType::new(
+                ErrorType {
+                    id: Uuid::new_v4().to_string(),
+                    code: 404,
+                    error_type: "entity_not_found".to_string(),
+                    detail: "one or more items do not exist".to_string(),
+                    source: "/orders/shipping-quote".to_string(),
+                },
+                None::<MetadataType>,
+            );
+
+            Ok(HttpResponse::NotFound().json(response))
+        }
+        Err(e) => {
+            let response: ErrorResponseType = ErrorResponseType::new(
+                ErrorType {
+                    id: Uuid::new_v4().to_string(),
+                    code: 500,
+                    error_type: "internal_server_error".to_string(),
+                    detail: e.to_string(),
+                    source: "/orders/shipping-quote".to_string(),
+                },
+                None::<MetadataType>,
+            );
+
+            Ok(HttpResponse::InternalServerError().json(response))
+        }
+    }
+}
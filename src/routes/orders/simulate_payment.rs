@@ -0,0 +1,213 @@
+use actix_web::{post, web, HttpResponse, Responder};
+use mysk_lib::models::common::{
+    requests::{FetchLevel, RequestType},
+    response::{ErrorResponseType, ErrorType, MetadataType, PaginationType, ResponseType},
+};
+use serde::Deserialize;
+use uuid::Uuid;
+
+use crate::{
+    models::{
+        auth::{permission::authorize_order_access, user::OptionalUser},
+        order::{
+            checkout::CheckoutSession,
+            db::OrderTable,
+            payment::WebhookOutcome,
+            provider::{PaymentCallback, PaymentGateway},
+            request::{QueryableOrder, SortableOrder},
+            Order,
+        },
+    },
+    utils::email::send_receipt_email,
+    AppState,
+};
+
+#[derive(Debug, Deserialize)]
+pub struct SimulatedPayment {
+    // defaults to the order total, a different amount exercises the amount check
+    amount_satang: Option<i64>,
+    is_successful: Option<bool>,
+}
+
+// pays for an order through the mock provider and its callback, only there when
+// PAYMENT_PROVIDER=mock
+#[post("/orders/{order_id}/simulate-payment")]
+pub async fn simulate_payment(
+    data: web::Data<AppState>,
+    order_id: web::Path<Uuid>,
+    request: web::Json<RequestType<SimulatedPayment, QueryableOrder, SortableOrder>>,
+    user: OptionalUser,
+) -> Result<impl Responder, actix_web::Error> {
+    let pool = &data.db;
+    let credential = &data.smtp_credential;
+    let payment_provider = &data.payment_provider;
+    let order_id = order_id.into_inner();
+    let source = format!("/orders/{order_id}/simulate-payment");
+
+    let mock = match payment_provider {
+        PaymentGateway::Mock(mock) => mock,
+        _ => {
+            let response: ErrorResponseType = ErrorResponseType::new(
+                ErrorType {
+                    id: Uuid::new_v4().to_string(),
+                    code: 404,
+                    error_type: "entity_not_found".to_string(),
+                    detail: "payments can only be simulated with the mock provider".to_string(),
+                    source,
+                },
+                None::<MetadataType>,
+            );
+
+            return Ok(HttpResponse::NotFound().json(response));
+        }
+    };
+
+    let user_id = user.0.map(|user| user.id());
+
+    authorize_order_access(pool, user_id, order_id, &source).await?;
+
+    let order = match OrderTable::get_by_id(pool, order_id).await {
+        Ok(order) => order,
+        Err(e) => {
+            let response: ErrorResponseType = ErrorResponseType::new(
+                ErrorType {
+                    id: Uuid::new_v4().to_string(),
+                    code: 404,
+                    error_type: "entity_not_found".to_string(),
+                    detail: e.to_string(),
+                    source,
+                },
+                None::<MetadataType>,
+            );
+
+            return Ok(HttpResponse::NotFound().json(response));
+        }
+    };
+
+    // orders checked out together are paid together under the session's reference
+    let (reference_no, total_price) = match order.checkout_session_id {
+        Some(session_id) => match CheckoutSession::get_by_id(pool, session_id).await {
+            Ok(session) => (session.ref_id, session.total_price),
+            Err(e) => {
+                let response: ErrorResponseType = ErrorResponseType::new(
+                    ErrorType {
+                        id: Uuid::new_v4().to_string(),
+                        code: 500,
+                        error_type: "internal_server_error".to_string(),
+                        detail: e.to_string(),
+                        source,
+                    },
+                    None::<MetadataType>,
+                );
+
+                return Ok(HttpResponse::InternalServerError().json(response));
+            }
+        },
+        None => (order.ref_id, order.total_price),
+    };
+
+    let amount_satang = request
+        .data
+        .as_ref()
+        .and_then(|data| data.amount_satang)
+        .unwrap_or(total_price * 100);
+    let is_successful = request
+        .data
+        .as_ref()
+        .and_then(|data| data.is_successful)
+        .unwrap_or(true);
+
+    // a failed payment never reaches the provider's books, only its callback is sent
+    let provider_reference_no = match is_successful {
+        true => mock.settle(&reference_no, amount_satang).provider_reference_no,
+        false => format!("MOCK-{}", Uuid::new_v4().simple()),
+    };
+
+    let callback = PaymentCallback {
+        reference_no,
+        provider_reference_no,
+        amount_satang,
+        is_successful,
+        result_code: None,
+        is_retry: false,
+        payload: None,
+    };
+
+    let settled_order_ids = match callback.process(pool, payment_provider).await {
+        Ok(WebhookOutcome::Ignored(reason)) => {
+            let response: ErrorResponseType = ErrorResponseType::new(
+                ErrorType {
+                    id: Uuid::new_v4().to_string(),
+                    code: 400,
+                    error_type: "bad_request".to_string(),
+                    detail: reason,
+                    source,
+                },
+                Some(MetadataType::new(None::<PaginationType>)),
+            );
+
+            return Ok(HttpResponse::BadRequest().json(response));
+        }
+        Ok(WebhookOutcome::Settled(order_ids)) => order_ids,
+        Ok(WebhookOutcome::AlreadySettled) => Vec::new(),
+        Err(e) => {
+            let response: ErrorResponseType = ErrorResponseType::new(
+                ErrorType {
+                    id: Uuid::new_v4().to_string(),
+                    code: 500,
+                    error_type: "internal_server_error".to_string(),
+                    detail: e.to_string(),
+                    source,
+                },
+                None::<MetadataType>,
+            );
+
+            return Ok(HttpResponse::InternalServerError().json(response));
+        }
+    };
+
+    for settled_order_id in settled_order_ids {
+        let order = Order::get_by_id(
+            pool,
+            settled_order_id,
+            Some(&FetchLevel::Default),
+            Some(&FetchLevel::Compact),
+        )
+        .await;
+
+        if let Ok(order) = order {
+            if let Err(e) = send_receipt_email(credential, order) {
+                println!("Error: {}", e);
+            }
+        }
+    }
+
+    let order = Order::get_by_id(
+        pool,
+        order_id,
+        Some(&FetchLevel::Default),
+        Some(&FetchLevel::Compact),
+    )
+    .await;
+
+    match order {
+        Ok(order) => Ok(HttpResponse::Ok().json(ResponseType::new(
+            order,
+            Some(MetadataType::new(None::<PaginationType>)),
+        ))),
+        Err(e) => {
+            let response: ErrorResponseType = ErrorResponseType::new(
+                ErrorType {
+                    id: Uuid::new_v4().to_string(),
+                    code: 500,
+                    error_type: "internal_server_error".to_string(),
+                    detail: e.to_string(),
+                    source,
+                },
+                None::<MetadataType>,
+            );
+
+            Ok(HttpResponse::InternalServerError().json(response))
+        }
+    }
+}
@@ -3,17 +3,18 @@ use mysk_lib::models::common::{
     requests::{FetchLevel, RequestType},
     response::{ErrorResponseType, ErrorType, MetadataType, PaginationType, ResponseType},
 };
-use sqlx::Row;
 use uuid::Uuid;
 
 use crate::{
     models::{
-        auth::user::User,
+        auth::permission::{RequireShopRole, Staff},
         order::{
+            db::OrderStatus,
             request::{QueryableOrder, SortableOrder, UpdatableOrder},
             Order,
         },
     },
+    utils::email::{send_pickup_ready_email, send_shipping_email},
     AppState,
 };
 
@@ -22,9 +23,10 @@ pub async fn update_order_by_id(
     data: web::Data<AppState>,
     order_id: web::Path<Uuid>,
     request: web::Json<RequestType<UpdatableOrder, QueryableOrder, SortableOrder>>,
-    user: User,
+    shop_role: RequireShopRole<Staff>,
 ) -> Result<impl Responder, actix_web::Error> {
     let pool: &sqlx::Pool<sqlx::Postgres> = &data.db;
+    let credential = &data.smtp_credential;
     let order_id = order_id.into_inner();
 
     let data = match &request.data {
@@ -45,41 +47,9 @@ pub async fn update_order_by_id(
         }
     };
 
-    let user_id = match user {
-        User::IdOnly(user) => user.id,
-        User::Compact(user) => user.id,
-        User::Default(user) => user.id,
-        User::Detailed(user) => user.id,
-    };
-
-    let res = sqlx::query(
-        r#"
-        SELECT COUNT(id) FROM shop_managers WHERE user_id = $1 AND shop_id IN (SELECT shop_id FROM orders WHERE id = $2)
-        "#,
-    )
-    .bind(user_id)
-    .bind(order_id)
-    .fetch_one(pool)
-    .await;
-
-    if let Ok(res) = res {
-        if res.get::<Option<i64>, _>("count").unwrap_or(0) == 0 {
-            let response: ErrorResponseType = ErrorResponseType::new(
-                ErrorType {
-                    id: Uuid::new_v4().to_string(),
-                    code: 403,
-                    error_type: "forbidden".to_string(),
-                    detail: format!("user {} is not a manager of shop {}", user_id, order_id),
-                    source: format!("/orders/{order_id}"),
-                },
-                None::<MetadataType>,
-            );
-
-            return Ok(HttpResponse::Forbidden().json(response));
-        }
-    }
-
-    let res = data.commit_changes(pool, order_id).await;
+    let res = data
+        .commit_changes(pool, order_id, shop_role.permission.user_id)
+        .await;
 
     if res.is_err() {
         let response: ErrorResponseType = ErrorResponseType::new(
@@ -96,6 +66,31 @@ pub async fn update_order_by_id(
         return Ok(HttpResponse::BadRequest().json(response));
     };
 
+    // the buyer gets their pickup code once there is something to pick up, and the tracking once
+    // the parcel has left
+    let is_ready_for_pickup = data.shipment_status == Some(OrderStatus::ReadyForPickup);
+    let is_shipped = data.tracking_number.is_some();
+
+    if is_ready_for_pickup || is_shipped {
+        let order = Order::get_by_id(
+            pool,
+            order_id,
+            Some(&FetchLevel::Default),
+            Some(&FetchLevel::Compact),
+        )
+        .await;
+
+        let res = match order {
+            Ok(order) if is_ready_for_pickup => send_pickup_ready_email(credential, order),
+            Ok(order) => send_shipping_email(credential, order),
+            Err(_) => Ok(()),
+        };
+
+        if let Err(e) = res {
+            println!("Error: {}", e);
+        }
+    }
+
     let fetch_level = match request.fetch_level.clone() {
         Some(fetch_level) => fetch_level,
         None => FetchLevel::Default,
@@ -4,21 +4,26 @@ use mysk_lib::models::common::{
     response::{ErrorResponseType, ErrorType, MetadataType, PaginationType, ResponseType},
 };
 use serde::Deserialize;
-use sqlx::Row;
 use uuid::Uuid;
 
 use crate::{
-    models::order::{
-        request::{QueryableOrder, SortableOrder},
-        Order,
+    models::{
+        auth::{permission::authorize_order_access, user::OptionalUser},
+        order::{
+            request::{QueryableOrder, SortableOrder},
+            slip::submit_slip,
+            status::OrderStatusError,
+            Order,
+        },
     },
-    utils::email::send_receipt_email,
     AppState,
 };
 
 #[derive(Debug, Deserialize)]
 pub struct UpdateOrder {
     payment_slip_url: String,
+    // what the buyer says they paid, shown to the manager reviewing the slip
+    amount_claimed: Option<i64>,
 }
 
 #[patch("/orders/{order_id}/slip")]
@@ -26,10 +31,9 @@ pub async fn upload_slip_payment(
     data: web::Data<AppState>,
     order_id: web::Path<Uuid>,
     request: web::Json<RequestType<UpdateOrder, QueryableOrder, SortableOrder>>,
-    // user: OptionalUser,
+    user: OptionalUser,
 ) -> Result<impl Responder, actix_web::Error> {
     let pool: &sqlx::Pool<sqlx::Postgres> = &data.db;
-    let credential = &data.smtp_credential;
     let order_id = order_id.into_inner();
 
     let data = match &request.data {
@@ -50,90 +54,52 @@ pub async fn upload_slip_payment(
         }
     };
 
-    // let user_id = match user.0 {
-    //     Some(user) => match user {
-    //         User::IdOnly(user) => Some(user.id),
-    //         User::Compact(user) => Some(user.id),
-    //         User::Default(user) => Some(user.id),
-    //         User::Detailed(user) => Some(user.id),
-    //     },
-    //     None => None,
-    // };
-
-    let res = sqlx::query(
-        r#"
-        UPDATE orders
-        SET payment_slip_url = $1, is_paid = true
-        WHERE id = $2
-        RETURNING id
-        "#,
-    )
-    .bind(&data.payment_slip_url)
-    .bind(order_id)
-    .fetch_one(pool)
-    .await;
-
-    let res = match res {
-        Ok(res) => res,
-        Err(err) => {
-            let response: ErrorResponseType = ErrorResponseType::new(
-                ErrorType {
-                    id: Uuid::new_v4().to_string(),
-                    code: 500,
-                    error_type: "internal_server_error".to_string(),
-                    detail: err.to_string(),
-                    source: format!("/orders/{order_id}"),
-                },
-                None::<MetadataType>,
-            );
-
-            return Ok(HttpResponse::InternalServerError().json(response));
-        }
-    };
+    let user_id = user.0.map(|user| user.id());
 
-    let order_id = res.get::<Uuid, _>("id");
+    authorize_order_access(pool, user_id, order_id, &format!("/orders/{order_id}/slip")).await?;
 
-    let order = Order::get_by_id(
+    let res = submit_slip(
         pool,
         order_id,
-        Some(&FetchLevel::Default),
-        Some(&FetchLevel::Compact),
+        &data.payment_slip_url,
+        data.amount_claimed,
+        user_id,
     )
     .await;
 
-    let order = match order {
-        Err(err) => {
-            let response: ErrorResponseType = ErrorResponseType::new(
-                ErrorType {
-                    id: Uuid::new_v4().to_string(),
-                    code: 500,
-                    error_type: "internal_server_error".to_string(),
-                    detail: err.to_string(),
-                    source: format!("/orders/{order_id}"),
-                },
-                None::<MetadataType>,
-            );
-
-            return Ok(HttpResponse::InternalServerError().json(response));
-        }
-        Ok(order) => order,
-    };
-
-    let res = send_receipt_email(credential, order);
-
     if let Err(err) = res {
-        let response: ErrorResponseType = ErrorResponseType::new(
-            ErrorType {
-                id: Uuid::new_v4().to_string(),
-                code: 500,
-                error_type: "internal_server_error".to_string(),
-                detail: err.to_string(),
-                source: format!("/orders/{order_id}"),
-            },
-            None::<MetadataType>,
-        );
-
-        return Ok(HttpResponse::InternalServerError().json(response));
+        let response = match err {
+            OrderStatusError::InvalidTransition { .. } | OrderStatusError::Rejected(_) => {
+                let response: ErrorResponseType = ErrorResponseType::new(
+                    ErrorType {
+                        id: Uuid::new_v4().to_string(),
+                        code: 400,
+                        error_type: "bad_request".to_string(),
+                        detail: err.to_string(),
+                        source: format!("/orders/{order_id}"),
+                    },
+                    None::<MetadataType>,
+                );
+
+                HttpResponse::BadRequest().json(response)
+            }
+            OrderStatusError::Database(err) => {
+                let response: ErrorResponseType = ErrorResponseType::new(
+                    ErrorType {
+                        id: Uuid::new_v4().to_string(),
+                        code: 500,
+                        error_type: "internal_server_error".to_string(),
+                        detail: err.to_string(),
+                        source: format!("/orders/{order_id}"),
+                    },
+                    None::<MetadataType>,
+                );
+
+                HttpResponse::InternalServerError().json(response)
+            }
+        };
+
+        return Ok(response);
     }
 
     let fetch_level = match request.fetch_level.clone() {
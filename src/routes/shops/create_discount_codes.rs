@@ -0,0 +1,106 @@
+use actix_web::{post, web, HttpResponse, Responder};
+use mysk_lib::models::common::{
+    requests::RequestType,
+    response::{ErrorResponseType, ErrorType, MetadataType, PaginationType, ResponseType},
+};
+use uuid::Uuid;
+
+use crate::{
+    models::{
+        auth::permission::{RequireShopRole, Staff},
+        discount::{request::CreatableDiscountCode, DiscountCode},
+        shop::request::{QueryableShop, SortableShop},
+    },
+    AppState,
+};
+
+#[post("/shops/{shop_id}/discount-codes")]
+pub async fn create_discount_codes(
+    data: web::Data<AppState>,
+    shop_id: web::Path<Uuid>,
+    request: web::Json<RequestType<Vec<CreatableDiscountCode>, QueryableShop, SortableShop>>,
+    _permission: RequireShopRole<Staff>,
+) -> Result<impl Responder, actix_web::Error> {
+    let pool = &data.db;
+    let shop_id = shop_id.into_inner();
+
+    let data = match &request.data {
+        Some(data) => data,
+        None => {
+            let response: ErrorResponseType = ErrorResponseType::new(
+                ErrorType {
+                    id: Uuid::new_v4().to_string(),
+                    code: 400,
+                    error_type: "bad_request".to_string(),
+                    detail: "request body is empty".to_string(),
+                    source: format!("/shops/{shop_id}/discount-codes"),
+                },
+                Some(MetadataType::new(None::<PaginationType>)),
+            );
+
+            return Ok(HttpResponse::BadRequest().json(response));
+        }
+    };
+
+    for discount_code in data {
+        if let Err(err) = discount_code.validate() {
+            let response: ErrorResponseType = ErrorResponseType::new(
+                ErrorType {
+                    id: Uuid::new_v4().to_string(),
+                    code: 400,
+                    error_type: "bad_request".to_string(),
+                    detail: err,
+                    source: format!("/shops/{shop_id}/discount-codes"),
+                },
+                Some(MetadataType::new(None::<PaginationType>)),
+            );
+
+            return Ok(HttpResponse::BadRequest().json(response));
+        }
+    }
+
+    let ids = match CreatableDiscountCode::bulk_insert(data, shop_id, pool).await {
+        Ok(ids) => ids,
+        Err(err) => {
+            let response: ErrorResponseType = ErrorResponseType::new(
+                ErrorType {
+                    id: Uuid::new_v4().to_string(),
+                    code: 400,
+                    error_type: "bad_request".to_string(),
+                    detail: err.to_string(),
+                    source: format!("/shops/{shop_id}/discount-codes"),
+                },
+                Some(MetadataType::new(None::<PaginationType>)),
+            );
+
+            return Ok(HttpResponse::BadRequest().json(response));
+        }
+    };
+
+    let mut discount_codes = Vec::new();
+
+    for id in ids {
+        match DiscountCode::get_by_id(pool, id).await {
+            Ok(discount_code) => discount_codes.push(discount_code),
+            Err(err) => {
+                let response: ErrorResponseType = ErrorResponseType::new(
+                    ErrorType {
+                        id: Uuid::new_v4().to_string(),
+                        code: 500,
+                        error_type: "internal_server_error".to_string(),
+                        detail: err.to_string(),
+                        source: format!("/shops/{shop_id}/discount-codes"),
+                    },
+                    Some(MetadataType::new(None::<PaginationType>)),
+                );
+
+                return Ok(HttpResponse::InternalServerError().json(response));
+            }
+        }
+    }
+
+    Ok(HttpResponse::Ok().json(ResponseType::new(
+        discount_codes,
+        Some(MetadataType::new(None::<PaginationType>)),
+    )))
+}
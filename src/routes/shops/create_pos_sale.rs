@@ -0,0 +1,137 @@
+use actix_web::{post, web, HttpResponse, Responder};
+use mysk_lib::models::common::{
+    requests::{FetchLevel, RequestType},
+    response::{ErrorResponseType, ErrorType, MetadataType, PaginationType, ResponseType},
+};
+use uuid::Uuid;
+
+use crate::{
+    models::{
+        auth::permission::{RequireShopRole, Staff},
+        order::{
+            pos::{CreatablePosSale, PosPaymentMethod},
+            request::{OrderCreationError, QueryableOrder, SortableOrder},
+            Order,
+        },
+    },
+    utils::email::send_receipt_email,
+    AppState,
+};
+
+// a sale at the shop's booth, answered with what goes on the printed receipt
+#[post("/shops/{shop_id}/pos/sales")]
+pub async fn create_pos_sale(
+    data: web::Data<AppState>,
+    shop_id: web::Path<Uuid>,
+    request: web::Json<RequestType<CreatablePosSale, QueryableOrder, SortableOrder>>,
+    shop_role: RequireShopRole<Staff>,
+) -> Result<impl Responder, actix_web::Error> {
+    let pool = &data.db;
+    let credential = &data.smtp_credential;
+    let payment_provider = &data.payment_provider;
+    let stock_hold = chrono::Duration::minutes(data.env.stock_hold_minutes);
+    let shop_id = shop_id.into_inner();
+    let source = format!("/shops/{shop_id}/pos/sales");
+
+    let sale = match &request.data {
+        Some(data) => data,
+        None => {
+            let response: ErrorResponseType = ErrorResponseType::new(
+                ErrorType {
+                    id: Uuid::new_v4().to_string(),
+                    code: 400,
+                    error_type: "bad_request".to_string(),
+                    detail: "request body is empty".to_string(),
+                    source,
+                },
+                Some(MetadataType::new(None::<PaginationType>)),
+            );
+
+            return Ok(HttpResponse::BadRequest().json(response));
+        }
+    };
+
+    if let Err(e) = sale.validate() {
+        let response: ErrorResponseType = ErrorResponseType::new(
+            ErrorType {
+                id: Uuid::new_v4().to_string(),
+                code: 400,
+                error_type: "bad_request".to_string(),
+                detail: e,
+                source,
+            },
+            Some(MetadataType::new(None::<PaginationType>)),
+        );
+
+        return Ok(HttpResponse::BadRequest().json(response));
+    }
+
+    let res = sale
+        .insert(
+            pool,
+            payment_provider,
+            shop_id,
+            shop_role.permission.user_id,
+            stock_hold,
+        )
+        .await;
+
+    let receipt = match res {
+        Ok(receipt) => receipt,
+        Err(OrderCreationError::Database(e)) => {
+            let response: ErrorResponseType = ErrorResponseType::new(
+                ErrorType {
+                    id: Uuid::new_v4().to_string(),
+                    code: 500,
+                    error_type: "internal_server_error".to_string(),
+                    detail: e.to_string(),
+                    source,
+                },
+                None::<MetadataType>,
+            );
+
+            return Ok(HttpResponse::InternalServerError().json(response));
+        }
+        Err(e) => {
+            let response: ErrorResponseType = ErrorResponseType::new(
+                ErrorType {
+                    id: Uuid::new_v4().to_string(),
+                    code: 400,
+                    error_type: "bad_request".to_string(),
+                    detail: e.to_string(),
+                    source,
+                },
+                None::<MetadataType>,
+            );
+
+            return Ok(HttpResponse::BadRequest().json(response));
+        }
+    };
+
+    // walk-in buyers only get an email when they ask for one, and only once the sale is paid
+    let wants_receipt = sale
+        .contact_email
+        .as_ref()
+        .is_some_and(|email| !email.is_empty());
+
+    if wants_receipt && receipt.payment_method == PosPaymentMethod::Cash {
+        let order = Order::get_by_id(
+            pool,
+            receipt.order_id,
+            Some(&FetchLevel::Default),
+            Some(&FetchLevel::Compact),
+        )
+        .await;
+
+        if let Ok(order) = order {
+            if let Err(e) = send_receipt_email(credential, order) {
+                println!("Error: {}", e);
+            }
+        }
+    }
+
+    Ok(HttpResponse::Ok().json(ResponseType::new(
+        receipt,
+        Some(MetadataType::new(None::<PaginationType>)),
+    )))
+}
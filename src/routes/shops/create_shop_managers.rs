@@ -0,0 +1,113 @@
+use actix_web::{post, web, HttpResponse, Responder};
+use mysk_lib::models::common::{
+    requests::{FetchLevel, RequestType},
+    response::{ErrorResponseType, ErrorType, MetadataType, PaginationType, ResponseType},
+};
+use uuid::Uuid;
+
+use crate::{
+    models::{
+        auth::permission::{Owner, RequireShopRole},
+        shop::{
+            db::ShopManagerError,
+            request::{CreatableShopManager, QueryableShop, SortableShop},
+            ShopManager,
+        },
+    },
+    AppState,
+};
+
+#[post("/shops/{shop_id}/managers")]
+pub async fn create_shop_managers(
+    data: web::Data<AppState>,
+    shop_id: web::Path<Uuid>,
+    request: web::Json<RequestType<CreatableShopManager, QueryableShop, SortableShop>>,
+    _permission: RequireShopRole<Owner>,
+) -> Result<impl Responder, actix_web::Error> {
+    let pool = &data.db;
+    let shop_id = shop_id.into_inner();
+
+    let data = match &request.data {
+        Some(data) => data,
+        None => {
+            let response: ErrorResponseType = ErrorResponseType::new(
+                ErrorType {
+                    id: Uuid::new_v4().to_string(),
+                    code: 400,
+                    error_type: "bad_request".to_string(),
+                    detail: "request body is empty".to_string(),
+                    source: format!("/shops/{shop_id}/managers"),
+                },
+                Some(MetadataType::new(None::<PaginationType>)),
+            );
+
+            return Ok(HttpResponse::BadRequest().json(response));
+        }
+    };
+
+    let manager_id = match data.insert(pool, shop_id).await {
+        Ok(manager_id) => manager_id,
+        Err(ShopManagerError::Database(sqlx::Error::RowNotFound)) => {
+            let response: ErrorResponseType = ErrorResponseType::new(
+                ErrorType {
+                    id: Uuid::new_v4().to_string(),
+                    code: 404,
+                    error_type: "entity_not_found".to_string(),
+                    detail: format!("no user has signed in with {}", data.email),
+                    source: format!("/shops/{shop_id}/managers"),
+                },
+                None::<MetadataType>,
+            );
+
+            return Ok(HttpResponse::NotFound().json(response));
+        }
+        Err(err) => {
+            let response: ErrorResponseType = ErrorResponseType::new(
+                ErrorType {
+                    id: Uuid::new_v4().to_string(),
+                    code: 400,
+                    error_type: "bad_request".to_string(),
+                    detail: err.to_string(),
+                    source: format!("/shops/{shop_id}/managers"),
+                },
+                Some(MetadataType::new(None::<PaginationType>)),
+            );
+
+            return Ok(HttpResponse::BadRequest().json(response));
+        }
+    };
+
+    let descendant_fetch_level = match request.descendant_fetch_level.clone() {
+        Some(descendant_fetch_level) => descendant_fetch_level,
+        None => FetchLevel::Compact,
+    };
+
+    let manager = ShopManager::get_by_shop_id_and_user_id(
+        pool,
+        shop_id,
+        manager_id,
+        Some(&descendant_fetch_level),
+    )
+    .await;
+
+    match manager {
+        Ok(manager) => Ok(HttpResponse::Ok().json(ResponseType::new(
+            manager,
+            Some(MetadataType::new(None::<PaginationType>)),
+        ))),
+        Err(err) => {
+            let response: ErrorResponseType = ErrorResponseType::new(
+                ErrorType {
+                    id: Uuid::new_v4().to_string(),
+                    code: 500,
+                    error_type: "internal_server_error".to_string(),
+                    detail: err.to_string(),
+                    source: format!("/shops/{shop_id}/managers"),
+                },
+                Some(MetadataType::new(None::<PaginationType>)),
+            );
+
+            Ok(HttpResponse::InternalServerError().json(response))
+        }
+    }
+}
@@ -0,0 +1,162 @@
+use actix_web::{post, web, HttpResponse, Responder};
+use mysk_lib::models::common::{
+    requests::{FetchLevel, RequestType},
+    response::{ErrorResponseType, ErrorType, MetadataType, PaginationType, ResponseType},
+};
+use uuid::Uuid;
+
+use crate::{
+    models::{
+        auth::{permission::Role, user::User},
+        shop::{
+            request::{CreatableShop, QueryableShop, SortableShop},
+            Shop,
+        },
+    },
+    AppState,
+};
+
+#[post("/shops")]
+pub async fn create_shops(
+    data: web::Data<AppState>,
+    request: web::Json<RequestType<Vec<CreatableShop>, QueryableShop, SortableShop>>,
+    user: User,
+) -> Result<impl Responder, actix_web::Error> {
+    let pool = &data.db;
+
+    let data = match &request.data {
+        Some(data) => data,
+        None => {
+            let response: ErrorResponseType = ErrorResponseType::new(
+                ErrorType {
+                    id: Uuid::new_v4().to_string(),
+                    code: 400,
+                    error_type: "bad_request".to_string(),
+                    detail: "request body is empty".to_string(),
+                    source: "/shops".to_string(),
+                },
+                Some(MetadataType::new(None::<PaginationType>)),
+            );
+
+            return Ok(HttpResponse::BadRequest().json(response));
+        }
+    };
+
+    let user_id = match user {
+        User::IdOnly(user) => user.id,
+        User::Compact(user) => user.id,
+        User::Default(user) => user.id,
+        User::Detailed(user) => user.id,
+    };
+
+    // shops are opened by the student council, who then hand them over to their managers
+    match Role::is_platform_admin(pool, user_id).await {
+        Ok(true) => {}
+        Ok(false) => {
+            let response: ErrorResponseType = ErrorResponseType::new(
+                ErrorType {
+                    id: Uuid::new_v4().to_string(),
+                    code: 403,
+                    error_type: "forbidden".to_string(),
+                    detail: "only platform admins can create shops".to_string(),
+                    source: "/shops".to_string(),
+                },
+                None::<MetadataType>,
+            );
+
+            return Ok(HttpResponse::Forbidden().json(response));
+        }
+        Err(err) => {
+            let response: ErrorResponseType = ErrorResponseType::new(
+                ErrorType {
+                    id: Uuid::new_v4().to_string(),
+                    code: 500,
+                    error_type: "internal_server_error".to_string(),
+                    detail: err.to_string(),
+                    source: "/shops".to_string(),
+                },
+                None::<MetadataType>,
+            );
+
+            return Ok(HttpResponse::InternalServerError().json(response));
+        }
+    }
+
+    for shop in data {
+        if let Err(err) = shop.validate() {
+            let response: ErrorResponseType = ErrorResponseType::new(
+                ErrorType {
+                    id: Uuid::new_v4().to_string(),
+                    code: 400,
+                    error_type: "bad_request".to_string(),
+                    detail: err,
+                    source: "/shops".to_string(),
+                },
+                Some(MetadataType::new(None::<PaginationType>)),
+            );
+
+            return Ok(HttpResponse::BadRequest().json(response));
+        }
+    }
+
+    let shop_ids = CreatableShop::bulk_insert(data, user_id, pool).await;
+
+    let shop_ids = match shop_ids {
+        Ok(shop_ids) => shop_ids,
+        Err(err) => {
+            let response: ErrorResponseType = ErrorResponseType::new(
+                ErrorType {
+                    id: Uuid::new_v4().to_string(),
+                    code: 400,
+                    error_type: "bad_request".to_string(),
+                    detail: err.to_string(),
+                    source: "/shops".to_string(),
+                },
+                Some(MetadataType::new(None::<PaginationType>)),
+            );
+
+            return Ok(HttpResponse::BadRequest().json(response));
+        }
+    };
+
+    let fetch_level = match request.fetch_level.clone() {
+        Some(fetch_level) => fetch_level,
+        None => FetchLevel::Default,
+    };
+
+    let descendant_fetch_level = match request.descendant_fetch_level.clone() {
+        Some(descendant_fetch_level) => descendant_fetch_level,
+        None => FetchLevel::IdOnly,
+    };
+
+    let shops = Shop::get_by_ids(
+        pool,
+        shop_ids,
+        Some(&fetch_level),
+        Some(&descendant_fetch_level),
+    )
+    .await;
+
+    match shops {
+        Ok(shops) => {
+            let response: ResponseType<Vec<Shop>> =
+                ResponseType::new(shops, Some(MetadataType::new(None::<PaginationType>)));
+
+            Ok(HttpResponse::Ok().json(response))
+        }
+        Err(err) => {
+            let response: ErrorResponseType = ErrorResponseType::new(
+                ErrorType {
+                    id: Uuid::new_v4().to_string(),
+                    code: 500,
+                    error_type: "internal_server_error".to_string(),
+                    detail: err.to_string(),
+                    source: "/shops".to_string(),
+                },
+                Some(MetadataType::new(None::<PaginationType>)),
+            );
+
+            Ok(HttpResponse::InternalServerError().json(response))
+        }
+    }
+}
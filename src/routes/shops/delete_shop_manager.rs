@@ -0,0 +1,80 @@
+use actix_web::{delete, web, HttpResponse, Responder};
+use mysk_lib::models::common::response::{
+    ErrorResponseType, ErrorType, MetadataType, PaginationType,
+};
+use uuid::Uuid;
+
+use crate::{
+    models::{
+        auth::{permission::Role, user::User},
+        shop::db::{ShopManagerError, ShopManagerTable},
+    },
+    AppState,
+};
+
+#[delete("/shops/{shop_id}/managers/{user_id}")]
+pub async fn delete_shop_manager(
+    data: web::Data<AppState>,
+    path: web::Path<(Uuid, Uuid)>,
+    user: User,
+) -> Result<impl Responder, actix_web::Error> {
+    let pool = &data.db;
+    let (shop_id, manager_id) = path.into_inner();
+
+    let user_id = user.id();
+
+    // owners can remove anyone, other managers can only remove themselves
+    let is_owner = match Role::for_shop(pool, user_id, shop_id).await {
+        Ok(role) => role >= Role::ShopOwner,
+        Err(_) => false,
+    };
+
+    if !is_owner && user_id != manager_id {
+        let response: ErrorResponseType = ErrorResponseType::new(
+            ErrorType {
+                id: Uuid::new_v4().to_string(),
+                code: 403,
+                error_type: "forbidden".to_string(),
+                detail: format!("user {} is not an owner of shop {}", user_id, shop_id),
+                source: format!("/shops/{shop_id}/managers/{manager_id}"),
+            },
+            None::<MetadataType>,
+        );
+
+        return Ok(HttpResponse::Forbidden().json(response));
+    }
+
+    let res = ShopManagerTable::delete(pool, shop_id, manager_id).await;
+
+    match res {
+        Ok(_) => Ok(HttpResponse::NoContent().finish()),
+        Err(ShopManagerError::Database(sqlx::Error::RowNotFound)) => {
+            let response: ErrorResponseType = ErrorResponseType::new(
+                ErrorType {
+                    id: Uuid::new_v4().to_string(),
+                    code: 404,
+                    error_type: "entity_not_found".to_string(),
+                    detail: format!("user {} is not a manager of shop {}", manager_id, shop_id),
+                    source: format!("/shops/{shop_id}/managers/{manager_id}"),
+                },
+                None::<MetadataType>,
+            );
+
+            Ok(HttpResponse::NotFound().json(response))
+        }
+        Err(err) => {
+            let response: ErrorResponseType = ErrorResponseType::new(
+                ErrorType {
+                    id: Uuid::new_v4().to_string(),
+                    code: 400,
+                    error_type: "bad_request".to_string(),
+                    detail: err.to_string(),
+                    source: format!("/shops/{shop_id}/managers/{manager_id}"),
+                },
+                Some(MetadataType::new(None::<PaginationType>)),
+            );
+
+            Ok(HttpResponse::BadRequest().json(response))
+        }
+    }
+}
@@ -0,0 +1,73 @@
+use actix_web::{post, web, HttpResponse, Responder};
+use mysk_lib::models::common::{
+    requests::FetchLevel,
+    response::{ErrorResponseType, ErrorType, MetadataType, PaginationType, ResponseType},
+};
+use uuid::Uuid;
+
+use crate::{
+    models::{
+        auth::permission::{RequireShopRole, Staff},
+        order::{shipment::import_tracking as import_order_tracking, Order},
+    },
+    utils::email::send_shipping_email,
+    AppState,
+};
+
+// ships many orders at once from a CSV with a header of ref_id,carrier,tracking_number, sent as
+// the request body. Answered with the outcome of every line
+#[post("/shops/{shop_id}/shipments/import")]
+pub async fn import_tracking(
+    data: web::Data<AppState>,
+    shop_id: web::Path<Uuid>,
+    body: String,
+    shop_role: RequireShopRole<Staff>,
+) -> Result<impl Responder, actix_web::Error> {
+    let pool = &data.db;
+    let credential = &data.smtp_credential;
+    let shop_id = shop_id.into_inner();
+    let source = format!("/shops/{shop_id}/shipments/import");
+
+    if body.trim().is_empty() {
+        let response: ErrorResponseType = ErrorResponseType::new(
+            ErrorType {
+                id: Uuid::new_v4().to_string(),
+                code: 400,
+                error_type: "bad_request".to_string(),
+                detail: "request body is empty".to_string(),
+                source,
+            },
+            Some(MetadataType::new(None::<PaginationType>)),
+        );
+
+        return Ok(HttpResponse::BadRequest().json(response));
+    }
+
+    let results = import_order_tracking(pool, shop_id, &body, shop_role.permission.user_id).await;
+
+    for result in results.iter().filter(|result| result.error.is_none()) {
+        let order_id = match result.order_id {
+            Some(order_id) => order_id,
+            None => continue,
+        };
+
+        let order = Order::get_by_id(
+            pool,
+            order_id,
+            Some(&FetchLevel::Default),
+            Some(&FetchLevel::Compact),
+        )
+        .await;
+
+        if let Ok(order) = order {
+            if let Err(e) = send_shipping_email(credential, order) {
+                println!("Error: {}", e);
+            }
+        }
+    }
+
+    Ok(HttpResponse::Ok().json(ResponseType::new(
+        results,
+        Some(MetadataType::new(None::<PaginationType>)),
+    )))
+}
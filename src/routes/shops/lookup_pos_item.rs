@@ -0,0 +1,95 @@
+use actix_web::{get, web, HttpResponse, Responder};
+use mysk_lib::models::common::{
+    requests::FetchLevel,
+    response::{ErrorResponseType, ErrorType, MetadataType, PaginationType, ResponseType},
+};
+use serde::Deserialize;
+use uuid::Uuid;
+
+use crate::{
+    models::{
+        auth::permission::{RequireShopRole, Staff},
+        item::Item,
+        order::pos::find_pos_item,
+    },
+    AppState,
+};
+
+#[derive(Debug, Deserialize)]
+pub struct PosItemQuery {
+    // a scanned barcode or an item id
+    code: String,
+}
+
+#[get("/shops/{shop_id}/pos/items")]
+pub async fn lookup_pos_item(
+    data: web::Data<AppState>,
+    shop_id: web::Path<Uuid>,
+    query: web::Query<PosItemQuery>,
+    _permission: RequireShopRole<Staff>,
+) -> Result<impl Responder, actix_web::Error> {
+    let pool = &data.db;
+    let shop_id = shop_id.into_inner();
+    let source = format!("/shops/{shop_id}/pos/items");
+
+    let item_id = match find_pos_item(pool, shop_id, query.code.trim()).await {
+        Ok(Some(item_id)) => item_id,
+        Ok(None) => {
+            let response: ErrorResponseType = ErrorResponseType::new(
+                ErrorType {
+                    id: Uuid::new_v4().to_string(),
+                    code: 404,
+                    error_type: "entity_not_found".to_string(),
+                    detail: format!("no item of this shop matches {}", query.code),
+                    source,
+                },
+                None::<MetadataType>,
+            );
+
+            return Ok(HttpResponse::NotFound().json(response));
+        }
+        Err(e) => {
+            let response: ErrorResponseType = ErrorResponseType::new(
+                ErrorType {
+                    id: Uuid::new_v4().to_string(),
+                    code: 500,
+                    error_type: "internal_server_error".to_string(),
+                    detail: e.to_string(),
+                    source,
+                },
+                None::<MetadataType>,
+            );
+
+            return Ok(HttpResponse::InternalServerError().json(response));
+        }
+    };
+
+    let item = Item::get_by_id(
+        pool,
+        item_id,
+        Some(&FetchLevel::Default),
+        Some(&FetchLevel::IdOnly),
+    )
+    .await;
+
+    match item {
+        Ok(item) => Ok(HttpResponse::Ok().json(ResponseType::new(
+            item,
+            Some(MetadataType::new(None::<PaginationType>)),
+        ))),
+        Err(e) => {
+            let response: ErrorResponseType = ErrorResponseType::new(
+                ErrorType {
+                    id: Uuid::new_v4().to_string(),
+                    code: 500,
+                    error_type: "internal_server_error".to_string(),
+                    detail: e.to_string(),
+                    source,
+                },
+                None::<MetadataType>,
+            );
+
+            Ok(HttpResponse::InternalServerError().json(response))
+        }
+    }
+}
@@ -1,3 +1,18 @@
+pub(crate) mod create_discount_codes;
+pub(crate) mod create_pos_sale;
+pub(crate) mod create_shop_managers;
+pub(crate) mod create_shops;
+pub(crate) mod delete_shop_manager;
+pub(crate) mod import_tracking;
+pub(crate) mod lookup_pos_item;
+pub(crate) mod query_cash_drawer;
+pub(crate) mod query_discount_codes;
+pub(crate) mod query_low_stock_items;
+pub(crate) mod query_payment_slips;
+pub(crate) mod query_shop_managers;
 pub(crate) mod query_shops;
+pub(crate) mod review_payment_slip;
+pub(crate) mod scan_pickup;
 pub(crate) mod shop_detail;
+pub(crate) mod update_discount_code;
 pub(crate) mod update_shop_by_id;
@@ -0,0 +1,58 @@
+use actix_web::{get, web, HttpResponse, Responder};
+use chrono::NaiveDate;
+use mysk_lib::models::common::response::{
+    ErrorResponseType, ErrorType, MetadataType, PaginationType, ResponseType,
+};
+use serde::Deserialize;
+use uuid::Uuid;
+
+use crate::{
+    models::{
+        auth::permission::{RequireShopRole, Staff},
+        order::pos::CashDrawerSummary,
+    },
+    AppState,
+};
+
+#[derive(Debug, Deserialize)]
+pub struct CashDrawerQuery {
+    // defaults to today in Bangkok
+    date: Option<NaiveDate>,
+}
+
+#[get("/shops/{shop_id}/pos/cash-drawer")]
+pub async fn query_cash_drawer(
+    data: web::Data<AppState>,
+    shop_id: web::Path<Uuid>,
+    query: web::Query<CashDrawerQuery>,
+    _permission: RequireShopRole<Staff>,
+) -> Result<impl Responder, actix_web::Error> {
+    let pool = &data.db;
+    let shop_id = shop_id.into_inner();
+
+    // Bangkok is UTC+7 all year round
+    let date = query
+        .date
+        .unwrap_or_else(|| (chrono::Utc::now() + chrono::Duration::hours(7)).date_naive());
+
+    match CashDrawerSummary::get(pool, shop_id, date).await {
+        Ok(summary) => Ok(HttpResponse::Ok().json(ResponseType::new(
+            summary,
+            Some(MetadataType::new(None::<PaginationType>)),
+        ))),
+        Err(e) => {
+            let response: ErrorResponseType = ErrorResponseType::new(
+                ErrorType {
+                    id: Uuid::new_v4().to_string(),
+                    code: 500,
+                    error_type: "internal_server_error".to_string(),
+                    detail: e.to_string(),
+                    source: format!("/shops/{shop_id}/pos/cash-drawer"),
+                },
+                None::<MetadataType>,
+            );
+
+            Ok(HttpResponse::InternalServerError().json(response))
+        }
+    }
+}
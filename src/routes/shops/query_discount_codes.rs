@@ -0,0 +1,46 @@
+use actix_web::{get, web, HttpResponse, Responder};
+use mysk_lib::models::common::response::{
+    ErrorResponseType, ErrorType, MetadataType, PaginationType, ResponseType,
+};
+use uuid::Uuid;
+
+use crate::{
+    models::{
+        auth::permission::{RequireShopRole, Staff},
+        discount::DiscountCode,
+    },
+    AppState,
+};
+
+#[get("/shops/{shop_id}/discount-codes")]
+pub async fn query_discount_codes(
+    data: web::Data<AppState>,
+    shop_id: web::Path<Uuid>,
+    _permission: RequireShopRole<Staff>,
+) -> Result<impl Responder, actix_web::Error> {
+    let pool = &data.db;
+    let shop_id = shop_id.into_inner();
+
+    let discount_codes = DiscountCode::get_by_shop_id(pool, shop_id).await;
+
+    match discount_codes {
+        Ok(discount_codes) => Ok(HttpResponse::Ok().json(ResponseType::new(
+            discount_codes,
+            Some(MetadataType::new(None::<PaginationType>)),
+        ))),
+        Err(e) => {
+            let response: ErrorResponseType = ErrorResponseType::new(
+                ErrorType {
+                    id: Uuid::new_v4().to_string(),
+                    code: 500,
+                    error_type: "internal_server_error".to_string(),
+                    detail: e.to_string(),
+                    source: format!("/shops/{shop_id}/discount-codes"),
+                },
+                None::<MetadataType>,
+            );
+
+            Ok(HttpResponse::InternalServerError().json(response))
+        }
+    }
+}
@@ -0,0 +1,46 @@
+use actix_web::{get, web, HttpResponse, Responder};
+use mysk_lib::models::common::response::{
+    ErrorResponseType, ErrorType, MetadataType, PaginationType, ResponseType,
+};
+use uuid::Uuid;
+
+use crate::{
+    models::{
+        auth::permission::{RequireShopRole, Staff},
+        item::low_stock::LowStockItem,
+    },
+    AppState,
+};
+
+// the shop's items that are sold out or at their low stock threshold, emptiest first
+#[get("/shops/{shop_id}/low-stock-items")]
+pub async fn query_low_stock_items(
+    data: web::Data<AppState>,
+    shop_id: web::Path<Uuid>,
+    _permission: RequireShopRole<Staff>,
+) -> Result<impl Responder, actix_web::Error> {
+    let pool = &data.db;
+    let shop_id = shop_id.into_inner();
+    let source = format!("/shops/{shop_id}/low-stock-items");
+
+    match LowStockItem::get_by_shop_id(pool, shop_id).await {
+        Ok(items) => Ok(HttpResponse::Ok().json(ResponseType::new(
+            items,
+            Some(MetadataType::new(None::<PaginationType>)),
+        ))),
+        Err(e) => {
+            let response: ErrorResponseType = ErrorResponseType::new(
+                ErrorType {
+                    id: Uuid::new_v4().to_string(),
+                    code: 500,
+                    error_type: "internal_server_error".to_string(),
+                    detail: e.to_string(),
+                    source,
+                },
+                None::<MetadataType>,
+            );
+
+            Ok(HttpResponse::InternalServerError().json(response))
+        }
+    }
+}
@@ -0,0 +1,62 @@
+use actix_web::{get, web, HttpResponse, Responder};
+use mysk_lib::models::common::response::{
+    ErrorResponseType, ErrorType, MetadataType, PaginationType, ResponseType,
+};
+use serde::Deserialize;
+use uuid::Uuid;
+
+use crate::{
+    models::{
+        auth::permission::{RequireShopRole, Staff},
+        order::slip::{PaymentSlip, SlipStatus},
+    },
+    AppState,
+};
+
+#[derive(Debug, Deserialize)]
+pub struct PaymentSlipQuery {
+    // defaults to the slips still waiting for a review
+    status: Option<SlipStatus>,
+    #[serde(default)]
+    all: bool,
+}
+
+#[get("/shops/{shop_id}/payment-slips")]
+pub async fn query_payment_slips(
+    data: web::Data<AppState>,
+    shop_id: web::Path<Uuid>,
+    query: web::Query<PaymentSlipQuery>,
+    _permission: RequireShopRole<Staff>,
+) -> Result<impl Responder, actix_web::Error> {
+    let pool = &data.db;
+    let shop_id = shop_id.into_inner();
+
+    let status = match (query.status, query.all) {
+        (Some(status), _) => Some(status),
+        (None, true) => None,
+        (None, false) => Some(SlipStatus::Pending),
+    };
+
+    let slips = PaymentSlip::get_by_shop_id(pool, shop_id, status).await;
+
+    match slips {
+        Ok(slips) => Ok(HttpResponse::Ok().json(ResponseType::new(
+            slips,
+            Some(MetadataType::new(None::<PaginationType>)),
+        ))),
+        Err(e) => {
+            let response: ErrorResponseType = ErrorResponseType::new(
+                ErrorType {
+                    id: Uuid::new_v4().to_string(),
+                    code: 500,
+                    error_type: "internal_server_error".to_string(),
+                    detail: e.to_string(),
+                    source: format!("/shops/{shop_id}/payment-slips"),
+                },
+                None::<MetadataType>,
+            );
+
+            Ok(HttpResponse::InternalServerError().json(response))
+        }
+    }
+}
@@ -0,0 +1,56 @@
+use actix_web::{get, web, HttpResponse, Responder};
+use mysk_lib::models::common::{
+    requests::{FetchLevel, RequestType},
+    response::{ErrorResponseType, ErrorType, MetadataType, PaginationType, ResponseType},
+};
+use uuid::Uuid;
+
+use crate::{
+    models::{
+        auth::permission::{RequireShopRole, Staff},
+        shop::{
+            request::{QueryableShop, SortableShop},
+            ShopManager,
+        },
+    },
+    AppState,
+};
+
+#[get("/shops/{shop_id}/managers")]
+pub async fn query_shop_managers(
+    data: web::Data<AppState>,
+    shop_id: web::Path<Uuid>,
+    request_query: web::Query<RequestType<ShopManager, QueryableShop, SortableShop>>,
+    _permission: RequireShopRole<Staff>,
+) -> Result<impl Responder, actix_web::Error> {
+    let pool = &data.db;
+    let shop_id = shop_id.into_inner();
+
+    let descendant_fetch_level = match request_query.descendant_fetch_level.clone() {
+        Some(descendant_fetch_level) => descendant_fetch_level,
+        None => FetchLevel::Compact,
+    };
+
+    let managers = ShopManager::get_by_shop_id(pool, shop_id, Some(&descendant_fetch_level)).await;
+
+    match managers {
+        Ok(managers) => Ok(HttpResponse::Ok().json(ResponseType::new(
+            managers,
+            Some(MetadataType::new(None::<PaginationType>)),
+        ))),
+        Err(e) => {
+            let response: ErrorResponseType = ErrorResponseType::new(
+                ErrorType {
+                    id: Uuid::new_v4().to_string(),
+                    code: 404,
+                    error_type: "entity_not_found".to_string(),
+                    detail: e.to_string(),
+                    source: format!("/shops/{shop_id}/managers"),
+                },
+                None::<MetadataType>,
+            );
+
+            Ok(HttpResponse::NotFound().json(response))
+        }
+    }
+}
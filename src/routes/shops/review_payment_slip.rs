@@ -0,0 +1,200 @@
+use actix_web::{patch, web, HttpResponse, Responder};
+use mysk_lib::models::common::{
+    requests::{FetchLevel, RequestType},
+    response::{ErrorResponseType, ErrorType, MetadataType, PaginationType, ResponseType},
+};
+use serde::Deserialize;
+use uuid::Uuid;
+
+use crate::{
+    models::{
+        auth::permission::{RequireShopRole, Staff},
+        order::{
+            request::{QueryableOrder, SortableOrder},
+            slip::{review_slip, SlipReviewError},
+            status::OrderStatusError,
+            Order,
+        },
+    },
+    utils::email::{send_receipt_email, send_slip_rejected_email},
+    AppState,
+};
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum SlipDecision {
+    Approve,
+    Reject,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct SlipReview {
+    decision: SlipDecision,
+    // sent to the buyer, required when rejecting
+    reason: Option<String>,
+}
+
+#[patch("/shops/{shop_id}/payment-slips/{slip_id}")]
+pub async fn review_payment_slip(
+    data: web::Data<AppState>,
+    path: web::Path<(Uuid, Uuid)>,
+    request: web::Json<RequestType<SlipReview, QueryableOrder, SortableOrder>>,
+    shop_role: RequireShopRole<Staff>,
+) -> Result<impl Responder, actix_web::Error> {
+    let pool = &data.db;
+    let credential = &data.smtp_credential;
+    let stock_hold = chrono::Duration::minutes(data.env.stock_hold_minutes);
+    let (shop_id, slip_id) = path.into_inner();
+    let source = format!("/shops/{shop_id}/payment-slips/{slip_id}");
+
+    let review = match &request.data {
+        Some(data) => data,
+        None => {
+            let response: ErrorResponseType = ErrorResponseType::new(
+                ErrorType {
+                    id: Uuid::new_v4().to_string(),
+                    code: 400,
+                    error_type: "bad_request".to_string(),
+                    detail: "request body is empty".to_string(),
+                    source,
+                },
+                Some(MetadataType::new(None::<PaginationType>)),
+            );
+
+            return Ok(HttpResponse::BadRequest().json(response));
+        }
+    };
+
+    let rejection_reason = match (&review.decision, review.reason.as_deref().map(str::trim)) {
+        (SlipDecision::Approve, _) => None,
+        (SlipDecision::Reject, Some(reason)) if !reason.is_empty() => Some(reason),
+        (SlipDecision::Reject, _) => {
+            let response: ErrorResponseType = ErrorResponseType::new(
+                ErrorType {
+                    id: Uuid::new_v4().to_string(),
+                    code: 400,
+                    error_type: "bad_request".to_string(),
+                    detail: "a reason is required to reject a slip".to_string(),
+                    source,
+                },
+                Some(MetadataType::new(None::<PaginationType>)),
+            );
+
+            return Ok(HttpResponse::BadRequest().json(response));
+        }
+    };
+
+    let res = review_slip(
+        pool,
+        shop_id,
+        slip_id,
+        shop_role.permission.user_id,
+        rejection_reason,
+        stock_hold,
+    )
+    .await;
+
+    let order_id = match res {
+        Ok(order_id) => order_id,
+        Err(SlipReviewError::Status(OrderStatusError::Database(sqlx::Error::RowNotFound))) => {
+            let response: ErrorResponseType = ErrorResponseType::new(
+                ErrorType {
+                    id: Uuid::new_v4().to_string(),
+                    code: 404,
+                    error_type: "entity_not_found".to_string(),
+                    detail: format!("payment slip {} not found", slip_id),
+                    source,
+                },
+                None::<MetadataType>,
+            );
+
+            return Ok(HttpResponse::NotFound().json(response));
+        }
+        Err(SlipReviewError::Status(OrderStatusError::Database(e))) => {
+            let response: ErrorResponseType = ErrorResponseType::new(
+                ErrorType {
+                    id: Uuid::new_v4().to_string(),
+                    code: 500,
+                    error_type: "internal_server_error".to_string(),
+                    detail: e.to_string(),
+                    source,
+                },
+                None::<MetadataType>,
+            );
+
+            return Ok(HttpResponse::InternalServerError().json(response));
+        }
+        Err(e) => {
+            let response: ErrorResponseType = ErrorResponseType::new(
+                ErrorType {
+                    id: Uuid::new_v4().to_string(),
+                    code: 400,
+                    error_type: "bad_request".to_string(),
+                    detail: e.to_string(),
+                    source,
+                },
+                None::<MetadataType>,
+            );
+
+            return Ok(HttpResponse::BadRequest().json(response));
+        }
+    };
+
+    let order = Order::get_by_id(
+        pool,
+        order_id,
+        Some(&FetchLevel::Default),
+        Some(&FetchLevel::Compact),
+    )
+    .await;
+
+    if let Ok(order) = order {
+        let res = match rejection_reason {
+            None => send_receipt_email(credential, order),
+            Some(reason) => send_slip_rejected_email(credential, order, reason),
+        };
+
+        if let Err(e) = res {
+            println!("Error: {}", e);
+        }
+    }
+
+    let fetch_level = match request.fetch_level.clone() {
+        Some(fetch_level) => fetch_level,
+        None => FetchLevel::Default,
+    };
+
+    let descendant_fetch_level = match request.descendant_fetch_level.clone() {
+        Some(descendant_fetch_level) => descendant_fetch_level,
+        None => FetchLevel::IdOnly,
+    };
+
+    let order = Order::get_by_id(
+        pool,
+        order_id,
+        Some(&fetch_level),
+        Some(&descendant_fetch_level),
+    )
+    .await;
+
+    match order {
+        Ok(order) => Ok(HttpResponse::Ok().json(ResponseType::new(
+            order,
+            Some(MetadataType::new(None::<PaginationType>)),
+        ))),
+        Err(e) => {
+            let response: ErrorResponseType = ErrorResponseType::new(
+                ErrorType {
+                    id: Uuid::new_v4().to_string(),
+                    code: 500,
+                    error_type: "internal_server_error".to_string(),
+                    detail: e.to_string(),
+                    source,
+                },
+                None::<MetadataType>,
+            );
+
+            Ok(HttpResponse::InternalServerError().json(response))
+        }
+    }
+}
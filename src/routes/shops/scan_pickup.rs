@@ -0,0 +1,153 @@
+use actix_web::{post, web, HttpResponse, Responder};
+use mysk_lib::models::common::{
+    requests::{FetchLevel, RequestType},
+    response::{ErrorResponseType, ErrorType, MetadataType, PaginationType, ResponseType},
+};
+use serde::Deserialize;
+use uuid::Uuid;
+
+use crate::{
+    models::{
+        auth::permission::{RequireShopRole, Staff},
+        order::{
+            pickup::{hand_over_pickup, PickupError},
+            request::{QueryableOrder, SortableOrder},
+            status::OrderStatusError,
+            Order,
+        },
+    },
+    AppState,
+};
+
+#[derive(Debug, Deserialize)]
+pub struct PickupScan {
+    // scanned from the buyer's QR code or typed in
+    pickup_code: String,
+    note: Option<String>,
+}
+
+// hands a school pickup order over at the booth, answered with the order so the staff can check
+// the items before giving them out
+#[post("/shops/{shop_id}/pickups")]
+pub async fn scan_pickup(
+    data: web::Data<AppState>,
+    shop_id: web::Path<Uuid>,
+    request: web::Json<RequestType<PickupScan, QueryableOrder, SortableOrder>>,
+    shop_role: RequireShopRole<Staff>,
+) -> Result<impl Responder, actix_web::Error> {
+    let pool = &data.db;
+    let shop_id = shop_id.into_inner();
+    let source = format!("/shops/{shop_id}/pickups");
+
+    let scan = match &request.data {
+        Some(data) => data,
+        None => {
+            let response: ErrorResponseType = ErrorResponseType::new(
+                ErrorType {
+                    id: Uuid::new_v4().to_string(),
+                    code: 400,
+                    error_type: "bad_request".to_string(),
+                    detail: "request body is empty".to_string(),
+                    source,
+                },
+                Some(MetadataType::new(None::<PaginationType>)),
+            );
+
+            return Ok(HttpResponse::BadRequest().json(response));
+        }
+    };
+
+    let res = hand_over_pickup(
+        pool,
+        shop_id,
+        &scan.pickup_code,
+        shop_role.permission.user_id,
+        scan.note.clone(),
+    )
+    .await;
+
+    let order_id = match res {
+        Ok(order_id) => order_id,
+        Err(PickupError::NotFound) => {
+            let response: ErrorResponseType = ErrorResponseType::new(
+                ErrorType {
+                    id: Uuid::new_v4().to_string(),
+                    code: 404,
+                    error_type: "not_found".to_string(),
+                    detail: PickupError::NotFound.to_string(),
+                    source,
+                },
+                None::<MetadataType>,
+            );
+
+            return Ok(HttpResponse::NotFound().json(response));
+        }
+        Err(PickupError::Status(OrderStatusError::Database(e))) => {
+            let response: ErrorResponseType = ErrorResponseType::new(
+                ErrorType {
+                    id: Uuid::new_v4().to_string(),
+                    code: 500,
+                    error_type: "internal_server_error".to_string(),
+                    detail: e.to_string(),
+                    source,
+                },
+                None::<MetadataType>,
+            );
+
+            return Ok(HttpResponse::InternalServerError().json(response));
+        }
+        Err(e) => {
+            let response: ErrorResponseType = ErrorResponseType::new(
+                ErrorType {
+                    id: Uuid::new_v4().to_string(),
+                    code: 400,
+                    error_type: "bad_request".to_string(),
+                    detail: e.to_string(),
+                    source,
+                },
+                None::<MetadataType>,
+            );
+
+            return Ok(HttpResponse::BadRequest().json(response));
+        }
+    };
+
+    let fetch_level = match request.fetch_level.clone() {
+        Some(fetch_level) => fetch_level,
+        None => FetchLevel::Default,
+    };
+
+    let descendant_fetch_level = match request.descendant_fetch_level.clone() {
+        Some(descendant_fetch_level) => descendant_fetch_level,
+        None => FetchLevel::Compact,
+    };
+
+    let order = Order::get_by_id(
+        pool,
+        order_id,
+        Some(&fetch_level),
+        Some(&descendant_fetch_level),
+    )
+    .await;
+
+    match order {
+        Ok(order) => Ok(HttpResponse::Ok().json(ResponseType::new(
+            order,
+            Some(MetadataType::new(None::<PaginationType>)),
+        ))),
+        Err(e) => {
+            let response: ErrorResponseType = ErrorResponseType::new(
+                ErrorType {
+                    id: Uuid::new_v4().to_string(),
+                    code: 500,
+                    error_type: "internal_server_error".to_string(),
+                    detail: e.to_string(),
+                    source,
+                },
+                None::<MetadataType>,
+            );
+
+            Ok(HttpResponse::InternalServerError().json(response))
+        }
+    }
+}
@@ -0,0 +1,116 @@
+use actix_web::{patch, web, HttpResponse, Responder};
+use mysk_lib::models::common::{
+    requests::RequestType,
+    response::{ErrorResponseType, ErrorType, MetadataType, PaginationType, ResponseType},
+};
+use uuid::Uuid;
+
+use crate::{
+    models::{
+        auth::permission::{RequireShopRole, Staff},
+        discount::{request::UpdatableDiscountCode, DiscountCode},
+        shop::request::{QueryableShop, SortableShop},
+    },
+    AppState,
+};
+
+#[patch("/shops/{shop_id}/discount-codes/{discount_code_id}")]
+pub async fn update_discount_code(
+    data: web::Data<AppState>,
+    path: web::Path<(Uuid, Uuid)>,
+    request: web::Json<RequestType<UpdatableDiscountCode, QueryableShop, SortableShop>>,
+    _permission: RequireShopRole<Staff>,
+) -> Result<impl Responder, actix_web::Error> {
+    let pool = &data.db;
+    let (shop_id, discount_code_id) = path.into_inner();
+    let source = format!("/shops/{shop_id}/discount-codes/{discount_code_id}");
+
+    let data = match &request.data {
+        Some(data) => data,
+        None => {
+            let response: ErrorResponseType = ErrorResponseType::new(
+                ErrorType {
+                    id: Uuid::new_v4().to_string(),
+                    code: 400,
+                    error_type: "bad_request".to_string(),
+                    detail: "request body is empty".to_string(),
+                    source,
+                },
+                Some(MetadataType::new(None::<PaginationType>)),
+            );
+
+            return Ok(HttpResponse::BadRequest().json(response));
+        }
+    };
+
+    if let Err(err) = data.validate() {
+        let response: ErrorResponseType = ErrorResponseType::new(
+            ErrorType {
+                id: Uuid::new_v4().to_string(),
+                code: 400,
+                error_type: "bad_request".to_string(),
+                detail: err,
+                source,
+            },
+            Some(MetadataType::new(None::<PaginationType>)),
+        );
+
+        return Ok(HttpResponse::BadRequest().json(response));
+    }
+
+    match data.commit_changes(pool, shop_id, discount_code_id).await {
+        Ok(_) => {}
+        Err(sqlx::Error::RowNotFound) => {
+            let response: ErrorResponseType = ErrorResponseType::new(
+                ErrorType {
+                    id: Uuid::new_v4().to_string(),
+                    code: 404,
+                    error_type: "entity_not_found".to_string(),
+                    detail: format!(
+                        "discount code {} not found in shop {}",
+                        discount_code_id, shop_id
+                    ),
+                    source,
+                },
+                None::<MetadataType>,
+            );
+
+            return Ok(HttpResponse::NotFound().json(response));
+        }
+        Err(err) => {
+            let response: ErrorResponseType = ErrorResponseType::new(
+                ErrorType {
+                    id: Uuid::new_v4().to_string(),
+                    code: 400,
+                    error_type: "bad_request".to_string(),
+                    detail: err.to_string(),
+                    source,
+                },
+                Some(MetadataType::new(None::<PaginationType>)),
+            );
+
+            return Ok(HttpResponse::BadRequest().json(response));
+        }
+    }
+
+    match DiscountCode::get_by_id(pool, discount_code_id).await {
+        Ok(discount_code) => Ok(HttpResponse::Ok().json(ResponseType::new(
+            discount_code,
+            Some(MetadataType::new(None::<PaginationType>)),
+        ))),
+        Err(err) => {
+            let response: ErrorResponseType = ErrorResponseType::new(
+                ErrorType {
+                    id: Uuid::new_v4().to_string(),
+                    code: 500,
+                    error_type: "internal_server_error".to_string(),
+                    detail: err.to_string(),
+                    source,
+                },
+                None::<MetadataType>,
+            );
+
+            Ok(HttpResponse::InternalServerError().json(response))
+        }
+    }
+}
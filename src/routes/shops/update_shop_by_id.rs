@@ -3,12 +3,11 @@ use mysk_lib::models::common::{
     requests::{FetchLevel, RequestType},
     response::{ErrorResponseType, ErrorType, MetadataType, PaginationType, ResponseType},
 };
-use sqlx::Row;
 use uuid::Uuid;
 
 use crate::{
     models::{
-        auth::user::User,
+        auth::permission::{RequireShopRole, Owner},
         shop::{
             request::{QueryableShop, SortableShop, UpdatableShop},
             Shop,
@@ -22,7 +21,7 @@ pub async fn update_shop_by_id(
     data: web::Data<AppState>,
     shop_id: web::Path<Uuid>,
     request: web::Json<RequestType<UpdatableShop, QueryableShop, SortableShop>>,
-    user: User,
+    _permission: RequireShopRole<Owner>,
 ) -> Result<impl Responder, actix_web::Error> {
     let pool = &data.db;
     let shop_id = shop_id.into_inner();
@@ -45,42 +44,23 @@ pub async fn update_shop_by_id(
         }
     };
 
-    let user_id = match user {
-        User::IdOnly(user) => user.id,
-        User::Compact(user) => user.id,
-        User::Default(user) => user.id,
-        User::Detailed(user) => user.id,
-    };
-
-    let res = sqlx::query(
-        r#"
-        SELECT COUNT(id) FROM shop_managers WHERE user_id = $1 AND shop_id = $2
-        "#,
-    )
-    .bind(user_id)
-    .bind(shop_id)
-    .fetch_one(pool)
-    .await;
+    // dbg!(data);
 
-    if let Ok(res) = res {
-        if res.get::<Option<i64>, _>("count").unwrap_or(0) == 0 {
-            let response: ErrorResponseType = ErrorResponseType::new(
-                ErrorType {
-                    id: Uuid::new_v4().to_string(),
-                    code: 403,
-                    error_type: "forbidden".to_string(),
-                    detail: "the user is not the shop manager".to_string(),
-                    source: format!("/shops/{shop_id}"),
-                },
-                None::<MetadataType>,
-            );
+    if let Err(err) = data.validate() {
+        let response: ErrorResponseType = ErrorResponseType::new(
+            ErrorType {
+                id: Uuid::new_v4().to_string(),
+                code: 400,
+                error_type: "bad_request".to_string(),
+                detail: err,
+                source: format!("/shops/{shop_id}"),
+            },
+            Some(MetadataType::new(None::<PaginationType>)),
+        );
 
-            return Ok(HttpResponse::Forbidden().json(response));
-        }
+        return Ok(HttpResponse::BadRequest().json(response));
     }
 
-    // dbg!(data);
-
     let res = data.commit_changes(pool, shop_id).await;
 
     if res.is_err() {
@@ -1,6 +1,7 @@
 #[derive(Debug, Clone)]
 pub struct Config {
     pub client_origin: String,
+    pub api_base_url: String,
     pub jwt_secret: String,
     pub jwt_expires_in: String,
     pub jwt_max_age: i64,
@@ -10,11 +11,22 @@ pub struct Config {
     pub google_email_user: String,
     pub google_email_password: String,
     pub gbprimepay_token: String,
+    pub gbprimepay_secret_key: String,
+    pub gbprimepay_base_url: String,
+    pub gbprimepay_callback_url: String,
+    pub payment_provider: String,
+    pub stock_hold_minutes: i64,
+    pub reservation_sweep_interval_seconds: u64,
+    pub idempotency_window_hours: i64,
+    pub stock_alert_interval_seconds: u64,
 }
 
 impl Config {
     pub fn init() -> Config {
         let client_origin = std::env::var("CLIENT_ORIGIN").expect("CLIENT_ORIGIN must be set");
+        // where this API is reachable from the links in emails
+        let api_base_url = std::env::var("API_BASE_URL")
+            .unwrap_or_else(|_| "https://api.shopping.skkornor.org".to_string());
         let jwt_secret = std::env::var("JWT_SECRET").expect("JWT_SECRET must be set");
         let jwt_expires_in =
             std::env::var("TOKEN_EXPIRED_IN").expect("TOKEN_EXPIRED_IN must be set");
@@ -31,11 +43,41 @@ impl Config {
         let google_email_password =
             std::env::var("GOOGLE_EMAIL_PASSWORD").expect("GOOGLE_EMAIL_PASSWORD must be set");
 
-        let gbprimepay_token =
-            std::env::var("GBPRIMEPAY_TOKEN").expect("GBPRIMEPAY_TOKEN must be set");
+        // gbprimepay, or mock to take payments without a network
+        let payment_provider =
+            std::env::var("PAYMENT_PROVIDER").unwrap_or_else(|_| "gbprimepay".to_string());
+
+        // the gateway's keys are only needed when payments go through it
+        let gbprimepay_var = |name: &str| match payment_provider.as_str() {
+            "gbprimepay" => std::env::var(name).unwrap_or_else(|_| panic!("{} must be set", name)),
+            _ => std::env::var(name).unwrap_or_default(),
+        };
+
+        let gbprimepay_token = gbprimepay_var("GBPRIMEPAY_TOKEN");
+        // used to look up payments when a webhook arrives
+        let gbprimepay_secret_key = gbprimepay_var("GBPRIMEPAY_SECRET_KEY");
+        let gbprimepay_base_url = std::env::var("GBPRIMEPAY_BASE_URL")
+            .unwrap_or_else(|_| "https://api.gbprimepay.com".to_string());
+        let gbprimepay_callback_url = std::env::var("GBPRIMEPAY_CALLBACK_URL")
+            .unwrap_or_else(|_| "https://api.shopping.skkornor.org/orders/webhook".to_string());
+
+        // how long an unpaid order keeps its items out of stock
+        let stock_hold_minutes =
+            std::env::var("STOCK_HOLD_MINUTES").unwrap_or_else(|_| "15".to_string());
+        let reservation_sweep_interval_seconds = std::env::var("RESERVATION_SWEEP_INTERVAL_SECONDS")
+            .unwrap_or_else(|_| "60".to_string());
+
+        // how long a response is kept for replay to retries with the same Idempotency-Key
+        let idempotency_window_hours =
+            std::env::var("IDEMPOTENCY_WINDOW_HOURS").unwrap_or_else(|_| "24".to_string());
+
+        // how often managers are told about items running out of stock
+        let stock_alert_interval_seconds = std::env::var("STOCK_ALERT_INTERVAL_SECONDS")
+            .unwrap_or_else(|_| "300".to_string());
 
         Config {
             client_origin,
+            api_base_url,
             jwt_secret,
             jwt_expires_in,
             jwt_max_age: jwt_max_age.parse::<i64>().unwrap(),
@@ -45,6 +87,28 @@ impl Config {
             google_email_user,
             google_email_password,
             gbprimepay_token,
+            gbprimepay_secret_key,
+            gbprimepay_base_url,
+            gbprimepay_callback_url,
+            payment_provider,
+            // a hold of 0 minutes would expire every order as soon as it is placed
+            stock_hold_minutes: stock_hold_minutes
+                .parse::<i64>()
+                .ok()
+                .filter(|minutes| *minutes > 0)
+                .expect("STOCK_HOLD_MINUTES must be a positive number of minutes"),
+            // the background loops can't tick every 0 seconds
+            reservation_sweep_interval_seconds: reservation_sweep_interval_seconds
+                .parse::<u64>()
+                .ok()
+                .filter(|seconds| *seconds > 0)
+                .expect("RESERVATION_SWEEP_INTERVAL_SECONDS must be a positive number of seconds"),
+            idempotency_window_hours: idempotency_window_hours.parse::<i64>().unwrap(),
+            stock_alert_interval_seconds: stock_alert_interval_seconds
+                .parse::<u64>()
+                .ok()
+                .filter(|seconds| *seconds > 0)
+                .expect("STOCK_ALERT_INTERVAL_SECONDS must be a positive number of seconds"),
         }
     }
 }
@@ -3,7 +3,16 @@ use lettre::{
     Message, SmtpTransport, Transport,
 };
 
-use crate::models::{item::Item, order::Order};
+use crate::{
+    models::{
+        item::{
+            low_stock::{LowStockItem, StockAlertLevel},
+            Item,
+        },
+        order::Order,
+    },
+    utils::promptpay,
+};
 
 pub fn send_invoice_email(credential: &Credentials, order: Order) -> Result<(), Error> {
     // let (email_address, ref_id) = match order {
@@ -31,6 +40,8 @@ pub fn send_invoice_email(credential: &Credentials, order: Order) -> Result<(),
         receiver_name,
         items,
         total_price,
+        discount_code,
+        discount_amount,
         delivery_type,
         pickup_location,
         payment_method,
@@ -47,6 +58,8 @@ pub fn send_invoice_email(credential: &Credentials, order: Order) -> Result<(),
             order.receiver_name,
             order.items,
             order.total_price,
+            order.discount_code,
+            order.discount_amount,
             order.delivery_type,
             order.pickup_location,
             order.payment_method,
@@ -57,6 +70,8 @@ pub fn send_invoice_email(credential: &Credentials, order: Order) -> Result<(),
             order.receiver_name,
             order.items,
             order.total_price,
+            order.discount_code,
+            order.discount_amount,
             order.delivery_type,
             order.pickup_location,
             order.payment_method,
@@ -86,6 +101,7 @@ pub fn send_invoice_email(credential: &Credentials, order: Order) -> Result<(),
                         {}
                     </tbody>
                 </table>
+                {}
                 <p>Total price: {}</p>
                 <p>Delivery type: {}</p>
                 <p>Pickup location: {}</p>
@@ -127,6 +143,13 @@ pub fn send_invoice_email(credential: &Credentials, order: Order) -> Result<(),
                 ))
             })
             .collect::<Result<String, Error>>()?,
+        match discount_code {
+            Some(discount_code) => format!(
+                "<p>Discount ({}): -{}</p>",
+                discount_code, discount_amount
+            ),
+            None => "".to_string(),
+        },
         total_price,
         delivery_type,
         pickup_location.unwrap_or(vec![]).join(", "),
@@ -358,3 +381,395 @@ pub fn send_receipt_email(credential: &Credentials, order: Order) -> Result<(),
         }
     }
 }
+
+// sends one html email from the student committee, failures to deliver are only logged like the
+// invoice and receipt emails
+fn send_html_email(
+    credential: &Credentials,
+    to: String,
+    subject: String,
+    html_content: String,
+) -> Result<(), Error> {
+    let to = match to.parse() {
+        Ok(to) => to,
+        Err(_) => {
+            return Err(Error::MissingTo);
+        }
+    };
+
+    let from = match "คณะกรรมการนักเรียน <kornor@sk.ac.th>".parse() {
+        Ok(from) => from,
+        Err(_) => {
+            return Err(Error::MissingFrom);
+        }
+    };
+
+    let email = Message::builder()
+        .to(to)
+        .from(from)
+        .subject(subject)
+        .header(ContentType::TEXT_HTML)
+        .body(html_content)?;
+
+    let mailer = SmtpTransport::relay("smtp-relay.sendinblue.com")
+        .unwrap()
+        .credentials(credential.clone())
+        .build();
+
+    if let Err(e) = mailer.send(&email) {
+        println!("{:?}", e);
+    }
+
+    Ok(())
+}
+
+pub fn send_slip_rejected_email(
+    credential: &Credentials,
+    order: Order,
+    reason: &str,
+) -> Result<(), Error> {
+    let (email_address, ref_id, receiver_name, total_price) = match order {
+        Order::Default(order) => (
+            order.contact_email,
+            order.ref_id,
+            order.receiver_name,
+            order.total_price,
+        ),
+        Order::Detailed(order) => (
+            order.contact_email,
+            order.ref_id,
+            order.receiver_name,
+            order.total_price,
+        ),
+        _ => return Err(Error::MissingTo),
+    };
+
+    let html_content = format!(
+        r#"
+        <html>
+            <head>
+                <title>Payment for order {}</title>
+            </head>
+            <body>
+                <h1>We could not confirm your payment for order {}</h1>
+                <p>Dear {}</p>
+                <p>The payment slip you uploaded was not accepted: {}</p>
+                <p>Please pay {} baht and upload a new slip to keep your order.</p>
+            </body>
+        </html>
+        "#,
+        ref_id, ref_id, receiver_name, reason, total_price
+    );
+
+    send_html_email(
+        credential,
+        format!("{} <{}>", receiver_name, email_address),
+        format!("Payment for order {} was not accepted", ref_id),
+        html_content,
+    )
+}
+
+// sent for every refund, `amount` is None when an unpaid order was only canceled
+pub fn send_refund_email(
+    credential: &Credentials,
+    order: Order,
+    amount: Option<i64>,
+    reason: Option<&str>,
+) -> Result<(), Error> {
+    let (email_address, ref_id, receiver_name) = match order {
+        Order::Default(order) => (order.contact_email, order.ref_id, order.receiver_name),
+        Order::Detailed(order) => (order.contact_email, order.ref_id, order.receiver_name),
+        _ => return Err(Error::MissingTo),
+    };
+
+    let message = match amount {
+        Some(amount) => format!("We have refunded {} baht for your order.", amount),
+        None => "Your order has been canceled, nothing was charged.".to_string(),
+    };
+
+    let reason = match reason {
+        Some(reason) => format!("<p>Reason: {}</p>", reason),
+        None => String::new(),
+    };
+
+    let html_content = format!(
+        r#"
+        <html>
+            <head>
+                <title>Refund for order {}</title>
+            </head>
+            <body>
+                <h1>Order {} has been updated</h1>
+                <p>Dear {}</p>
+                <p>{}</p>
+                {}
+            </body>
+        </html>
+        "#,
+        ref_id, ref_id, receiver_name, message, reason
+    );
+
+    send_html_email(
+        credential,
+        format!("{} <{}>", receiver_name, email_address),
+        format!("Refund for order {}", ref_id),
+        html_content,
+    )
+}
+
+// sent once a school pickup order can be collected, the code is what the booth scans
+pub fn send_pickup_ready_email(credential: &Credentials, order: Order) -> Result<(), Error> {
+    let (email_address, ref_id, receiver_name, pickup_location, pickup_code) = match order {
+        Order::Default(order) => (
+            order.contact_email,
+            order.ref_id,
+            order.receiver_name,
+            order.pickup_location,
+            order.pickup_code,
+        ),
+        Order::Detailed(order) => (
+            order.contact_email,
+            order.ref_id,
+            order.receiver_name,
+            order.pickup_location,
+            order.pickup_code,
+        ),
+        _ => return Err(Error::MissingTo),
+    };
+
+    let pickup_code = match pickup_code {
+        Some(pickup_code) => pickup_code,
+        None => return Ok(()),
+    };
+
+    // the code is still readable below when the mail client hides the image
+    let qr_code = match promptpay::png_data_url(&pickup_code) {
+        Ok(qr_code) => format!(r#"<img src="{}" alt="{}" />"#, qr_code, pickup_code),
+        Err(_) => String::new(),
+    };
+
+    let html_content = format!(
+        r#"
+        <html>
+            <head>
+                <title>Order {} is ready for pickup</title>
+            </head>
+            <body>
+                <h1>Order {} is ready for pickup</h1>
+                <p>Dear {}</p>
+                <p>Show this code at the booth to collect your order.</p>
+                {}
+                <p>Pickup code: <b>{}</b></p>
+                <p>Pickup location: {}</p>
+            </body>
+        </html>
+        "#,
+        ref_id,
+        ref_id,
+        receiver_name,
+        qr_code,
+        pickup_code,
+        pickup_location.unwrap_or(vec![]).join(", "),
+    );
+
+    send_html_email(
+        credential,
+        format!("{} <{}>", receiver_name, email_address),
+        format!("Order {} is ready for pickup", ref_id),
+        html_content,
+    )
+}
+
+// sent when a delivery order is shipped, and again when its tracking is corrected
+pub fn send_shipping_email(credential: &Credentials, order: Order) -> Result<(), Error> {
+    let (email_address, ref_id, receiver_name, tracking) = match order {
+        Order::Default(order) => (
+            order.contact_email,
+            order.ref_id,
+            order.receiver_name,
+            order.tracking,
+        ),
+        Order::Detailed(order) => (
+            order.contact_email,
+            order.ref_id,
+            order.receiver_name,
+            order.tracking,
+        ),
+        _ => return Err(Error::MissingTo),
+    };
+
+    let tracking = match tracking {
+        Some(tracking) => tracking,
+        None => return Ok(()),
+    };
+
+    let tracking_link = match tracking.tracking_url {
+        Some(tracking_url) => format!(r#"<p><a href="{}">Track your parcel</a></p>"#, tracking_url),
+        None => String::new(),
+    };
+
+    let html_content = format!(
+        r#"
+        <html>
+            <head>
+                <title>Order {} has been shipped</title>
+            </head>
+            <body>
+                <h1>Order {} has been shipped</h1>
+                <p>Dear {}</p>
+                <p>Your order is on its way.</p>
+                <p>Carrier: {}</p>
+                <p>Tracking number: {}</p>
+                {}
+            </body>
+        </html>
+        "#,
+        ref_id, ref_id, receiver_name, tracking.carrier, tracking.tracking_number, tracking_link
+    );
+
+    send_html_email(
+        credential,
+        format!("{} <{}>", receiver_name, email_address),
+        format!("Order {} has been shipped", ref_id),
+        html_content,
+    )
+}
+
+// sent to every manager of the shop, listing the items that ran low or sold out since last time
+pub fn send_low_stock_email(
+    credential: &Credentials,
+    managers: &[String],
+    shop_name: &str,
+    items: &[&LowStockItem],
+) -> Result<(), Error> {
+    let rows = items
+        .iter()
+        .map(|item| {
+            let name = match &item.variant_name {
+                Some(variant_name) => format!("{} ({})", item.name, variant_name),
+                None => item.name.clone(),
+            };
+
+            format!(
+                r#"
+                <tr>
+                    <td>{}</td>
+                    <td>{}</td>
+                </tr>
+                "#,
+                name,
+                match item.level {
+                    StockAlertLevel::SoldOut => "sold out".to_string(),
+                    StockAlertLevel::Low => format!("{} left", item.available),
+                }
+            )
+        })
+        .collect::<String>();
+
+    let html_content = format!(
+        r#"
+        <html>
+            <head>
+                <title>Items running out at {}</title>
+            </head>
+            <body>
+                <h1>Items running out at {}</h1>
+                <p>These items are low on stock or sold out. Restock them so buyers can keep
+                ordering.</p>
+                <table>
+                    <thead>
+                        <tr>
+                            <th>Item</th>
+                            <th>Stock</th>
+                        </tr>
+                    </thead>
+                    <tbody>
+                        {}
+                    </tbody>
+                </table>
+            </body>
+        </html>
+        "#,
+        shop_name, shop_name, rows
+    );
+
+    for manager in managers {
+        send_html_email(
+            credential,
+            manager.clone(),
+            format!("Items running out at {}", shop_name),
+            html_content.clone(),
+        )?;
+    }
+
+    Ok(())
+}
+
+// sent to each buyer whose preorder of the item is covered by a batch that just arrived
+pub fn send_preorder_arrived_email(
+    credential: &Credentials,
+    order: Order,
+    item_name: &str,
+) -> Result<(), Error> {
+    let (email_address, ref_id, receiver_name) = match order {
+        Order::Default(order) => (order.contact_email, order.ref_id, order.receiver_name),
+        Order::Detailed(order) => (order.contact_email, order.ref_id, order.receiver_name),
+        _ => return Err(Error::MissingTo),
+    };
+
+    let html_content = format!(
+        r#"
+        <html>
+            <head>
+                <title>Your preorder of {} has arrived</title>
+            </head>
+            <body>
+                <h1>Your preorder of {} has arrived</h1>
+                <p>Dear {}</p>
+                <p>The {} you preordered in order {} has arrived at the shop. We will let you
+                know once your order is on its way or ready for pickup.</p>
+            </body>
+        </html>
+        "#,
+        item_name, item_name, receiver_name, item_name, ref_id
+    );
+
+    send_html_email(
+        credential,
+        format!("{} <{}>", receiver_name, email_address),
+        format!("Your preorder of {} has arrived", item_name),
+        html_content,
+    )
+}
+
+// sent to users on the waitlist of an item when it is restocked
+pub fn send_back_in_stock_email(
+    credential: &Credentials,
+    email_address: String,
+    item_name: &str,
+    unsubscribe_url: &str,
+) -> Result<(), Error> {
+    let html_content = format!(
+        r#"
+        <html>
+            <head>
+                <title>{} is back in stock</title>
+            </head>
+            <body>
+                <h1>{} is back in stock</h1>
+                <p>You asked us to let you know when {} is available again. Order soon, it may
+                sell out again.</p>
+                <p><a href="{}">Stop emailing me about this item</a></p>
+            </body>
+        </html>
+        "#,
+        item_name, item_name, item_name, unsubscribe_url
+    );
+
+    send_html_email(
+        credential,
+        email_address,
+        format!("{} is back in stock", item_name),
+        html_content,
+    )
+}
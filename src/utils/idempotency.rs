@@ -0,0 +1,414 @@
+use std::{
+    future::{ready, Future, Ready},
+    pin::Pin,
+    rc::Rc,
+};
+
+use actix_web::{
+    body::{self, BoxBody, MessageBody},
+    dev::{self, Service, ServiceRequest, ServiceResponse, Transform},
+    http::{
+        header::{self, HeaderName, HeaderValue},
+        StatusCode,
+    },
+    web, HttpResponse,
+};
+use mysk_lib::models::common::response::{ErrorResponseType, ErrorType, MetadataType};
+use sha2::{Digest, Sha256};
+use sqlx::{PgPool, Row};
+use uuid::Uuid;
+
+use crate::AppState;
+
+pub const IDEMPOTENCY_KEY: &str = "idempotency-key";
+const IDEMPOTENT_REPLAYED: &str = "idempotent-replayed";
+// how long a request may hold its key without finishing. A request the handler never answered
+// frees its key, one that is dropped halfway never gets to, so its key can be claimed again after
+// this
+const LEASE_SECONDS: i64 = 120;
+
+// replays the first response to a request that is sent again with the same Idempotency-Key
+// header, so a retry over a bad connection doesn't create a second order. Requests without the
+// header are passed through untouched. Used on a route with `wrap = "Idempotency"`
+pub struct Idempotency;
+
+impl<S, B> Transform<S, ServiceRequest> for Idempotency
+where
+    S: Service<ServiceRequest, Response = ServiceResponse<B>, Error = actix_web::Error> + 'static,
+    B: MessageBody + 'static,
+{
+    type Response = ServiceResponse<BoxBody>;
+    type Error = actix_web::Error;
+    type Transform = IdempotencyMiddleware<S>;
+    type InitError = ();
+    type Future = Ready<Result<Self::Transform, Self::InitError>>;
+
+    fn new_transform(&self, service: S) -> Self::Future {
+        ready(Ok(IdempotencyMiddleware {
+            service: Rc::new(service),
+        }))
+    }
+}
+
+pub struct IdempotencyMiddleware<S> {
+    service: Rc<S>,
+}
+
+// what is known about a key when a request arrives with it
+enum KeyState {
+    // first time the key is seen, the request goes through and its response is stored
+    Claimed,
+    // the first request with this key hasn't finished yet and is still within its lease
+    InProgress,
+    // the same key was sent with a different request
+    Mismatch,
+    Completed {
+        status_code: u16,
+        content_type: Option<String>,
+        body: Vec<u8>,
+    },
+}
+
+fn error_response(
+    status: StatusCode,
+    error_type: &str,
+    detail: String,
+    source: String,
+) -> HttpResponse {
+    let response: ErrorResponseType = ErrorResponseType::new(
+        ErrorType {
+            id: Uuid::new_v4().to_string(),
+            code: status.as_u16() as i64,
+            error_type: error_type.to_string(),
+            detail,
+            source,
+        },
+        None::<MetadataType>,
+    );
+
+    HttpResponse::build(status).json(response)
+}
+
+// the hash covers who sent the request as well as what was sent, a key reused by someone else
+// is a mismatch instead of a way to read their response
+fn request_hash(req: &ServiceRequest, body: &[u8]) -> String {
+    let mut hasher = Sha256::new();
+
+    hasher.update(req.method().as_str());
+    hasher.update(req.path());
+    hasher.update(req.query_string());
+
+    for name in [header::AUTHORIZATION, HeaderName::from_static("x-guest-cart")] {
+        hasher.update(
+            req.headers()
+                .get(name)
+                .map(|value| value.as_bytes())
+                .unwrap_or_default(),
+        );
+        hasher.update([0]);
+    }
+
+    hasher.update(body);
+
+    format!("{:x}", hasher.finalize())
+}
+
+fn bytes_to_payload(buf: web::Bytes) -> dev::Payload {
+    let (_, mut payload) = actix_http::h1::Payload::create(true);
+    payload.unread_data(buf);
+
+    dev::Payload::from(payload)
+}
+
+async fn claim_key(
+    pool: &PgPool,
+    key: &str,
+    req: &ServiceRequest,
+    request_hash: &str,
+    window: chrono::Duration,
+) -> Result<KeyState, sqlx::Error> {
+    let mut transaction = pool.begin().await?;
+
+    // keys past the window are cleared here rather than by a sweeper, an expired key is then free
+    // to be claimed again below
+    sqlx::query("DELETE FROM idempotency_keys WHERE expires_at <= NOW()")
+        .execute(transaction.as_mut())
+        .await?;
+
+    let claimed = sqlx::query(
+        r#"
+        INSERT INTO idempotency_keys (key, method, path, request_hash, expires_at)
+        VALUES ($1, $2, $3, $4, $5)
+        ON CONFLICT (key, method, path) DO NOTHING
+        "#,
+    )
+    .bind(key)
+    .bind(req.method().as_str())
+    .bind(req.path())
+    .bind(request_hash)
+    .bind(chrono::Utc::now() + window)
+    .execute(transaction.as_mut())
+    .await?
+    .rows_affected()
+        == 1;
+
+    if claimed {
+        transaction.commit().await?;
+
+        return Ok(KeyState::Claimed);
+    }
+
+    let row = sqlx::query(
+        r#"
+        SELECT
+            request_hash,
+            status_code,
+            content_type,
+            response_body,
+            locked_at <= NOW() - make_interval(secs => $4) AS is_lease_expired
+        FROM idempotency_keys
+        WHERE key = $1 AND method = $2 AND path = $3
+        FOR UPDATE
+        "#,
+    )
+    .bind(key)
+    .bind(req.method().as_str())
+    .bind(req.path())
+    .bind(LEASE_SECONDS as f64)
+    .fetch_one(transaction.as_mut())
+    .await?;
+
+    if row.get::<String, _>("request_hash") != request_hash {
+        transaction.commit().await?;
+
+        return Ok(KeyState::Mismatch);
+    }
+
+    let state = match row.get::<Option<i32>, _>("status_code") {
+        Some(status_code) => KeyState::Completed {
+            status_code: status_code as u16,
+            content_type: row.get::<Option<String>, _>("content_type"),
+            body: row
+                .get::<Option<Vec<u8>>, _>("response_body")
+                .unwrap_or_default(),
+        },
+        // the request that held the key is gone, this one takes over
+        None if row.get::<bool, _>("is_lease_expired") => {
+            sqlx::query(
+                r#"
+                UPDATE idempotency_keys SET locked_at = NOW()
+                WHERE key = $1 AND method = $2 AND path = $3
+                "#,
+            )
+            .bind(key)
+            .bind(req.method().as_str())
+            .bind(req.path())
+            .execute(transaction.as_mut())
+            .await?;
+
+            KeyState::Claimed
+        }
+        None => KeyState::InProgress,
+    };
+
+    transaction.commit().await?;
+
+    Ok(state)
+}
+
+async fn store_response(
+    pool: &PgPool,
+    key: &str,
+    method: &str,
+    path: &str,
+    response: Option<(StatusCode, Option<String>, &[u8])>,
+) -> Result<(), sqlx::Error> {
+    match response {
+        Some((status, content_type, body)) => {
+            sqlx::query(
+                r#"
+                UPDATE idempotency_keys
+                SET status_code = $1, content_type = $2, response_body = $3
+                WHERE key = $4 AND method = $5 AND path = $6
+                "#,
+            )
+            .bind(status.as_u16() as i32)
+            .bind(content_type)
+            .bind(body)
+            .bind(key)
+            .bind(method)
+            .bind(path)
+            .execute(pool)
+            .await?;
+        }
+        // the handler never answered, the key is freed so the retry is handled for real
+        None => {
+            sqlx::query(
+                "DELETE FROM idempotency_keys WHERE key = $1 AND method = $2 AND path = $3",
+            )
+            .bind(key)
+            .bind(method)
+            .bind(path)
+            .execute(pool)
+            .await?;
+        }
+    }
+
+    Ok(())
+}
+
+impl<S, B> Service<ServiceRequest> for IdempotencyMiddleware<S>
+where
+    S: Service<ServiceRequest, Response = ServiceResponse<B>, Error = actix_web::Error> + 'static,
+    B: MessageBody + 'static,
+{
+    type Response = ServiceResponse<BoxBody>;
+    type Error = actix_web::Error;
+    type Future = Pin<Box<dyn Future<Output = Result<Self::Response, Self::Error>>>>;
+
+    dev::forward_ready!(service);
+
+    fn call(&self, mut req: ServiceRequest) -> Self::Future {
+        let service = Rc::clone(&self.service);
+
+        Box::pin(async move {
+            let key = match req.headers().get(IDEMPOTENCY_KEY) {
+                Some(key) => key.to_str().unwrap_or_default().trim().to_string(),
+                None => return Ok(service.call(req).await?.map_into_boxed_body()),
+            };
+
+            let source = req.path().to_string();
+
+            if key.is_empty() || key.len() > 255 {
+                let response = error_response(
+                    StatusCode::BAD_REQUEST,
+                    "bad_request",
+                    "Idempotency-Key must be between 1 and 255 characters".to_string(),
+                    source,
+                );
+
+                return Ok(req.into_response(response));
+            }
+
+            let (pool, window) = match req.app_data::<web::Data<AppState>>() {
+                Some(state) => (
+                    state.db.clone(),
+                    chrono::Duration::hours(state.env.idempotency_window_hours),
+                ),
+                None => return Ok(service.call(req).await?.map_into_boxed_body()),
+            };
+
+            // the body is read here to be hashed, then put back for the handler
+            let body = req.extract::<web::Bytes>().await?;
+            req.set_payload(bytes_to_payload(body.clone()));
+
+            let hash = request_hash(&req, &body);
+
+            let state = match claim_key(&pool, &key, &req, &hash, window).await {
+                Ok(state) => state,
+                Err(e) => {
+                    let response = error_response(
+                        StatusCode::INTERNAL_SERVER_ERROR,
+                        "internal_server_error",
+                        e.to_string(),
+                        source,
+                    );
+
+                    return Ok(req.into_response(response));
+                }
+            };
+
+            match state {
+                KeyState::Claimed => (),
+                KeyState::InProgress => {
+                    let response = error_response(
+                        StatusCode::CONFLICT,
+                        "conflict",
+                        "a request with this Idempotency-Key is still being processed".to_string(),
+                        source,
+                    );
+
+                    return Ok(req.into_response(response));
+                }
+                KeyState::Mismatch => {
+                    let response = error_response(
+                        StatusCode::UNPROCESSABLE_ENTITY,
+                        "unprocessable_entity",
+                        "this Idempotency-Key was already used for a different request".to_string(),
+                        source,
+                    );
+
+                    return Ok(req.into_response(response));
+                }
+                KeyState::Completed {
+                    status_code,
+                    content_type,
+                    body,
+                } => {
+                    let status = StatusCode::from_u16(status_code).unwrap_or(StatusCode::OK);
+                    let mut response = HttpResponse::build(status);
+
+                    if let Some(content_type) = content_type {
+                        response.insert_header((header::CONTENT_TYPE, content_type));
+                    }
+
+                    response.insert_header((
+                        HeaderName::from_static(IDEMPOTENT_REPLAYED),
+                        HeaderValue::from_static("true"),
+                    ));
+
+                    return Ok(req.into_response(response.body(body)));
+                }
+            }
+
+            let method = req.method().as_str().to_string();
+            let path = req.path().to_string();
+
+            let res = match service.call(req).await {
+                Ok(res) => res,
+                Err(e) => {
+                    if let Err(e) = store_response(&pool, &key, &method, &path, None).await {
+                        println!("Error: {}", e);
+                    }
+
+                    return Err(e);
+                }
+            };
+
+            let (req, res) = res.into_parts();
+            let (res, body) = res.into_parts();
+
+            let status = res.status();
+            let content_type = res
+                .headers()
+                .get(header::CONTENT_TYPE)
+                .and_then(|value| value.to_str().ok())
+                .map(str::to_string);
+
+            // the handler ran and may have committed its changes, so even a server error keeps
+            // the key. Otherwise the retry could do it all a second time
+            let body = match body::to_bytes(body).await {
+                Ok(body) => body,
+                Err(e) => {
+                    let stored = Some((status, content_type, &[][..]));
+
+                    if let Err(e) = store_response(&pool, &key, &method, &path, stored).await {
+                        println!("Error: {}", e);
+                    }
+
+                    let e: Box<dyn std::error::Error> = e.into();
+
+                    return Err(actix_web::error::ErrorInternalServerError(e.to_string()));
+                }
+            };
+
+            let stored = Some((status, content_type, body.as_ref()));
+
+            if let Err(e) = store_response(&pool, &key, &method, &path, stored).await {
+                println!("Error: {}", e);
+            }
+
+            Ok(ServiceResponse::new(req, res.set_body(BoxBody::new(body))))
+        })
+    }
+}
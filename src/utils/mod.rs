@@ -1,2 +1,4 @@
 pub(crate) mod common;
 pub(crate) mod email;
+pub(crate) mod idempotency;
+pub(crate) mod promptpay;
@@ -0,0 +1,158 @@
+use std::{fmt::Display, io::Cursor};
+
+use base64::{engine::general_purpose, prelude::*};
+use image::{ImageFormat, Luma};
+use qrcode::{render::svg, EcLevel, QrCode};
+
+// application id every PromptPay merchant account starts with
+const PROMPTPAY_AID: &str = "A000000677010111";
+const QR_SIZE: u32 = 320;
+
+#[derive(Debug)]
+pub enum PromptPayError {
+    // the promptpay number is not a phone number, national id, tax id or e-wallet id
+    InvalidNumber(String),
+    Render(String),
+}
+
+impl Display for PromptPayError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::InvalidNumber(number) => write!(f, "{} is not a valid promptpay number", number),
+            Self::Render(err) => write!(f, "could not render the QR code: {}", err),
+        }
+    }
+}
+
+// one EMVCo data object, the id followed by the two digit length of its value
+fn field(id: &str, value: &str) -> String {
+    format!("{}{:02}{}", id, value.len(), value)
+}
+
+// CRC-16/CCITT-FALSE, the checksum EMVCo puts at the end of the payload
+fn crc16(data: &[u8]) -> u16 {
+    let mut crc: u16 = 0xFFFF;
+
+    for byte in data {
+        crc ^= (*byte as u16) << 8;
+
+        for _ in 0..8 {
+            crc = if crc & 0x8000 != 0 {
+                (crc << 1) ^ 0x1021
+            } else {
+                crc << 1
+            };
+        }
+    }
+
+    crc
+}
+
+fn account_field(number: &str) -> Result<String, PromptPayError> {
+    let digits = number
+        .chars()
+        .filter(|c| c.is_ascii_digit())
+        .collect::<String>();
+
+    let target = match digits.len() {
+        // phone numbers are written with the country code, padded to 13 digits
+        10 if digits.starts_with('0') => field("01", &format!("0066{}", &digits[1..])),
+        13 => field("02", &digits),
+        15 => field("03", &digits),
+        _ => return Err(PromptPayError::InvalidNumber(number.to_string())),
+    };
+
+    Ok(field("29", &format!("{}{}", field("00", PROMPTPAY_AID), target)))
+}
+
+// the text encoded in a PromptPay QR code, banking apps fill in the amount when it is given
+pub fn payload(promptpay_number: &str, amount: Option<i64>) -> Result<String, PromptPayError> {
+    let mut payload = field("00", "01");
+
+    // 11 is a QR that can be paid many times, 12 is one made for a single payment
+    payload.push_str(&field("01", if amount.is_some() { "12" } else { "11" }));
+    payload.push_str(&account_field(promptpay_number)?);
+    payload.push_str(&field("58", "TH"));
+    // ISO 4217 code of the baht
+    payload.push_str(&field("53", "764"));
+
+    if let Some(amount) = amount {
+        payload.push_str(&field("54", &format!("{}.00", amount)));
+    }
+
+    // the checksum covers its own id and length
+    payload.push_str("6304");
+    payload.push_str(&format!("{:04X}", crc16(payload.as_bytes())));
+
+    Ok(payload)
+}
+
+fn qr_code(payload: &str) -> Result<QrCode, PromptPayError> {
+    QrCode::with_error_correction_level(payload.as_bytes(), EcLevel::M)
+        .map_err(|err| PromptPayError::Render(err.to_string()))
+}
+
+pub fn render_png(payload: &str) -> Result<Vec<u8>, PromptPayError> {
+    let image = qr_code(payload)?
+        .render::<Luma<u8>>()
+        .min_dimensions(QR_SIZE, QR_SIZE)
+        .build();
+
+    let mut png = Vec::new();
+
+    image
+        .write_to(&mut Cursor::new(&mut png), ImageFormat::Png)
+        .map_err(|err| PromptPayError::Render(err.to_string()))?;
+
+    Ok(png)
+}
+
+pub fn render_svg(payload: &str) -> Result<String, PromptPayError> {
+    Ok(qr_code(payload)?
+        .render::<svg::Color>()
+        .min_dimensions(QR_SIZE, QR_SIZE)
+        .build())
+}
+
+// the same data url format GBPrimePay QR codes are stored in
+pub fn png_data_url(payload: &str) -> Result<String, PromptPayError> {
+    Ok(format!(
+        "data:image/png;base64,{}",
+        general_purpose::STANDARD.encode(render_png(payload)?)
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn crc16_matches_the_ccitt_false_check_value() {
+        assert_eq!(crc16(b"123456789"), 0x29B1);
+    }
+
+    #[test]
+    fn payload_for_a_phone_number() {
+        assert_eq!(
+            payload("000-000-0000", None).unwrap(),
+            "00020101021129370016A000000677010111011300660000000005802TH530376463048956"
+        );
+    }
+
+    #[test]
+    fn payload_with_an_amount_is_for_a_single_payment() {
+        let payload = payload("0812345678", Some(100)).unwrap();
+
+        assert!(payload.starts_with("000201010212"));
+        assert!(payload.contains("5406100.00"));
+        assert_eq!(
+            &payload[payload.len() - 4..],
+            format!("{:04X}", crc16(&payload.as_bytes()[..payload.len() - 4]))
+        );
+    }
+
+    #[test]
+    fn payload_rejects_invalid_numbers() {
+        assert!(payload("12345", None).is_err());
+    }
+}